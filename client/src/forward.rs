@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use ansi_term::Colour::Red;
+use shared::data::{answer::Answer, request::Request};
+use shared::net::dial;
+use shared::net::forward::{host_port, Direction, Spec, FORWARDED_CMD, FORWARD_CMD, LISTEN_CMD};
+use shared::net::mux::{Channel, Mux};
+use shared::net::socks;
+use shared::xerror::Error;
+use crate::remote::Remote;
+
+/// Cele przekierowań `R:` według portu, na którym nasłuchuje serwer.
+type Targets = Arc<Mutex<HashMap<u16, (String, u16)>>>;
+
+/// Przekierowania portów (polecenie `forward`), aktywne do końca sesji.
+pub struct Forwards {
+    mux: Option<Arc<Mux>>,
+    active: Vec<Spec>,
+    targets: Targets,
+    /// Czy działa wątek przyjmujący połączenia od serwera (dla `R:`).
+    accepting: bool,
+}
+
+impl Forwards {
+    pub fn new(conn: &Remote) -> Self {
+        Forwards { mux: conn.background(), active: Vec::new(), targets: Targets::default(), accepting: false }
+    }
+
+    /// `forward` - lista przekierowań, `forward <opis>` - nowe przekierowanie.
+    /// Zwraca tekst do wyświetlenia albo opis błędu.
+    pub fn command(&mut self, params: &[String]) -> Result<Vec<String>, String> {
+        let spec = match params {
+            [] => return Ok(self.active.iter().map(Spec::to_string).collect()),
+            [spec] => spec.parse::<Spec>()?,
+            _ => return Err("usage: forward [L:[addr:]port:host:port | R:[addr:]port:host:port]".into()),
+        };
+        let Some(mux) = self.mux.clone() else {
+            return Err("Server does not support port forwarding.".into());
+        };
+        let spec = match spec.direction {
+            Direction::Local => local(mux, spec).map_err(|e| e.to_string())?,
+            Direction::Remote => self.remote(mux, spec)?,
+        };
+        self.active.push(spec.clone());
+        Ok(vec![format!("forwarding {}", spec)])
+    }
+
+    /// Lokalne proxy SOCKS5: każde połączenie idzie przez serwer
+    /// (jak `L:`, z celem podanym przez klienta proxy).
+    pub fn socks(&mut self, addr: &str) -> Result<SocketAddr, String> {
+        let Some(mux) = self.mux.clone() else {
+            return Err("Server does not support port forwarding.".into());
+        };
+        let listener = TcpListener::bind(addr).map_err(|e| format!("can't listen on {}: {}", addr, e))?;
+        let addr = listener.local_addr().map_err(|e| e.to_string())?;
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mux = mux.clone();
+                thread::spawn(move || {
+                    if let Err(err) = stream.and_then(|stream| socks::serve(&mux, stream)) {
+                        eprintln!("{}", Red.paint(format!("socks: {}", err)));
+                    }
+                });
+            }
+        });
+        Ok(addr)
+    }
+
+    /// `R:` - serwer nasłuchuje, połączenia przychodzą do nas w nowych kanałach.
+    fn remote(&mut self, mux: Arc<Mux>, mut spec: Spec) -> Result<Spec, String> {
+        let request = Request::new(LISTEN_CMD.into(), vec![spec.bind.clone(), spec.port.to_string()]);
+        let answer = mux.request(request).map_err(|e| e.to_string())?;
+        if answer.code != 0 {
+            return Err(Error::from(answer).msg);
+        }
+        // Port 0: serwer wybrał port sam.
+        let addr: SocketAddr = answer.data.first()
+            .and_then(|addr| addr.parse().ok())
+            .ok_or("invalid answer from server")?;
+        spec.port = addr.port();
+        self.targets.lock().unwrap().insert(spec.port, (spec.host.clone(), spec.host_port));
+        if !self.accepting {
+            self.accepting = true;
+            let targets = self.targets.clone();
+            thread::spawn(move || accept_loop(mux, targets));
+        }
+        Ok(spec)
+    }
+}
+
+/// `L:` - nasłuchujemy lokalnie, każde połączenie idzie przez serwer do celu.
+fn local(mux: Arc<Mux>, spec: Spec) -> io::Result<Spec> {
+    let listener = TcpListener::bind(host_port(&spec.bind, spec.port))?;
+    let mut spec = spec;
+    spec.port = listener.local_addr()?.port();
+    let result = spec.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let (mux, spec) = (mux.clone(), spec.clone());
+            thread::spawn(move || {
+                if let Err(err) = stream.and_then(|stream| connect(&mux, &spec, stream)) {
+                    report(&spec, err);
+                }
+            });
+        }
+    });
+    Ok(result)
+}
+
+/// Tunel dla jednego połączenia `L:`.
+fn connect(mux: &Mux, spec: &Spec, stream: TcpStream) -> io::Result<()> {
+    let mut channel = mux.open()?;
+    channel.send_request(Request::new(FORWARD_CMD.into(), vec![spec.host.clone(), spec.host_port.to_string()]))?;
+    let answer = channel.read_answer()?;
+    if answer.code != 0 {
+        return Err(io::Error::other(Error::from(answer).msg));
+    }
+    channel.tunnel(stream).map(|_| ())
+}
+
+/// Przyjmowanie kanałów otwieranych przez serwer dla `R:`.
+fn accept_loop(mux: Arc<Mux>, targets: Targets) {
+    while let Ok(channel) = mux.accept(Duration::MAX) {
+        if let Some(channel) = channel {
+            let targets = targets.clone();
+            thread::spawn(move || forwarded(channel, &targets));
+        }
+    }
+}
+
+/// Połączenie z celem `R:` i tunel dla połączenia przyjętego przez serwer.
+fn forwarded(mut channel: Channel, targets: &Targets) {
+    let target = match channel.read_request() {
+        Ok(request) if request.command == FORWARDED_CMD => request.params.first()
+            .and_then(|port| port.parse().ok())
+            .and_then(|port: u16| targets.lock().unwrap().get(&port).cloned())
+            .ok_or_else(|| format!("unknown forward: {:?}", request.params)),
+        Ok(request) => Err(format!("unexpected request from server: {}", request.command)),
+        Err(_) => return,
+    };
+    let stream = target.and_then(|(host, port)| {
+        let addr = host_port(&host, port);
+        dial::connect(&addr).map_err(|e| format!("can't connect to {}: {}", addr, e))
+    });
+    let answer = match &stream {
+        Ok(_) => Answer::new(0, "OK", FORWARDED_CMD),
+        Err(why) => {
+            eprintln!("{}", Red.paint(format!("forward: {}", why)));
+            Answer::from(Error::new(-1, why))
+        }
+    };
+    if channel.send_answer(answer).is_ok() && let Ok(stream) = stream {
+        let _ = channel.tunnel(stream);
+    }
+}
+
+fn report(spec: &Spec, err: io::Error) {
+    eprintln!("{}", Red.paint(format!("forward {}: {}", spec, err)));
+}
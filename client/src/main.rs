@@ -20,13 +20,20 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+mod forward;
+mod remote;
 mod side;
 
 use shared::data::{request::Request, answer::Answer};
-use std::{net::*, io::ErrorKind};
+use std::{collections::HashMap, io::{self, ErrorKind}, path::Path};
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::net::UnixStream;
 use shared::net::connector::{ConnectionSide, Connector};
+use shared::net::dial;
+use shared::net::forward::FORWARD_CMD;
+use shared::net::transport::{Stdio, Transport};
 use shared::ufs::fileinfo::FileInfo;
-use shared::xerror::{Error, Result};
+use shared::xerror::{Error, ErrSrc, Result};
 use ansi_term::Colour::*;
 use rustyline::{
     DefaultEditor,
@@ -38,22 +45,37 @@ use rustyline::{
     EventContext,
     Cmd, Cmd::AcceptLine,
     EventHandler};
-use shared::executor::Executor;
+use shared::data::table::Table;
+use shared::executor::{Executor, SQL_CMD, SQL_WRITE};
 use shared::ufs::file::File;
+use std::sync::atomic::{AtomicU32, Ordering::Relaxed};
+use std::process::{self, Command};
+use std::thread;
+use crate::forward::Forwards;
+use crate::remote::Remote;
 use crate::side::Side;
 
 static mut REMOTE_HOST: bool = true;
+const DEFAULT_ADDRESS: &str = "localhost:25105";
+const DEFAULT_PORT: u16 = 25105;
+/// Opcje, po których następuje wartość.
+const VALUE_OPTIONS: [&str; 7] = ["--address", "-a", "--batch", "-b", "--exec", "-e", "--socks"];
+/// Numer kolejnego polecenia wykonywanego w tle.
+static JOB_ID: AtomicU32 = AtomicU32::new(1);
 
 fn main() -> Result<()>{
-    let addr = SocketAddr::from(([127, 0, 0, 1], 25105));
-    match TcpStream::connect(addr) {
-        Ok(socket) => {
-            println!("Connected to server: {}", socket.peer_addr()?);
-            handle_connection(socket)?;
+    let transport = match exec_command() {
+        Some(command) => spawn_server(&command),
+        None => connect(&server_address()),
+    };
+    match transport {
+        Ok(transport) => {
+            println!("Connected to server: {}", transport.peer_addr()?);
+            handle_connection(transport, batch_file())?;
         }
         Err(e) => {
             match e.kind() {
-                ErrorKind::ConnectionRefused => eprintln!("Server is not running."),
+                ErrorKind::ConnectionRefused | ErrorKind::NotFound => eprintln!("Server is not running."),
                 _ => eprintln!("Connection error: {}", e),
             };
         },
@@ -61,6 +83,87 @@ fn main() -> Result<()>{
     Ok(())
 }
 
+/// Połączenie z serwerem: `unix:<ścieżka>` to gniazdo Unix,
+/// każdy inny adres to `host:port` dla TCP (nazwa, IPv4 albo `[IPv6]`);
+/// wszystkie adresy hosta są próbowane metodą "happy eyeballs".
+fn connect(addr: &str) -> io::Result<Box<dyn Transport>> {
+    match addr.strip_prefix("unix:") {
+        Some(path) => Ok(Box::new(UnixStream::connect(path)?)),
+        None => Ok(Box::new(dial::connect(addr)?)),
+    }
+}
+
+/// Opcja `--batch <plik>` (`-b`): wykonanie poleceń z pliku
+/// zamiast pracy interaktywnej.
+fn batch_file() -> Option<String> {
+    option_value(&["--batch", "-b"])
+}
+
+/// Opcja `--address <adres>` (`-a`) albo pierwszy argument bez opcji: adres serwera,
+/// np. `unix:/run/server.sock`, `host` lub `host:port`.
+fn server_address() -> String {
+    match option_value(&["--address", "-a"]).or_else(positional) {
+        Some(addr) => with_default_port(addr),
+        None => DEFAULT_ADDRESS.to_string(),
+    }
+}
+
+/// Adres bez portu (`host`, `1.2.3.4`, `::1`, `[::1]`) z domyślnym portem serwera.
+fn with_default_port(addr: String) -> String {
+    if addr.starts_with("unix:") {
+        return addr;
+    }
+    match addr.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, DEFAULT_PORT).to_string(),
+        Err(_) if !addr.contains(':') || addr.ends_with(']') => format!("{}:{}", addr, DEFAULT_PORT),
+        Err(_) => addr,
+    }
+}
+
+/// Opcja `--socks <port>` (lub `<adres:port>`): lokalne proxy SOCKS5
+/// łączące przez serwer. Sam port oznacza nasłuchiwanie na 127.0.0.1.
+fn socks_address() -> Option<String> {
+    option_value(&["--socks"]).map(|value| match value.parse::<u16>() {
+        Ok(port) => format!("127.0.0.1:{}", port),
+        Err(_) => value,
+    })
+}
+
+/// Opcja `--exec <polecenie>` (`-e`): połączenie przez wejście i wyjście
+/// polecenia uruchamiającego serwer, np. `ssh host server --stdio`.
+fn exec_command() -> Option<String> {
+    option_value(&["--exec", "-e"])
+}
+
+/// Pierwszy argument, który nie jest opcją ani jej wartością.
+fn positional() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if VALUE_OPTIONS.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+fn option_value(names: &[&str]) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| names.contains(&arg.as_str()))
+        .and_then(|idx| args.get(idx + 1).cloned())
+}
+
+fn spawn_server(command: &str) -> io::Result<Box<dyn Transport>> {
+    let child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .spawn()?;
+    Ok(Box::new(Stdio::child(child, command)?))
+}
+
 struct SwitchContext;
 impl ConditionalEventHandler for SwitchContext {
     fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, _ctx: &EventContext) -> Option<Cmd> {
@@ -72,10 +175,23 @@ impl ConditionalEventHandler for SwitchContext {
 /// Obsługa połączenie z serwerem.
 /// Odczytujemy polecenia z linii poleceń,
 /// wysyłamy do serwera i wyświetlamy wynik.
-fn handle_connection(stream: TcpStream) -> Result<()> {
-    let mut conn = Connector::new(stream.try_clone()?, ConnectionSide::Client);
-    conn.init()?;
+fn handle_connection(transport: impl Transport + 'static, batch: Option<String>) -> Result<()> {
+    let mut conn = Connector::new(transport, ConnectionSide::Client);
+    if let Err(err) = conn.init() {
+        return connection_lost(err.into());
+    }
+    let mut conn = Remote::new(conn)?;
     serve_line_remote(&mut conn, "cd".to_string(), false)?;
+    let mut forwards = Forwards::new(&conn);
+    if let Some(addr) = socks_address() {
+        match forwards.socks(&addr) {
+            Ok(addr) => println!("SOCKS5 proxy on {}", addr),
+            Err(why) => eprintln!("{}", Red.paint(format!("SOCKS5 proxy: {}", why))),
+        }
+    }
+    if let Some(path) = batch {
+        return run_batch(&mut conn, &path);
+    }
     let mut side = Side::new()?;
     
     let mut edt = DefaultEditor::new().unwrap();
@@ -91,7 +207,9 @@ fn handle_connection(stream: TcpStream) -> Result<()> {
 
     loop {
         if unsafe{ REMOTE_HOST } {
-            side.set_remote(&mut conn)?;
+            if let Err(err) = side.set_remote(&mut conn) {
+                return connection_lost(err);
+            }
         } else {
             side.set_local()?;
         }
@@ -102,8 +220,17 @@ fn handle_connection(stream: TcpStream) -> Result<()> {
                 let line = line.trim().to_string();
                 if !line.is_empty() {
                     edt.add_history_entry(line.as_str()).expect("can't add to history");
-                    if side.remote {
-                        serve_line_remote(&mut conn, line, true)?;
+                    if side.remote && let Some(line) = line.strip_suffix('&') {
+                        run_in_background(&conn, line.trim_end().to_string());
+                    } else if side.remote && line.split_whitespace().next() == Some(FORWARD_CMD) {
+                        match forwards.command(&parse_line(&line).params) {
+                            Ok(lines) => print_common(&lines),
+                            Err(why) => eprintln!("{}", Red.paint(why)),
+                        }
+                    } else if side.remote {
+                        if let Err(err) = serve_line_remote(&mut conn, line, true) {
+                            return connection_lost(err);
+                        }
                     } else {
                         serve_line(line, true)?;
                     }
@@ -120,16 +247,102 @@ fn handle_connection(stream: TcpStream) -> Result<()> {
     Ok(())
 }
 
-/// Wykonanie polecenia lokalnie.
-fn serve_line(line: String, display: bool) -> Result<Answer>{
-    let tokens = line.split_whitespace().collect::<Vec<&str>>();
-    let command = tokens[0].to_string();
-    let args = tokens[1..]
-        .iter()
-        .map(|item| item.to_string())
+/// Wykonanie poleceń z pliku (po jednym w linii, `#` rozpoczyna komentarz).
+/// Polecenia wysyłamy bez czekania na odpowiedzi,
+/// wyniki wyświetlamy w kolejności poleceń.
+fn run_batch(conn: &mut Remote, path: &str) -> Result<()> {
+    let text = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    // Linie, z których nie da się zbudować żądania (np. `put` bez pliku),
+    // nie trafiają do serwera; ich odpowiedzią jest opis błędu.
+    let mut failed = HashMap::new();
+    let requests = lines.iter().enumerate()
+        .filter_map(|(i, line)| remote_request(line).map_err(|err| failed.insert(i, Answer::from(err))).ok())
         .collect();
+    let mut answers = match conn.batch(requests) {
+        Ok(answers) => answers.into_iter(),
+        Err(err) => return connection_lost(err.into()),
+    };
+    for (i, line) in lines.iter().enumerate() {
+        let answer = failed.remove(&i).or_else(|| answers.next()).unwrap_or_default();
+        println!("{}", Yellow.paint(format!("> {}", line)));
+        save_upload(&answer)?;
+        display_answer(&answer);
+    }
+    Ok(())
+}
 
-    let request = Request::new(command, args);
+/// Obsługa błędu komunikacji z serwerem.
+/// Jeśli serwer odmówił połączenia (także z powodu niezgodnej wersji protokołu),
+/// zakończył pracę lub zerwał połączenie, informujemy
+/// o tym użytkownika i kończymy pracę. Inne błędy przekazujemy dalej.
+fn connection_lost(err: Error) -> Result<()> {
+    let lost = [
+        ErrorKind::ConnectionRefused,
+        ErrorKind::Unsupported,
+        ErrorKind::ConnectionAborted,
+        ErrorKind::ConnectionReset,
+        ErrorKind::BrokenPipe,
+        ErrorKind::UnexpectedEof,
+    ].iter().any(|kind| kind.to_string() == err.kind);
+    
+    if err.src == ErrSrc::IO && lost {
+        println!("{}", Yellow.paint(err.msg));
+        return Ok(());
+    }
+    Err(err)
+}
+
+/// Podział linii na polecenie i jego parametry.
+/// Dla polecenia `sql` zapytanie (wszystko po ścieżce bazy)
+/// jest jednym parametrem, żeby zachować odstępy w tekstach.
+fn parse_line(line: &str) -> Request {
+    let mut rest = line.trim();
+    let mut next = || {
+        let (token, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        rest = tail.trim_start();
+        token.to_string()
+    };
+    let command = next();
+    if command != SQL_CMD {
+        let args = line.split_whitespace().skip(1).map(str::to_string).collect();
+        return Request::new(command, args);
+    }
+    let mut args = vec![next()];
+    if args[0] == SQL_WRITE {
+        args.push(next());
+    }
+    args.push(rest.to_string());
+    args.retain(|arg| !arg.is_empty());
+    Request::new(command, args)
+}
+
+/// Żądanie do serwera. `put <plik> [ścieżka]` wysyła zawartość lokalnego
+/// pliku, który na serwerze powstaje pod podaną ścieżką, a bez niej
+/// pod własną nazwą w katalogu roboczym sesji.
+fn remote_request(line: &str) -> Result<Request> {
+    let mut request = parse_line(line);
+    if request.command == "put" {
+        let Some(local) = request.params.first().cloned() else {
+            return Err(Error::new(-1, "usage: put <local-file> [remote-path]"));
+        };
+        let remote = match request.params.get(1) {
+            Some(remote) => remote.clone(),
+            None => local_name(&local)?,
+        };
+        request.binary = std::fs::read(&local)
+            .map_err(|err| Error::new(-1, &format!("can't read '{}': {}", local, err)))?;
+        request.params = vec![remote];
+    }
+    Ok(request)
+}
+
+/// Wykonanie polecenia lokalnie.
+fn serve_line(line: String, display: bool) -> Result<Answer>{
+    let request = parse_line(&line);
     let answer = Executor::execute(request)?;
     if display {
         display_answer(&answer);       
@@ -138,28 +351,67 @@ fn serve_line(line: String, display: bool) -> Result<Answer>{
 }
 
 /// Wykonanie polecenia zdalnie
-fn serve_line_remote(conn: &mut Connector, line: String, display: bool) -> Result<Answer>{
-    let tokens = line.split_whitespace().collect::<Vec<&str>>();
-    let command = tokens[0].to_string();
-    let args = tokens[1..]
-        .iter()
-        .map(|item| item.to_string())
-        .collect();
+fn serve_line_remote(conn: &mut Remote, line: String, display: bool) -> Result<Answer>{
+    // Błąd po stronie klienta (np. brak pliku do `put`) nie kończy sesji.
+    let answer = match remote_request(&line) {
+        Ok(request) => conn.request(request)?,
+        Err(err) => Answer::from(err),
+    };
+    save_upload(&answer)?;
+    if display {
+        display_answer(&answer);
+    }
+    Ok(answer)
+}
 
-    // "get", "send"
-    let request = Request::new(command, args);
-    conn.send_request(request)?;
-    let answer = conn.read_answer()?;
+/// Wykonanie polecenia zdalnie w tle (polecenie zakończone znakiem '&').
+/// W tym czasie można wykonywać kolejne polecenia.
+/// Wynik wyświetlamy, gdy polecenie się zakończy.
+fn run_in_background(conn: &Remote, line: String) {
+    let Some(mux) = conn.background() else {
+        eprintln!("{}", Red.paint("Server does not support background commands."));
+        return;
+    };
+    let request = match remote_request(&line) {
+        Ok(request) => request,
+        Err(err) => {
+            display_answer(&Answer::from(err));
+            return;
+        }
+    };
+    let job = JOB_ID.fetch_add(1, Relaxed);
+    println!("[{}] {}", job, line);
+    thread::spawn(move || {
+        let result = mux.request(request)
+            .map_err(Error::from)
+            .and_then(|answer| save_upload(&answer).map(|_| answer));
+        println!("\n[{}] done: {}", job, line);
+        match result {
+            Ok(answer) => display_answer(&answer),
+            Err(err) => eprintln!("{}", Red.paint(format!("{:?}", err))),
+        }
+    });
+}
+
+/// Zapis pobranego pliku.
+fn save_upload(answer: &Answer) -> Result<()> {
     if answer.cmd == "upload" {
-        let mut fh = File::new(answer.data[0].as_str());
+        let mut fh = File::new(&local_name(&answer.data[0])?);
         fh.create()?;
         fh.write(answer.binary.as_slice())?;
         fh.close()?;
     }
-    if display {
-        display_answer(&answer);
+    Ok(())
+}
+
+/// Nazwa, pod którą zapisujemy pobrany plik: w bieżącym katalogu,
+/// pod jego własną nazwą (serwer nie może wskazać innego miejsca).
+/// Taką samą nazwę domyślnie dostaje plik wysyłany przez `put`.
+fn local_name(remote: &str) -> Result<String> {
+    match Path::new(remote).file_name().filter(|_| !remote.ends_with('/')) {
+        Some(name) => Ok(name.to_string_lossy().to_string()),
+        None => Err(Error::new(-1, &format!("'{}' has no file name", remote))),
     }
-    Ok(answer)
 }
 
 fn display_answer(answer: &Answer) {
@@ -169,6 +421,7 @@ fn display_answer(answer: &Answer) {
                 match answer.cmd.as_str() {
                     "ll" | "la" => print_file_info(&answer.data),
                     "stat" =>print_stat(&answer.data),
+                    SQL_CMD => print_table(&answer.data),
                     _ => print_common(&answer.data),
                 }
             }
@@ -198,6 +451,16 @@ fn print_file_info(data: &[String]) {
         });
 }
 
+fn print_table(data: &[String]) {
+    data.iter()
+        .for_each(|item| {
+            match Table::from_json(item) {
+                Ok(table) => println!("{}", table),
+                Err(err) => println!("{:?}", err)
+            }
+        });
+}
+
 fn print_stat(data: &[String]) {
     data.iter()
         .for_each(|item| {
@@ -207,3 +470,36 @@ fn print_stat(data: &[String]) {
             }
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_name() {
+        assert_eq!(local_name("/home/piotr/notes.txt").unwrap(), "notes.txt");
+        assert_eq!(local_name("../../etc/passwd").unwrap(), "passwd");
+        assert_eq!(local_name("notes.txt").unwrap(), "notes.txt");
+        for remote in ["..", ".", "/", "dir/", ""] {
+            assert!(local_name(remote).unwrap_err().msg.contains("has no file name"), "{}", remote);
+        }
+    }
+
+    #[test]
+    fn test_put_request() {
+        let path = std::env::temp_dir().join(format!("client-put-{}.txt", process::id()));
+        std::fs::write(&path, "zażółć").unwrap();
+        let local = path.to_str().unwrap();
+        let name = local_name(local).unwrap();
+
+        let request = remote_request(&format!("put {}", local)).unwrap();
+        assert_eq!((request.params, request.binary), (vec![name], "zażółć".as_bytes().to_vec()));
+        let request = remote_request(&format!("put {} /tmp/copy.txt", local)).unwrap();
+        assert_eq!(request.params, vec!["/tmp/copy.txt".to_string()]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(remote_request(&format!("put {}", local)).unwrap_err().msg.starts_with("can't read"));
+        assert!(remote_request("put").is_err());
+        assert!(remote_request("ls -l").unwrap().binary.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::Arc;
+use shared::data::{request::Request, answer::Answer};
+use shared::net::connector::Connector;
+use shared::net::mux::{Channel, Mux};
+use shared::net::protocol::CAP_CHANNELS;
+
+/// Najwięcej żądań wysłanych bez odpowiedzi w trybie wsadowym.
+const PIPELINE_DEPTH: usize = 64;
+/// Polecenia zmieniające stan sesji, od którego zależą kolejne (katalog roboczy).
+const BARRIERS: [&str; 1] = ["cd"];
+
+/// Połączenie z serwerem.
+/// Jeśli serwer obsługuje kanały, kilka poleceń może być wykonywanych
+/// jednocześnie (polecenia w tle). Starsze serwery obsługują
+/// tylko jedno polecenie naraz.
+pub enum Remote {
+    Single(Box<Connector>),
+    Channels(Arc<Mux>),
+}
+
+impl Remote {
+    pub fn new(conn: Connector) -> io::Result<Self> {
+        match conn.protocol().has(CAP_CHANNELS) {
+            true => Ok(Remote::Channels(Arc::new(Mux::new(conn)?))),
+            false => Ok(Remote::Single(Box::new(conn))),
+        }
+    }
+
+    pub fn request(&mut self, request: Request) -> io::Result<Answer> {
+        match self {
+            Remote::Single(conn) => {
+                conn.send_request(request)?;
+                conn.read_answer()
+            }
+            Remote::Channels(mux) => mux.request(request),
+        }
+    }
+
+    /// Wykonanie wielu żądań bez czekania na kolejne odpowiedzi.
+    /// Serwer może je wykonać w dowolnej kolejności, odpowiedzi
+    /// zwracamy w kolejności żądań.
+    /// Kanały serwer wykonuje równolegle, więc `cd` wysyłamy dopiero po
+    /// odpowiedziach na wcześniejsze żądania, a kolejne - po odpowiedzi na `cd`.
+    pub fn batch(&mut self, requests: Vec<Request>) -> io::Result<Vec<Answer>> {
+        match self {
+            Remote::Channels(mux) => {
+                let mut answers = Vec::with_capacity(requests.len());
+                let mut in_flight = VecDeque::new();
+                for request in requests {
+                    let barrier = BARRIERS.contains(&request.command.as_str());
+                    if barrier {
+                        wait_all(&mut in_flight, &mut answers)?;
+                    }
+                    if in_flight.len() == PIPELINE_DEPTH {
+                        let mut channel: Channel = in_flight.pop_front().unwrap();
+                        answers.push(channel.read_answer()?);
+                    }
+                    let mut channel = mux.open()?;
+                    channel.send_request(request)?;
+                    in_flight.push_back(channel);
+                    if barrier {
+                        wait_all(&mut in_flight, &mut answers)?;
+                    }
+                }
+                wait_all(&mut in_flight, &mut answers)?;
+                Ok(answers)
+            }
+            Remote::Single(conn) if conn.pipelined() => {
+                let mut ids = Vec::with_capacity(requests.len());
+                let mut done = HashMap::new();
+                for request in requests {
+                    if conn.pending() == PIPELINE_DEPTH {
+                        let answer = conn.read_answer()?;
+                        done.insert(answer.id(), answer);
+                    }
+                    ids.push(conn.send_request(request)?);
+                }
+                while conn.pending() > 0 {
+                    let answer = conn.read_answer()?;
+                    done.insert(answer.id(), answer);
+                }
+                Ok(ids.iter().map(|id| done.remove(id).unwrap()).collect())
+            }
+            Remote::Single(_) => requests.into_iter().map(|request| self.request(request)).collect(),
+        }
+    }
+
+    /// Połączenie do wykonywania poleceń w tle (tylko z kanałami).
+    pub fn background(&self) -> Option<Arc<Mux>> {
+        match self {
+            Remote::Channels(mux) => Some(mux.clone()),
+            Remote::Single(_) => None,
+        }
+    }
+}
+
+/// Odczyt odpowiedzi na wszystkie wysłane żądania.
+fn wait_all(in_flight: &mut VecDeque<Channel>, answers: &mut Vec<Answer>) -> io::Result<()> {
+    for mut channel in in_flight.drain(..) {
+        answers.push(channel.read_answer()?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+    use shared::net::connector::ConnectionSide;
+
+    #[test]
+    fn test_batch_barrier() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Serwer odpowiada dopiero, gdy przez chwilę nie przychodzą nowe żądania,
+        // i zapisuje, które żądania dostał razem.
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = Connector::new(stream, ConnectionSide::Server);
+            conn.init().unwrap();
+            let mux = Mux::new(conn).unwrap();
+            let mut groups = Vec::new();
+            let mut pending = Vec::new();
+            loop {
+                match mux.accept(Duration::from_millis(200)) {
+                    Ok(Some(mut channel)) => {
+                        let request = channel.read_request().unwrap();
+                        pending.push((channel, request.command));
+                    }
+                    Ok(None) if !pending.is_empty() => {
+                        groups.push(pending.iter().map(|(_, cmd)| cmd.clone()).collect::<Vec<_>>());
+                        for (mut channel, cmd) in pending.drain(..) {
+                            channel.send_answer(Answer::new(0, "OK", &cmd)).unwrap();
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(_) => return groups,
+                }
+            }
+        });
+        let mut conn = Connector::new(TcpStream::connect(addr).unwrap(), ConnectionSide::Client);
+        conn.init().unwrap();
+        let mut remote = Remote::new(conn).unwrap();
+
+        let requests = ["ls", "ls", "cd", "pwd"].map(|cmd| Request::new(cmd.into(), vec![]));
+        let answers = remote.batch(requests.to_vec()).unwrap();
+        assert_eq!(answers.iter().map(|answer| answer.cmd.as_str()).collect::<Vec<_>>(), ["ls", "ls", "cd", "pwd"]);
+        drop(remote);
+        assert_eq!(server.join().unwrap(), [vec!["ls", "ls"], vec!["cd"], vec!["pwd"]]);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{serve_line, serve_line_remote};
 use shared::xerror::Result;
 use ansi_term::Colour::*;
-use shared::net::connector::Connector;
+use crate::remote::Remote;
 
 #[derive(Debug, Default)]
 pub struct Side {
@@ -19,7 +19,7 @@ impl Side {
         Ok(side)   
     }
     
-    pub fn set_remote(&mut self, conn: &mut Connector) -> Result<()> {
+    pub fn set_remote(&mut self, conn: &mut Remote) -> Result<()> {
         let host_answer = serve_line_remote(conn, "uname -n".into(), false)?;
         let user_answer = serve_line_remote(conn, "whoami".into(), false)?;
         self.remote = true;
@@ -0,0 +1,143 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::ffi::{CStr, CString};
+use std::io::{self, ErrorKind};
+use std::mem::MaybeUninit;
+use std::ptr;
+use serde::{Deserialize, Serialize};
+
+/// Rozmiar bufora na dane z `/etc/passwd` i `/etc/group`.
+const BUFFER_SIZE: usize = 16384;
+
+/// Konto systemowe, na które serwer może przełączyć proces.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Account {
+    /// Konto o podanej nazwie albo numerze uid.
+    pub fn by_name(name: &str) -> io::Result<Account> {
+        if let Ok(uid) = name.parse::<u32>() {
+            return Account::by_uid(uid);
+        }
+        let cname = CString::new(name).map_err(|_| not_found("user", name))?;
+        lookup_passwd(|pwd, buf, result| unsafe {
+            libc::getpwnam_r(cname.as_ptr(), pwd, buf.as_mut_ptr(), buf.len(), result)
+        }).and_then(|account| account.ok_or_else(|| not_found("user", name)))
+    }
+
+    pub fn by_uid(uid: u32) -> io::Result<Account> {
+        lookup_passwd(|pwd, buf, result| unsafe {
+            libc::getpwuid_r(uid, pwd, buf.as_mut_ptr(), buf.len(), result)
+        }).and_then(|account| account.ok_or_else(|| not_found("uid", &uid.to_string())))
+    }
+
+    /// Przełączenie procesu na to konto (z grupą `gid`): grupy dodatkowe
+    /// konta, gid i uid. Po zmianie uid nie da się wrócić do poprzednich uprawnień.
+    pub fn switch(&self, gid: u32) -> io::Result<()> {
+        let name = CString::new(self.name.as_str()).map_err(|_| not_found("user", &self.name))?;
+        unsafe {
+            check(libc::initgroups(name.as_ptr(), gid))?;
+            check(libc::setgid(gid))?;
+            check(libc::setuid(self.uid))?;
+            // Powrót do roota musi być niemożliwy.
+            if self.uid != 0 && libc::setuid(0) == 0 {
+                return Err(io::Error::other("privileges can still be regained"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Numer grupy o podanej nazwie albo numerze.
+pub fn group_id(name: &str) -> io::Result<u32> {
+    if let Ok(gid) = name.parse::<u32>() {
+        return Ok(gid);
+    }
+    let cname = CString::new(name).map_err(|_| not_found("group", name))?;
+    let mut grp = MaybeUninit::<libc::group>::uninit();
+    let mut buf = vec![0 as libc::c_char; BUFFER_SIZE];
+    let mut result = ptr::null_mut();
+    let code = unsafe { libc::getgrnam_r(cname.as_ptr(), grp.as_mut_ptr(), buf.as_mut_ptr(), buf.len(), &mut result) };
+    match code {
+        0 if result.is_null() => Err(not_found("group", name)),
+        0 => Ok(unsafe { grp.assume_init() }.gr_gid),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// Zmiana samej grupy procesu (bez zmiany uid); grupy dodatkowe są usuwane.
+pub fn switch_group(gid: u32) -> io::Result<()> {
+    unsafe {
+        check(libc::setgroups(1, &gid))?;
+        check(libc::setgid(gid))
+    }
+}
+
+fn lookup_passwd(
+    f: impl FnOnce(*mut libc::passwd, &mut [libc::c_char], *mut *mut libc::passwd) -> libc::c_int,
+) -> io::Result<Option<Account>> {
+    let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+    let mut buf = vec![0 as libc::c_char; BUFFER_SIZE];
+    let mut result = ptr::null_mut();
+    match f(pwd.as_mut_ptr(), &mut buf, &mut result) {
+        0 if result.is_null() => Ok(None),
+        0 => {
+            let pwd = unsafe { pwd.assume_init() };
+            let name = unsafe { CStr::from_ptr(pwd.pw_name) }.to_string_lossy().into_owned();
+            Ok(Some(Account { name, uid: pwd.pw_uid, gid: pwd.pw_gid }))
+        }
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+fn check(code: libc::c_int) -> io::Result<()> {
+    match code {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn not_found(what: &str, name: &str) -> io::Error {
+    io::Error::new(ErrorKind::NotFound, format!("unknown {}: {}", what, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let root = Account::by_uid(0).unwrap();
+        assert_eq!(root, Account { name: "root".into(), uid: 0, gid: 0 });
+        assert_eq!(Account::by_name("root").unwrap(), root);
+        assert_eq!(Account::by_name("0").unwrap(), root);
+        assert_eq!(group_id("0").unwrap(), 0);
+        assert_eq!(group_id("root").unwrap(), 0);
+        assert_eq!(Account::by_name("no-such-user-xyz").unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(group_id("no-such-group-xyz").unwrap_err().kind(), ErrorKind::NotFound);
+    }
+}
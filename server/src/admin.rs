@@ -0,0 +1,122 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use shared::data::answer::Answer;
+use shared::xerror::Error;
+use crate::context::Context;
+use crate::logging;
+use crate::session::Identity;
+
+/// Polecenia administracyjne wykonuje serwer, a nie Executor.
+pub const ADMIN_CMD: &str = "admin";
+
+const USAGE: &str = "usage: admin stats | admin history [commands|sessions|transfers] [count] \
+    | admin sessions | admin kill <sid> | admin commands <sid> [count] | admin reload";
+/// Domyślna liczba wyświetlanych wpisów historii.
+const HISTORY_COUNT: i64 = 20;
+
+/// Polecenia administracyjne są dostępne tylko dla użytkowników z listy `admins`
+/// uwierzytelnionych przez gniazdo Unix.
+pub fn execute(ctx: &Context, id: &Identity, params: &[String]) -> Answer {
+    if !id.is_admin(&ctx.config()) {
+        return Answer::from(Error::new(libc::EACCES, "admin commands are not allowed for this session"));
+    }
+    match params.first().map(String::as_str) {
+        Some("stats") => stats(ctx),
+        Some("history") => history(ctx, &params[1..]),
+        Some("sessions") => Answer::new_with_data(0, "OK", ADMIN_CMD, ctx.sessions.list()),
+        Some("kill") => kill(ctx, id, &params[1..]),
+        Some("commands") => commands(ctx, &params[1..]),
+        Some("reload") => reload(ctx),
+        _ => Answer::from(Error::new(-1, USAGE)),
+    }
+}
+
+/// Liczniki połączeń i limitów.
+fn stats(ctx: &Context) -> Answer {
+    let mut data = ctx.limiter.report();
+    data.push(format!("registered sessions: {}", ctx.sessions.count()));
+    Answer::new_with_data(0, "OK", ADMIN_CMD, data)
+}
+
+/// Ostatnie wpisy z bazy historii.
+/// Parametry (oba opcjonalne, w dowolnej kolejności): rodzaj wpisów i ich liczba.
+fn history(ctx: &Context, params: &[String]) -> Answer {
+    let mut kind = "commands";
+    let mut count = HISTORY_COUNT;
+    for param in params {
+        match param.as_str() {
+            "commands" | "sessions" | "transfers" => kind = param.as_str(),
+            n => match n.parse::<i64>() {
+                Ok(n) if n > 0 => count = n,
+                _ => return Answer::from(Error::new(-1, USAGE)),
+            }
+        }
+    }
+    match ctx.history.recent(kind, count) {
+        Ok(data) => Answer::new_with_data(0, "OK", ADMIN_CMD, data),
+        Err(err) => Answer::from(shared::xerror::Error::from(err)),
+    }
+}
+
+/// Zamknięcie połączenia wskazanej sesji.
+fn kill(ctx: &Context, id: &Identity, params: &[String]) -> Answer {
+    let Some(sid) = params.first().and_then(|sid| sid.parse::<u32>().ok()) else {
+        return Answer::from(Error::new(-1, USAGE));
+    };
+    if sid == id.sid {
+        return Answer::from(Error::new(-1, "use exit to end your own session"));
+    }
+    if !ctx.sessions.kill(sid) {
+        return Answer::from(Error::new(-1, &format!("no session {sid}")));
+    }
+    id.log(logging::warn("session.kill")).field("target", sid).emit();
+    Answer::new(0, "OK", ADMIN_CMD)
+}
+
+/// Ostatnie polecenia sesji: aktywnej z pamięci, zakończonej z bazy historii.
+fn commands(ctx: &Context, params: &[String]) -> Answer {
+    let sid = params.first().and_then(|sid| sid.parse::<u32>().ok());
+    let count = match params.get(1).map(|n| n.parse::<i64>()) {
+        None => Some(HISTORY_COUNT),
+        Some(Ok(n)) if n > 0 => Some(n),
+        Some(_) => None,
+    };
+    let (Some(sid), Some(count)) = (sid, count) else {
+        return Answer::from(Error::new(-1, USAGE));
+    };
+    if let Some(data) = ctx.sessions.recent(sid, count as usize) {
+        return Answer::new_with_data(0, "OK", ADMIN_CMD, data);
+    }
+    match ctx.history.session_commands(sid, count) {
+        Ok(data) => Answer::new_with_data(0, "OK", ADMIN_CMD, data),
+        Err(err) => Answer::from(shared::xerror::Error::from(err)),
+    }
+}
+
+/// Ponowne wczytanie konfiguracji bez restartu serwera.
+fn reload(ctx: &Context) -> Answer {
+    match ctx.reload() {
+        Ok(data) => Answer::new_with_data(0, "OK", ADMIN_CMD, data),
+        Err(why) => Answer::from(Error::new(-1, &why)),
+    }
+}
@@ -0,0 +1,273 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::{self, ErrorKind};
+use std::panic;
+use std::sync::Arc;
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+use std::time::Instant;
+use crossbeam_channel::Receiver;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::sync::watch;
+use tokio::time::timeout;
+use shared::net::async_connector::AsyncConnector;
+use shared::net::connector::ConnectionSide;
+use shared::net::protocol::{Hello, CAPABILITIES, CAP_COMPRESSION};
+use shared::net::transport::{peer_credentials, Metered, Traffic, Transport};
+use crate::context::Context;
+use crate::limits::{Admission, Origin, Permit};
+use crate::listener::Listener;
+use crate::logging;
+use crate::session::{execute, finish, start_worker, End, Identity, SESSIONS_TOTAL};
+use crate::{drain, REJECT_TIMEOUT, TASK_COUNT, TASK_ID};
+
+/// Serwer asynchroniczny (`--async`): wszystkie sesje obsługuje kilka
+/// wątków tokio, a bezczynna sesja to tylko zarejestrowane gniazdo.
+/// Polecenia wykonujemy w wątkach `spawn_blocking`, bo `Executor`
+/// korzysta z blokujących operacji na plikach i bazie.
+/// Sesje nie oferują kanałów (`CAP_CHANNELS`), pozostałe możliwości
+/// protokołu są takie same jak w serwerze wielowątkowym.
+/// Zwraca liczbę siłowo zamkniętych sesji.
+pub fn run(listeners: Vec<Listener>, ctx: &Arc<Context>, ctrl_receiver: Receiver<()>) -> io::Result<usize> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("session")
+        .enable_all()
+        .build()?;
+    let (stop_sender, stop) = watch::channel(false);
+    // Pierwszy sygnał rozpoczyna zamykanie (drugi obsługuje `drain` przez FORCE).
+    thread::spawn(move || {
+        let _ = ctrl_receiver.recv();
+        let _ = stop_sender.send(true);
+    });
+
+    runtime.block_on(async {
+        // Każde gniazdo nasłuchujące ma własne zadanie accept.
+        let mut acceptors = Vec::new();
+        for listener in listeners {
+            let (ctx, stop) = (ctx.clone(), stop.clone());
+            acceptors.push(match listener {
+                Listener::Tcp(listener) => {
+                    let listener = tokio::net::TcpListener::from_std(listener)?;
+                    tokio::spawn(async move { accept_tcp(&listener, &ctx, stop).await })
+                }
+                Listener::Unix(listener, file) => {
+                    let listener = tokio::net::UnixListener::from_std(listener)?;
+                    tokio::spawn(async move {
+                        accept_unix(&listener, &ctx, stop).await;
+                        drop(file);
+                    })
+                }
+            });
+        }
+        for acceptor in acceptors {
+            let _ = acceptor.await;
+        }
+        let ctx = ctx.clone();
+        let grace_period = ctx.config().grace_period();
+        Ok(tokio::task::spawn_blocking(move || drain(&ctx.sessions, grace_period)).await.unwrap_or(0))
+    })
+} // fn run
+
+async fn accept_tcp(listener: &tokio::net::TcpListener, ctx: &Arc<Context>, stop: watch::Receiver<bool>) {
+    let mut stopping = stop.clone();
+    loop {
+        tokio::select! {
+            _ = stopping.wait_for(|stop| *stop) => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, addr)) => admit(stream, addr.ip().into(), None, ctx, &stop),
+                Err(e) => logging::error("accept").field("error", e).emit(),
+            }
+        }
+    }
+} // fn accept_tcp
+
+async fn accept_unix(listener: &tokio::net::UnixListener, ctx: &Arc<Context>, stop: watch::Receiver<bool>) {
+    let mut stopping = stop.clone();
+    loop {
+        tokio::select! {
+            _ = stopping.wait_for(|stop| *stop) => break,
+            accepted = listener.accept() => match accepted.and_then(|(stream, _)| unix_uid(stream)) {
+                Ok((stream, uid)) => admit(stream, Origin::Uid(uid), Some(uid), ctx, &stop),
+                Err(e) => logging::error("accept").field("error", e).emit(),
+            }
+        }
+    }
+} // fn accept_unix
+
+fn unix_uid(stream: UnixStream) -> io::Result<(UnixStream, u32)> {
+    let stream = stream.into_std()?;
+    let uid = peer_credentials(&stream)?.uid;
+    Ok((UnixStream::from_std(stream)?, uid))
+}
+
+/// Gniazdo tokio, którego drugi (blokujący) uchwyt rejestrujemy
+/// w `Sessions`, aby `close_all` mogło zamknąć także sesje asynchroniczne.
+trait Socket: AsyncRead + AsyncWrite + Unpin + Send + Sized + 'static {
+    type Std: Transport + 'static;
+    fn into_std(self) -> io::Result<Self::Std>;
+    fn from_std(stream: Self::Std) -> io::Result<Self>;
+}
+
+impl Socket for TcpStream {
+    type Std = std::net::TcpStream;
+    fn into_std(self) -> io::Result<Self::Std> {
+        TcpStream::into_std(self)
+    }
+    fn from_std(stream: Self::Std) -> io::Result<Self> {
+        TcpStream::from_std(stream)
+    }
+}
+
+impl Socket for UnixStream {
+    type Std = std::os::unix::net::UnixStream;
+    fn into_std(self) -> io::Result<Self::Std> {
+        UnixStream::into_std(self)
+    }
+    fn from_std(stream: Self::Std) -> io::Result<Self> {
+        UnixStream::from_std(stream)
+    }
+}
+
+/// Sprawdzenie limitów i uruchomienie sesji dla nowego połączenia.
+/// Odmowa to osobne zadanie z limitem czasu, więc nie potrzebuje kolejki.
+fn admit<S: Socket>(stream: S, origin: Origin, uid: Option<u32>, ctx: &Arc<Context>, stop: &watch::Receiver<bool>) {
+    match ctx.limiter.admit(origin) {
+        Admission::Accepted(permit) => {
+            tokio::spawn(handle_client(stream, uid, permit, ctx.clone(), stop.clone()));
+        }
+        Admission::Rejected(reason) => {
+            logging::warn("session.rejected").field("peer", origin).field("reason", reason).emit();
+            tokio::spawn(async move {
+                let mut conn = AsyncConnector::new(stream, ConnectionSide::Server);
+                let _ = timeout(REJECT_TIMEOUT, conn.reject(reason)).await;
+            });
+        }
+        Admission::Banned => (),
+    }
+} // fn admit
+
+async fn handle_client<S: Socket>(stream: S, uid: Option<u32>, permit: Permit, ctx: Arc<Context>, stop: watch::Receiver<bool>) {
+    TASK_COUNT.fetch_add(1, Relaxed);
+    SESSIONS_TOTAL.fetch_add(1, Relaxed);
+    let task_id = TASK_ID.fetch_add(1, Relaxed);
+
+    match register(&ctx, task_id, uid, stream) {
+        Ok((stream, id)) => serve(stream, id, &permit, &ctx, stop).await,
+        Err(why) => logging::warn("session.register").field("sid", task_id).field("error", why).emit(),
+    }
+    // Usunięcie sesji czeka na koniec jej procesu roboczego.
+    let sessions_ctx = ctx.clone();
+    blocking(move || sessions_ctx.sessions.unregister(task_id)).await;
+    TASK_COUNT.fetch_sub(1, Relaxed);
+} // fn handle_client
+
+/// Rejestracja drugiego uchwytu połączenia w `Sessions`.
+/// Zwraca gniazdo z licznikiem przesłanych bajtów i tożsamość sesji.
+fn register<S: Socket>(ctx: &Context, task_id: u32, uid: Option<u32>, stream: S) -> io::Result<(Metered<S>, Identity)> {
+    let stream = stream.into_std()?;
+    let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    let id = Identity::new(ctx, task_id, peer, uid);
+    let traffic = Arc::new(Traffic::default());
+    ctx.sessions.register(&id, Box::new(stream.try_clone()?), traffic.clone());
+    Ok((Metered::new(S::from_std(stream)?, traffic), id))
+} // fn register
+
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(stream: S, id: Identity, permit: &Permit, ctx: &Arc<Context>, stop: watch::Receiver<bool>) {
+    let mut conn = AsyncConnector::new(stream, ConnectionSide::Server);
+    conn.set_max_frame(ctx.config().limits.max_frame_size);
+    if !ctx.config().compression {
+        conn.set_hello(Hello { capabilities: CAPABILITIES & !CAP_COMPRESSION, ..Hello::default() });
+    }
+    id.log(logging::info("session.start")).emit();
+
+    // Cały handshake musi się zmieścić w zadanym czasie.
+    let handshake = timeout(ctx.config().limits.handshake_timeout(), conn.init()).await
+        .unwrap_or_else(|_| Err(io::Error::new(ErrorKind::TimedOut, "Handshake timed out.")));
+    ctx.metrics.handshake(handshake.is_ok());
+    if let Err(why) = handshake {
+        // Klient w niezgodnej wersji protokołu nie jest powodem do blokady.
+        if why.kind() != ErrorKind::Unsupported {
+            permit.limiter().handshake_failed(permit.origin());
+        }
+        id.log(logging::warn("session.handshake_failed")).field("error", why).emit();
+        return;
+    }
+    id.log(logging::debug("session.handshake")).field("protocol", conn.protocol()).emit();
+    // Start procesu roboczego (do `START_TIMEOUT`) i zapisy w bazie
+    // historii blokują wątek, więc jak polecenia idą do `spawn_blocking`.
+    let worker_ctx = ctx.clone();
+    let (mut id, worker) = blocking(move || {
+        let worker = start_worker(&worker_ctx, &id);
+        (id, worker)
+    }).await;
+    if let Err(why) = worker {
+        id.log(logging::error("session.worker")).field("error", why).emit();
+        return;
+    }
+
+    let started = Instant::now();
+    let history_ctx = ctx.clone();
+    let id = blocking(move || {
+        id.history_id = history_ctx.history.session_started(&id);
+        Arc::new(id)
+    }).await;
+    let result = session_loop(&mut conn, ctx, &id, stop).await;
+    let ctx = ctx.clone();
+    blocking(move || finish(&ctx, &id, started, result)).await;
+} // fn serve
+
+/// Wywołanie blokującej funkcji w wątku `spawn_blocking`.
+/// Panika w niej przechodzi do zadania, jak przy bezpośrednim wywołaniu.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    tokio::task::spawn_blocking(f).await.unwrap_or_else(|why| panic::resume_unwind(why.into_panic()))
+}
+
+/// Obsługa kolejnych żądań klienta.
+/// Bezczynna sesja czeka jednocześnie na dane i na sygnał zamykania
+/// serwera, bez cyklicznego sprawdzania. Rozpoczęte polecenie
+/// zawsze jest dokańczane.
+async fn session_loop<S: AsyncRead + AsyncWrite + Unpin>(conn: &mut AsyncConnector<S>, ctx: &Arc<Context>, id: &Arc<Identity>, mut stop: watch::Receiver<bool>) -> io::Result<End> {
+    loop {
+        if *stop.borrow() {
+            conn.send_shutdown_notice().await?;
+            return Ok(End::Drained);
+        }
+        tokio::select! {
+            ready = conn.wait_for_data() => match ready {
+                Ok(()) => one_loop(conn, ctx, id).await?,
+                Err(why) if why.kind() == ErrorKind::UnexpectedEof => return Ok(End::Disconnected),
+                Err(why) => return Err(why),
+            },
+            _ = stop.changed() => (),
+        }
+    }
+} // fn session_loop
+
+async fn one_loop<S: AsyncRead + AsyncWrite + Unpin>(conn: &mut AsyncConnector<S>, ctx: &Arc<Context>, id: &Arc<Identity>) -> io::Result<()> {
+    let request = conn.read_request().await?;
+    let (ctx, id) = (ctx.clone(), id.clone());
+    let answer = tokio::task::spawn_blocking(move || execute(&ctx, &id, &request)).await
+        .map_err(io::Error::other)?;
+    conn.send_answer(answer).await
+} // fn one_loop
@@ -0,0 +1,147 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use chrono::Local;
+use serde::Serialize;
+use shared::data::{answer::Answer, request::Request};
+use crate::logging;
+use crate::session::Identity;
+
+/// Rodzaj wpisu w dzienniku audytu.
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Command,
+    Transfer,
+}
+
+/// Jeden wpis w dzienniku audytu (jedna linia JSON).
+/// Nigdy nie zawiera treści przesyłanych plików.
+#[derive(Serialize)]
+struct Record<'a> {
+    ts: String,
+    kind: Kind,
+    sid: u32,
+    peer: &'a str,
+    user: &'a str,
+    command: &'a str,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    params: &'a [String],
+    code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<usize>,
+}
+
+/// Dziennik audytu: plik, do którego wyłącznie dopisujemy
+/// wykonane polecenia i przesłane pliki.
+pub struct Audit {
+    file: Option<Mutex<File>>,
+}
+
+impl Audit {
+    /// Otwarcie dziennika (dostępny tylko dla właściciela).
+    /// Bez ścieżki audyt jest wyłączony.
+    pub fn open(path: Option<&Path>) -> io::Result<Self> {
+        let file = match path {
+            Some(path) => {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                let file = OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .mode(0o600)
+                    .open(path)?;
+                Some(Mutex::new(file))
+            }
+            None => None,
+        };
+        Ok(Audit { file })
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("client-server").join("audit.log"))
+    }
+
+    /// Wykonane polecenie (wraz z parametrami i kodem wyniku).
+    pub fn command(&self, id: &Identity, request: &Request, answer: &Answer, duration: Duration) {
+        self.write(Record {
+            ts: Self::now(),
+            kind: Kind::Command,
+            sid: id.sid,
+            peer: &id.peer,
+            user: &id.user,
+            command: &request.command,
+            params: &request.params,
+            code: answer.code,
+            duration_ms: Some(duration.as_millis()),
+            path: None,
+            bytes: None,
+        });
+    }
+
+    /// Przesłany plik: tylko nazwa i rozmiar, nigdy zawartość.
+    pub fn transfer(&self, id: &Identity, command: &str, path: &str, bytes: usize) {
+        self.write(Record {
+            ts: Self::now(),
+            kind: Kind::Transfer,
+            sid: id.sid,
+            peer: &id.peer,
+            user: &id.user,
+            command,
+            params: &[],
+            code: 0,
+            duration_ms: None,
+            path: Some(path),
+            bytes: Some(bytes),
+        });
+    }
+
+    fn now() -> String {
+        Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string()
+    }
+
+    fn write(&self, record: Record) {
+        let Some(file) = &self.file else { return };
+        let mut line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(err) => {
+                logging::error("audit.encode").field("error", err).emit();
+                return;
+            }
+        };
+        line.push('\n');
+        if let Err(err) = file.lock().unwrap().write_all(line.as_bytes()) {
+            logging::error("audit.write").field("error", err).emit();
+        }
+    }
+}
@@ -0,0 +1,283 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use shared::data::message::MAX_FRAME;
+use crate::audit::Audit;
+use crate::history::History;
+use crate::logging::Level;
+
+/// Konfiguracja serwera.
+/// Odczytywana z pliku JSON wskazanego opcją `--config`, a jeśli
+/// jej nie podano, z `<config_dir>/client-server/server.json`.
+/// Brakujące pola przyjmują wartości domyślne.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Czas (w sekundach) na dokończenie poleceń przy zamykaniu serwera.
+    pub grace_period: u64,
+    /// Najmniej ważny poziom wpisów zapisywanych w logu.
+    pub log_level: Level,
+    /// Plik dziennika audytu, `null` wyłącza audyt.
+    pub audit_log: Option<PathBuf>,
+    /// Baza SQLite z historią sesji, poleceń i transferów, `null` ją wyłącza.
+    pub history_db: Option<PathBuf>,
+    /// Czy polecenie `sql --write` może zmieniać bazy danych.
+    pub allow_sql_write: bool,
+    /// Czy serwer proponuje klientom kompresję ramek.
+    pub compression: bool,
+    /// Czy serwer przyjmuje połączenia TCP.
+    pub tcp: bool,
+    /// Adresy TCP (`host:port`), na których nasłuchuje serwer.
+    /// Nazwa hosta oznacza wszystkie jej adresy, np. `localhost:25105`
+    /// to `127.0.0.1` i `::1`; adres IPv6 zapisujemy w nawiasach: `[::]:25105`.
+    pub listen: Vec<String>,
+    /// Gniazdo Unix, na którym serwer nasłuchuje obok TCP
+    /// (albo zamiast niego, z `"tcp": false`). Dostęp kontrolują prawa pliku.
+    pub unix_socket: Option<PathBuf>,
+    /// Prawa dostępu do gniazda Unix, ósemkowo (np. "660").
+    pub unix_socket_mode: String,
+    /// Czy użytkownikiem sesji przez gniazdo Unix jest właściciel
+    /// procesu klienta (`SO_PEERCRED`), bez podawania hasła.
+    pub peer_credentials: bool,
+    /// Użytkownicy serwera przypisani do uid klientów (dla `peer_credentials`).
+    /// Uid spoza tej listy to konto systemowe o tym uid.
+    pub unix_users: HashMap<u32, String>,
+    /// Użytkownicy sesji z `peer_credentials`, którzy mogą wykonywać
+    /// polecenia `admin` ("*" - wszyscy tacy użytkownicy). Sesje TCP
+    /// nie mają uwierzytelnionego użytkownika, więc nie mają do nich dostępu.
+    pub admins: Vec<String>,
+    /// Czy polecenia sesji użytkownika z `peer_credentials` wykonuje proces
+    /// z jego uid i gid (wymaga uruchomienia serwera jako root).
+    pub switch_user: bool,
+    /// Czy każda sesja wykonuje polecenia we własnym procesie roboczym,
+    /// więc awaria polecenia lub `cd` nie dotyczą innych sesji.
+    pub isolation: bool,
+    /// Adres HTTP z metrykami w formacie Prometheus (`GET /metrics`),
+    /// np. "127.0.0.1:9105". Bez adresu metryki są wyłączone.
+    pub metrics: Option<String>,
+    pub limits: Limits,
+    pub forwarding: Forwarding,
+}
+
+/// Ograniczenia liczby połączeń.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Limits {
+    /// Maksymalna liczba jednoczesnych sesji.
+    pub max_sessions: usize,
+    /// Maksymalna liczba jednoczesnych sesji z jednego adresu IP.
+    pub max_sessions_per_ip: usize,
+    /// Czas (w sekundach) na wykonanie handshake.
+    pub handshake_timeout: u64,
+    /// Po tylu nieudanych handshake w oknie `failure_window`
+    /// adres IP jest czasowo blokowany.
+    pub max_handshake_failures: u32,
+    /// Okno (w sekundach), w którym zliczamy nieudane handshake.
+    pub failure_window: u64,
+    /// Czas (w sekundach) blokady adresu IP.
+    pub ban_time: u64,
+    /// Największa ramka (w bajtach) przyjmowana od klienta po handshake.
+    pub max_frame_size: usize,
+    /// Maksymalna liczba jednocześnie otwartych kanałów sesji: poleceń,
+    /// przekierowań i połączeń przyjętych dla `R:`. Kolejne są odrzucane.
+    pub max_channels: usize,
+}
+
+/// Przekierowanie portów przez sesję (`forward`). Domyślnie wyłączone.
+/// Wzorce `host:port`: `*` zastępuje dowolny host lub port,
+/// `*.domena` - hosty w domenie, adres IPv6 w nawiasach.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Forwarding {
+    /// Cele, z którymi klient może się łączyć przez serwer (`L:`),
+    /// np. "db.internal:5432". Porównywana jest nazwa podana przez klienta.
+    pub allow: Vec<String>,
+    /// Adresy, na których klient może kazać serwerowi nasłuchiwać (`R:`),
+    /// np. "127.0.0.1:9000".
+    pub listen: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            grace_period: 10,
+            log_level: Level::Info,
+            audit_log: Audit::default_path(),
+            history_db: History::default_path(),
+            allow_sql_write: false,
+            compression: true,
+            tcp: true,
+            listen: vec!["0.0.0.0:25105".to_string(), "[::]:25105".to_string()],
+            unix_socket: None,
+            unix_socket_mode: "600".to_string(),
+            peer_credentials: false,
+            unix_users: HashMap::new(),
+            admins: Vec::new(),
+            switch_user: false,
+            isolation: false,
+            metrics: None,
+            limits: Limits::default(),
+            forwarding: Forwarding::default(),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_sessions: 64,
+            max_sessions_per_ip: 8,
+            handshake_timeout: 10,
+            max_handshake_failures: 5,
+            failure_window: 60,
+            ban_time: 300,
+            max_frame_size: MAX_FRAME,
+            max_channels: 64,
+        }
+    }
+}
+
+impl Config {
+    /// Odczyt konfiguracji.
+    /// Jawnie wskazany plik musi istnieć, domyślny - nie musi.
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            }
+        };
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("can't read config {}: {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("invalid config {}: {}", path.display(), e))
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("client-server").join("server.json"))
+    }
+
+    pub fn grace_period(&self) -> Duration {
+        Duration::from_secs(self.grace_period)
+    }
+
+    pub fn unix_socket_mode(&self) -> Result<u32, String> {
+        u32::from_str_radix(&self.unix_socket_mode, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o777)
+            .ok_or_else(|| format!("invalid unix_socket_mode: {}", self.unix_socket_mode))
+    }
+
+    pub fn is_admin(&self, user: &str) -> bool {
+        self.admins.iter().any(|admin| admin == "*" || admin == user)
+    }
+
+    /// Przepisanie do `new` ustawień, których nie da się zmienić bez restartu.
+    /// Zwraca nazwy tych, które w `new` były inne.
+    pub fn keep_restart_only(&self, new: &mut Config) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+        macro_rules! keep {
+            ($($field:ident),*) => {$(
+                if new.$field != self.$field {
+                    ignored.push(stringify!($field));
+                    new.$field = self.$field.clone();
+                }
+            )*};
+        }
+        keep!(tcp, listen, unix_socket, unix_socket_mode, audit_log, history_db, metrics);
+        // Pula wątków sesji ma rozmiar ustalony przy starcie.
+        if new.limits.max_sessions != self.limits.max_sessions {
+            ignored.push("limits.max_sessions");
+            new.limits.max_sessions = self.limits.max_sessions;
+        }
+        ignored
+    }
+
+    /// Adresy z `listen` po rozwiązaniu nazw, bez powtórzeń.
+    pub fn listen_addrs(&self) -> Result<Vec<SocketAddr>, String> {
+        let mut addrs = Vec::new();
+        for entry in &self.listen {
+            let resolved = entry.to_socket_addrs()
+                .map_err(|e| format!("invalid listen address {}: {}", entry, e))?;
+            for addr in resolved {
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+        }
+        Ok(addrs)
+    }
+}
+
+impl Limits {
+    pub fn handshake_timeout(&self) -> Duration {
+        Duration::from_secs(self.handshake_timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_addrs() {
+        let mut config = Config::default();
+        let addrs = config.listen_addrs().unwrap();
+        assert_eq!(addrs, vec![
+            SocketAddr::from(([0, 0, 0, 0], 25105)),
+            SocketAddr::from(([0u16; 8], 25105)),
+        ]);
+
+        config.listen = vec!["localhost:7000".into(), "127.0.0.1:7000".into(), "[::1]:7001".into()];
+        let addrs = config.listen_addrs().unwrap();
+        assert!(addrs.contains(&SocketAddr::from(([127, 0, 0, 1], 7000))));
+        assert!(addrs.contains(&SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 7001))));
+        assert_eq!(addrs.iter().filter(|addr| **addr == SocketAddr::from(([127, 0, 0, 1], 7000))).count(), 1);
+
+        config.listen = vec!["25105".into()];
+        assert!(config.listen_addrs().is_err());
+    }
+
+    #[test]
+    fn test_keep_restart_only() {
+        let old = Config::default();
+        let mut new = Config { allow_sql_write: true, history_db: None, admins: vec!["root".into()], ..Config::default() };
+        new.listen.push("127.0.0.1:7000".into());
+        new.limits.max_sessions = 1000;
+        new.limits.max_sessions_per_ip = 2;
+        assert_eq!(old.keep_restart_only(&mut new), ["listen", "history_db", "limits.max_sessions"]);
+        assert_eq!(new.listen, old.listen);
+        assert_eq!(new.limits.max_sessions, old.limits.max_sessions);
+        assert_eq!(new.limits.max_sessions_per_ip, 2);
+        assert_eq!(new.history_db, old.history_db);
+        assert!(new.allow_sql_write);
+        assert!(new.is_admin("root") && !new.is_admin("alice"));
+        assert!(!old.is_admin("alice"));
+    }
+}
@@ -0,0 +1,94 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+use shared::ufs::fileinfo::FileInfo;
+use crate::audit::Audit;
+use crate::config::Config;
+use crate::history::History;
+use crate::limits::Limiter;
+use crate::logging;
+use crate::metrics::Metrics;
+use crate::session::Sessions;
+
+/// Stan serwera współdzielony przez wszystkie sesje.
+pub struct Context {
+    /// Aktualna konfiguracja, wymieniana przez `admin reload`.
+    config: RwLock<Arc<Config>>,
+    /// Plik, z którego odczytano konfigurację (`--config`).
+    config_path: Option<String>,
+    pub limiter: Arc<Limiter>,
+    pub sessions: Sessions,
+    pub audit: Audit,
+    pub history: History,
+    pub metrics: Metrics,
+    /// Użytkownik, z którego uprawnieniami wykonywane są polecenia.
+    pub user: String,
+}
+
+impl Context {
+    /// `user` to konto, na które serwer przejdzie po starcie (`--user`),
+    /// domyślnie właściciel procesu.
+    pub fn new(config: Config, config_path: Option<String>, user: Option<String>) -> Result<Arc<Self>, Box<dyn Error>> {
+        let audit = Audit::open(config.audit_log.as_deref())?;
+        let history = History::open(config.history_db.as_deref())?;
+        let user = user.unwrap_or_else(|| FileInfo::user_name(unsafe { libc::geteuid() })
+            .unwrap_or_else(|_| "?".to_string()));
+        Ok(Arc::new(Context {
+            limiter: Limiter::new(config.limits.clone()),
+            config: RwLock::new(Arc::new(config)),
+            config_path,
+            sessions: Sessions::new(),
+            audit,
+            history,
+            metrics: Metrics::default(),
+            user,
+        }))
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Ponowny odczyt konfiguracji bez restartu serwera.
+    /// Nowe ustawienia obowiązują od kolejnych sesji i poleceń; zmiany
+    /// gniazd, audytu, historii i liczby sesji wymagają restartu, więc zostają stare.
+    /// Zwraca opis zmian dla operatora.
+    pub fn reload(&self) -> Result<Vec<String>, String> {
+        let mut config = Config::load(self.config_path.as_deref())?;
+        config.unix_socket_mode()?;
+        let old = self.config();
+        let ignored = old.keep_restart_only(&mut config);
+
+        logging::set_level(config.log_level);
+        self.limiter.set_limits(config.limits.clone());
+        *self.config.write().unwrap() = Arc::new(config);
+
+        logging::info("server.reload").field("ignored", ignored.join(",")).emit();
+        let mut report = vec!["configuration reloaded".to_string()];
+        if !ignored.is_empty() {
+            report.push(format!("restart required for: {}", ignored.join(", ")));
+        }
+        Ok(report)
+    }
+}
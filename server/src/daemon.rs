@@ -0,0 +1,155 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Potwierdzenie startu demona dla procesu, który go uruchomił.
+/// Bez `ready` (np. błąd startu) proces uruchamiający kończy się z kodem 1.
+pub struct Readiness {
+    pipe: File,
+}
+
+impl Readiness {
+    pub fn ready(mut self) {
+        let _ = self.pipe.write_all(&[1]);
+    }
+}
+
+/// Przejście w tryb demona: podwójny fork, nowa sesja (`setsid`),
+/// katalog `/`, standardowe wejście z `/dev/null`, wyjście i błędy do `log_file`.
+/// Musi być wywołane przed utworzeniem jakichkolwiek wątków.
+/// Proces, który uruchomił serwer, czeka na `Readiness::ready`.
+pub fn daemonize(log_file: Option<&Path>) -> io::Result<Readiness> {
+    // Plik logu otwieramy przed forkiem, żeby błąd był widoczny od razu.
+    let log = match log_file {
+        Some(path) => OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("can't open {}: {}", path.display(), e)))?,
+        None => OpenOptions::new().write(true).open("/dev/null")?,
+    };
+    let null = File::open("/dev/null")?;
+    let (mut reader, writer) = pipe()?;
+
+    if fork()? != 0 {
+        drop(writer);
+        let mut status = [0u8];
+        match reader.read(&mut status) {
+            Ok(1) => process::exit(0),
+            _ => {
+                eprintln!("server failed to start{}", log_file.map_or(String::new(), |path| format!(", see {}", path.display())));
+                process::exit(1);
+            }
+        }
+    }
+    drop(reader);
+    unsafe {
+        if libc::setsid() < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    // Drugi fork: demon nie jest liderem sesji, więc nie przejmie terminala.
+    if fork()? != 0 {
+        unsafe { libc::_exit(0) };
+    }
+    std::env::set_current_dir("/")?;
+    unsafe {
+        libc::umask(0o027);
+        for (from, to) in [(null.as_raw_fd(), 0), (log.as_raw_fd(), 1), (log.as_raw_fd(), 2)] {
+            if libc::dup2(from, to) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(Readiness { pipe: writer })
+}
+
+fn fork() -> io::Result<libc::pid_t> {
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        pid => Ok(pid),
+    }
+}
+
+fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (reader, writer) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    Ok((File::from(reader), File::from(writer)))
+}
+
+/// Plik z numerem procesu serwera, zablokowany (`flock`) przez cały czas pracy.
+/// Blokada, a nie samo istnienie pliku, oznacza działający serwer,
+/// więc plik pozostały po awarii nie przeszkadza w starcie.
+pub struct Pidfile {
+    path: PathBuf,
+    _file: File,
+}
+
+impl Pidfile {
+    pub fn create(path: &Path) -> io::Result<Pidfile> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("can't open {}: {}", path.display(), e)))?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } < 0 {
+            let why = io::Error::last_os_error();
+            if why.kind() != ErrorKind::WouldBlock {
+                return Err(why);
+            }
+            let mut pid = String::new();
+            let _ = file.read_to_string(&mut pid);
+            return Err(io::Error::new(ErrorKind::AddrInUse,
+                format!("server is already running (pid {}, {})", pid.trim(), path.display())));
+        }
+        file.set_len(0)?;
+        writeln!(file, "{}", process::id())?;
+        Ok(Pidfile { path: path.to_path_buf(), _file: file })
+    }
+}
+
+impl Drop for Pidfile {
+    fn drop(&mut self) {
+        // Po zmianie użytkownika usunięcie może się nie udać; blokada i tak znika.
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pidfile() {
+        let path = std::env::temp_dir().join(format!("server-test-{}.pid", process::id()));
+        let pidfile = Pidfile::create(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n", process::id()));
+        // Blokada dotyczy otwartego pliku, więc drugie otwarcie w tym samym procesie też jest odrzucone.
+        let err = Pidfile::create(&path).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::AddrInUse);
+        drop(pidfile);
+        assert!(!path.exists());
+        drop(Pidfile::create(&path).unwrap());
+    }
+}
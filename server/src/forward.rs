@@ -0,0 +1,273 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::Relaxed};
+use std::thread::{self, Scope};
+use std::time::Instant;
+use shared::data::{answer::Answer, request::Request};
+use shared::net::dial;
+use shared::net::forward::{host_port, FORWARDED_CMD, FORWARD_CMD, LISTEN_CMD};
+use shared::net::mux::{Channel, Mux};
+use shared::xerror::Error;
+use crate::context::Context;
+use crate::logging;
+use crate::session::{ChannelSlots, Identity, COMMANDS_TOTAL};
+use crate::{ACCEPT_POLL, STOP};
+
+/// Przekierowania portów jednej sesji.
+/// Pamiętamy drugie uchwyty połączeń, żeby przy końcu sesji przerwać tunele.
+pub(crate) struct Forwards {
+    /// Limit kanałów sesji, wspólny dla poleceń i przekierowań.
+    pub(crate) slots: ChannelSlots,
+    closed: AtomicBool,
+    streams: Mutex<HashMap<u64, TcpStream>>,
+    next_id: AtomicU64,
+}
+
+impl Forwards {
+    pub(crate) fn new(max_channels: usize) -> Self {
+        Forwards {
+            slots: ChannelSlots::new(max_channels),
+            closed: AtomicBool::new(false),
+            streams: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// `forward <host> <port>` (`L:`): połączenie z celem i tunel w kanale.
+    pub(crate) fn connect(&self, ctx: &Context, id: &Identity, mut channel: Channel, request: &Request) -> io::Result<()> {
+        let started = Instant::now();
+        let (host, port) = match endpoint(request) {
+            Ok(endpoint) => endpoint,
+            Err(answer) => return channel.send_answer(answer),
+        };
+        let target = host_port(host, port);
+        // Kod błędu to errno, żeby klient (np. proxy SOCKS) mógł rozróżnić przyczyny.
+        let result = match allowed(&ctx.config().forwarding.allow, host, port) {
+            true => dial::connect(&target).map_err(|e| {
+                // Błąd rozwiązania nazwy lub upływ czasu nie mają errno.
+                Error::new(e.raw_os_error().unwrap_or(libc::EHOSTUNREACH), &format!("can't connect to {}: {}", target, e))
+            }),
+            false => Err(Error::new(libc::EACCES, &format!("forwarding to {} is not allowed by server policy", target))),
+        };
+        let (answer, stream) = match result {
+            Ok(stream) => (Answer::new(0, "OK", FORWARD_CMD), Some(stream)),
+            Err(why) => (Answer::from(why), None),
+        };
+        record(ctx, id, request, &answer, started);
+        channel.send_answer(answer)?;
+        match stream {
+            Some(stream) => self.tunnel(id, channel, stream, "L", &target),
+            None => Ok(()),
+        }
+    } // fn connect
+
+    /// `forward-listen <adres> <port>` (`R:`): nasłuchiwanie do końca sesji,
+    /// każde połączenie idzie do klienta w nowym kanale.
+    pub(crate) fn listen<'scope>(
+        &'scope self,
+        scope: &'scope Scope<'scope, '_>,
+        mux: &'scope Mux,
+        ctx: &Context,
+        id: &'scope Identity,
+        mut channel: Channel,
+        request: &Request,
+    ) -> io::Result<()> {
+        let started = Instant::now();
+        let (host, port) = match endpoint(request) {
+            Ok(endpoint) => endpoint,
+            Err(answer) => return channel.send_answer(answer),
+        };
+        let addr = host_port(host, port);
+        let result = match allowed(&ctx.config().forwarding.listen, host, port) {
+            true => TcpListener::bind(&addr)
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+                .map_err(|e| format!("can't listen on {}: {}", addr, e)),
+            false => Err(format!("listening on {} is not allowed by server policy", addr)),
+        };
+        let answer = match &result {
+            Ok(listener) => Answer::new_with_data(0, "OK", LISTEN_CMD, vec![listener.local_addr()?.to_string()]),
+            Err(why) => Answer::from(Error::new(-1, why)),
+        };
+        record(ctx, id, request, &answer, started);
+        channel.send_answer(answer)?;
+        if let Ok(listener) = result {
+            id.log(logging::info("forward.listen")).field("addr", listener.local_addr()?).emit();
+            scope.spawn(move || self.accept_loop(scope, mux, id, listener));
+        }
+        Ok(())
+    } // fn listen
+
+    /// Przyjmowanie połączeń dla `R:` do końca sesji.
+    /// Połączenie ponad limit kanałów sesji jest od razu zamykane.
+    fn accept_loop<'scope>(
+        &'scope self,
+        scope: &'scope Scope<'scope, '_>,
+        mux: &'scope Mux,
+        id: &'scope Identity,
+        listener: TcpListener,
+    ) {
+        let port = listener.local_addr().map(|addr| addr.port()).unwrap_or_default();
+        while !self.closed.load(Relaxed) && !STOP.load(Relaxed) {
+            match listener.accept() {
+                Ok((stream, peer)) => match self.slots.acquire() {
+                    Some(slot) => {
+                        scope.spawn(move || {
+                            if let Err(why) = self.forwarded(mux, id, stream, port, peer) {
+                                id.log(logging::warn("forward.error")).field("peer", peer).field("error", why).emit();
+                            }
+                            drop(slot);
+                        });
+                    }
+                    None => {
+                        id.log(logging::warn("forward.refused")).field("peer", peer).field("max", self.slots.max()).emit();
+                        let _ = stream.shutdown(Shutdown::Both);
+                    }
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+                Err(e) => {
+                    id.log(logging::error("forward.accept")).field("port", port).field("error", e).emit();
+                    thread::sleep(ACCEPT_POLL);
+                }
+            }
+        }
+    } // fn accept_loop
+
+    /// Połączenie przyjęte dla `R:` - klient łączy się z celem i otwieramy tunel.
+    fn forwarded(&self, mux: &Mux, id: &Identity, stream: TcpStream, port: u16, peer: SocketAddr) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        let mut channel = mux.open()?;
+        channel.send_request(Request::new(FORWARDED_CMD.into(), vec![port.to_string(), peer.to_string()]))?;
+        let answer = channel.read_answer()?;
+        if answer.code != 0 {
+            return Err(io::Error::other(Error::from(answer).msg));
+        }
+        self.tunnel(id, channel, stream, "R", &peer.to_string())
+    }
+
+    /// Przeniesienie danych aż do zamknięcia połączenia (lub końca sesji).
+    /// `addr` to cel (`L`) albo adres, z którego przyszło połączenie (`R`).
+    /// Błąd tunelu to tylko wpis w logu.
+    fn tunnel(&self, id: &Identity, channel: Channel, stream: TcpStream, direction: &str, addr: &str) -> io::Result<()> {
+        let key = self.next_id.fetch_add(1, Relaxed);
+        self.streams.lock().unwrap().insert(key, stream.try_clone()?);
+        // Sesja mogła się skończyć przed rejestracją połączenia.
+        if self.closed.load(Relaxed) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        let started = Instant::now();
+        id.log(logging::info("forward.open"))
+            .field("channel", channel.id())
+            .field("dir", direction)
+            .field("addr", addr)
+            .emit();
+        let channel_id = channel.id();
+        let result = channel.tunnel(stream);
+        self.streams.lock().unwrap().remove(&key);
+
+        let entry = id.log(logging::info("forward.close"))
+            .field("channel", channel_id)
+            .field("dir", direction)
+            .field("addr", addr)
+            .field("duration_ms", started.elapsed().as_millis());
+        match result {
+            Ok((sent, received)) => entry.field("sent", sent).field("received", received).emit(),
+            Err(why) => entry.field("error", why).emit(),
+        }
+        Ok(())
+    } // fn tunnel
+
+    /// Koniec sesji: koniec nasłuchiwania dla `R:` i przerwanie tuneli.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Relaxed);
+        self.streams.lock().unwrap().values().for_each(|stream| {
+            let _ = stream.shutdown(Shutdown::Both);
+        });
+    }
+}
+
+/// Parametry `host port` żądania.
+fn endpoint(request: &Request) -> Result<(&str, u16), Answer> {
+    match request.params.as_slice() {
+        [host, port] => port.parse().map(|port| (host.as_str(), port))
+            .map_err(|_| Answer::from(Error::new(-1, &format!("invalid port: {}", port)))),
+        _ => Err(Answer::from(Error::new(-1, &format!("usage: {} <host> <port>", request.command)))),
+    }
+}
+
+/// Zapis żądania w logu, dzienniku audytu i historii, jak dla innych poleceń.
+fn record(ctx: &Context, id: &Identity, request: &Request, answer: &Answer, started: Instant) {
+    let duration = started.elapsed();
+    COMMANDS_TOTAL.fetch_add(1, Relaxed);
+    id.log(logging::info("request"))
+        .field("cmd", &request.command)
+        .field("code", answer.code)
+        .field("duration_ms", duration.as_millis())
+        .field("params", format!("{:?}", request.params))
+        .emit();
+    ctx.audit.command(id, request, answer, duration);
+    ctx.history.command(id, request, answer, duration);
+    ctx.metrics.command(&request.command, answer, duration);
+}
+
+/// Czy `host:port` pasuje do któregoś ze wzorców polityki.
+fn allowed(patterns: &[String], host: &str, port: u16) -> bool {
+    patterns.iter().any(|pattern| matches(pattern, host, port))
+}
+
+/// Wzorzec `host:port`: `*` to dowolny host lub port, `*.domena` - hosty w domenie.
+/// Nazwy porównujemy bez względu na wielkość liter.
+fn matches(pattern: &str, host: &str, port: u16) -> bool {
+    let Some((pattern_host, pattern_port)) = pattern.rsplit_once(':') else {
+        return false;
+    };
+    let pattern_host = pattern_host.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    let host_matches = pattern_host == "*" || pattern_host == host || pattern_host.strip_prefix("*.")
+        .is_some_and(|domain| host.strip_suffix(domain).is_some_and(|name| name.len() > 1 && name.ends_with('.')));
+    host_matches && (pattern_port == "*" || pattern_port.parse() == Ok(port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("db.internal:5432", "db.internal", 5432));
+        assert!(matches("db.internal:5432", "DB.Internal", 5432));
+        assert!(!matches("db.internal:5432", "db.internal", 5433));
+        assert!(matches("*.internal:*", "db.internal", 1));
+        assert!(!matches("*.internal:*", "internal", 1));
+        assert!(!matches("*.internal:*", "evilinternal", 1));
+        assert!(matches("*:22", "10.0.0.1", 22));
+        assert!(matches("[::1]:9000", "::1", 9000));
+        assert!(!matches("[::1]:9000", "::2", 9000));
+        assert!(!matches("db.internal", "db.internal", 5432));
+        assert!(!allowed(&[], "db.internal", 5432));
+        assert!(allowed(&["a:1".into(), "b:2".into()], "b", 2));
+    }
+}
@@ -0,0 +1,277 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use shared::data::{answer::Answer, request::Request};
+use std::fmt;
+use shared::from_row;
+use shared::sqlite::{migrate, schema_version, Connection, Migration, Result, Timestamp};
+use crate::logging;
+use crate::session::Identity;
+
+/// Kolejne wersje schematu bazy historii.
+/// Nowe zmiany dopisujemy na końcu, istniejących kroków nie zmieniamy.
+const MIGRATIONS: &[Migration] = &[
+    // Tabele tworzone są warunkowo, bo bazy sprzed wprowadzenia
+    // migracji mają już ten schemat.
+    Migration {
+        version: 1,
+        description: "sessions, commands and transfers",
+        sql: "
+            CREATE TABLE IF NOT EXISTS sessions (
+                id       INTEGER PRIMARY KEY AUTOINCREMENT,
+                sid      INTEGER NOT NULL,
+                peer     TEXT NOT NULL,
+                user     TEXT NOT NULL,
+                started  INTEGER NOT NULL,
+                ended    INTEGER,
+                reason   TEXT
+            );
+            CREATE TABLE IF NOT EXISTS commands (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id  INTEGER NOT NULL REFERENCES sessions(id),
+                ts          INTEGER NOT NULL,
+                command     TEXT NOT NULL,
+                params      TEXT NOT NULL,
+                code        INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS transfers (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id  INTEGER NOT NULL REFERENCES sessions(id),
+                ts          INTEGER NOT NULL,
+                command     TEXT NOT NULL,
+                path        TEXT NOT NULL,
+                bytes       INTEGER NOT NULL
+            );",
+    },
+    Migration {
+        version: 2,
+        description: "session indexes",
+        sql: "
+            CREATE INDEX commands_session ON commands (session_id);
+            CREATE INDEX transfers_session ON transfers (session_id);
+            CREATE INDEX sessions_peer ON sessions (peer);",
+    },
+];
+
+/// Historia sesji, poleceń i przesłanych plików w lokalnej bazie SQLite.
+/// Błędy zapisu historii są logowane, ale nie przerywają sesji.
+pub struct History {
+    conn: Option<Mutex<Connection>>,
+}
+
+impl History {
+    /// Otwarcie (utworzenie) bazy historii i uzupełnienie jej schematu.
+    /// Bez ścieżki historia jest wyłączona.
+    pub fn open(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(History { conn: None });
+        };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let mut conn = Connection::open(&path.to_string_lossy())?;
+        // Operatorzy mogą czytać bazę w trakcie pracy serwera.
+        conn.busy_timeout(5000)?;
+        conn.execute("PRAGMA journal_mode = WAL;")?;
+        let from = schema_version(&conn)?;
+        let to = migrate(&mut conn, MIGRATIONS)?;
+        if from != to {
+            logging::info("history.migrated")
+                .field("path", path.display())
+                .field("from", from)
+                .field("to", to)
+                .emit();
+        }
+        Ok(History { conn: Some(Mutex::new(conn)) })
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("client-server").join("history.db"))
+    }
+
+    /// Początek sesji. Zwraca identyfikator zapisu sesji w bazie.
+    pub fn session_started(&self, id: &Identity) -> Option<i64> {
+        self.write("history.session", |conn| {
+            conn.execute_with(
+                "INSERT INTO sessions (sid, peer, user, started) VALUES (?, ?, ?, ?)",
+                (id.sid, id.peer.as_str(), id.user.as_str(), Timestamp::now()))?;
+            Ok(conn.last_insert_id())
+        })
+    }
+
+    pub fn session_ended(&self, id: &Identity, reason: &str) {
+        let Some(session_id) = id.history_id else { return };
+        self.write("history.session", |conn| {
+            conn.execute_with(
+                "UPDATE sessions SET ended = ?, reason = ? WHERE id = ?",
+                (Timestamp::now(), reason, session_id))
+        });
+    }
+
+    pub fn command(&self, id: &Identity, request: &Request, answer: &Answer, duration: Duration) {
+        let Some(session_id) = id.history_id else { return };
+        self.write("history.command", |conn| {
+            conn.execute_with(
+                "INSERT INTO commands (session_id, ts, command, params, code, duration_ms) VALUES (?, ?, ?, ?, ?, ?)",
+                (session_id,
+                 Timestamp::now(),
+                 request.command.as_str(),
+                 request.params.join(" "),
+                 answer.code,
+                 duration.as_millis() as i64))
+        });
+    }
+
+    pub fn transfer(&self, id: &Identity, command: &str, path: &str, bytes: usize) {
+        let Some(session_id) = id.history_id else { return };
+        self.write("history.transfer", |conn| {
+            conn.execute_with(
+                "INSERT INTO transfers (session_id, ts, command, path, bytes) VALUES (?, ?, ?, ?, ?)",
+                (session_id, Timestamp::now(), command, path, bytes as i64))
+        });
+    }
+
+    /// Odczyt ostatnich wpisów wskazanego rodzaju (od najstarszego).
+    pub fn recent(&self, kind: &str, limit: i64) -> Result<Vec<String>> {
+        let sql = match kind {
+            "sessions" => "
+                SELECT * FROM (
+                    SELECT s.id, s.started AS ts, s.peer, s.user,
+                           printf('sid %d, %s', s.sid, COALESCE(s.reason, 'active')) AS what
+                    FROM sessions s ORDER BY s.id DESC LIMIT ?)
+                ORDER BY id",
+            "transfers" => "
+                SELECT * FROM (
+                    SELECT t.id, t.ts, s.peer, s.user,
+                           printf('%s %s (%d bytes)', t.command, t.path, t.bytes) AS what
+                    FROM transfers t JOIN sessions s ON s.id = t.session_id
+                    ORDER BY t.id DESC LIMIT ?)
+                ORDER BY id",
+            _ => "
+                SELECT * FROM (
+                    SELECT c.id, c.ts, s.peer, s.user,
+                           printf('%s -> %d (%d ms)', trim(c.command || ' ' || c.params), c.code, c.duration_ms) AS what
+                    FROM commands c JOIN sessions s ON s.id = c.session_id
+                    ORDER BY c.id DESC LIMIT ?)
+                ORDER BY id",
+        };
+        let Some(conn) = &self.conn else {
+            return Ok(vec!["history is disabled".to_string()]);
+        };
+        let entries = conn.lock().unwrap().query_as::<Entry>(sql, (limit,))?;
+        Ok(entries.iter().map(Entry::to_string).collect())
+    }
+
+    /// Ostatnie polecenia sesji o numerze `sid` (od najstarszego).
+    /// Numery sesji zaczynają się od nowa po restarcie serwera,
+    /// więc bierzemy najnowszą sesję o tym numerze.
+    pub fn session_commands(&self, sid: u32, limit: i64) -> Result<Vec<String>> {
+        let sql = "
+            SELECT * FROM (
+                SELECT c.id, c.ts, s.peer, s.user,
+                       printf('%s -> %d (%d ms)', trim(c.command || ' ' || c.params), c.code, c.duration_ms) AS what
+                FROM commands c JOIN sessions s ON s.id = c.session_id
+                WHERE s.id = (SELECT max(id) FROM sessions WHERE sid = ?)
+                ORDER BY c.id DESC LIMIT ?)
+            ORDER BY id";
+        let Some(conn) = &self.conn else {
+            return Ok(vec!["history is disabled".to_string()]);
+        };
+        let entries = conn.lock().unwrap().query_as::<Entry>(sql, (sid, limit))?;
+        Ok(entries.iter().map(Entry::to_string).collect())
+    }
+
+    fn write<T>(&self, event: &'static str, f: impl FnOnce(&Connection) -> Result<T>) -> Option<T> {
+        let conn = self.conn.as_ref()?;
+        match f(&conn.lock().unwrap()) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                logging::warn(event).field("error", err).emit();
+                None
+            }
+        }
+    }
+}
+
+/// Wpis historii w postaci do wyświetlenia.
+struct Entry {
+    ts: Timestamp,
+    peer: String,
+    user: String,
+    what: String,
+}
+from_row!(Entry { ts, peer, user, what });
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ts = chrono::DateTime::from_timestamp(self.ts.value(), 0)
+            .map(|dt| dt.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        write!(f, "{}  {:<21} {:<10} {}", ts, self.peer, self.user, self.what)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Przykładowe dane zapisywane w bazie o danej wersji schematu.
+    fn fill(conn: &Connection, version: usize) {
+        if version >= 1 {
+            conn.execute_with(
+                "INSERT INTO sessions (sid, peer, user, started) VALUES (?, ?, ?, ?)",
+                (7, "127.0.0.1:5000", "piotr", 1000)).unwrap();
+            conn.execute_with(
+                "INSERT INTO commands (session_id, ts, command, params, code, duration_ms) VALUES (?, ?, ?, ?, ?, ?)",
+                (1, 1001, "ls", "-l", 0, 3)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_upgrade_from_each_version() {
+        for version in 0..=MIGRATIONS.len() {
+            let mut conn = Connection::open_in_memory().unwrap();
+            assert_eq!(migrate(&mut conn, &MIGRATIONS[..version]).unwrap() as usize, version);
+            fill(&conn, version);
+
+            assert_eq!(migrate(&mut conn, MIGRATIONS).unwrap() as usize, MIGRATIONS.len());
+            assert_eq!(schema_version(&conn).unwrap() as usize, MIGRATIONS.len());
+            let rows = conn.query("SELECT * FROM commands", ()).unwrap();
+            assert_eq!(rows.len(), if version >= 1 { 1 } else { 0 }, "upgrade from version {}", version);
+        }
+    }
+
+    #[test]
+    fn test_upgrade_unversioned() {
+        // Baza utworzona przed wprowadzeniem migracji: tabele bez metadanych.
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute(MIGRATIONS[0].sql).unwrap();
+        fill(&conn, 1);
+        assert_eq!(migrate(&mut conn, MIGRATIONS).unwrap() as usize, MIGRATIONS.len());
+        assert_eq!(conn.query("SELECT * FROM sessions", ()).unwrap().len(), 1);
+    }
+}
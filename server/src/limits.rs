@@ -0,0 +1,340 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::time::{Duration, Instant};
+use crate::config::Limits;
+use crate::logging;
+
+/// Liczniki odrzuconych i przyjętych połączeń (dla operatora).
+#[derive(Default)]
+pub struct Counters {
+    pub accepted: AtomicU64,
+    pub rejected_busy: AtomicU64,
+    pub rejected_per_ip: AtomicU64,
+    pub rejected_banned: AtomicU64,
+    pub handshake_failures: AtomicU64,
+    pub bans: AtomicU64,
+}
+
+/// Źródło połączenia, według którego liczone są limity i blokady:
+/// adres IP, a dla gniazda Unix - uid procesu klienta.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Origin {
+    Ip(IpAddr),
+    Uid(u32),
+}
+
+impl From<IpAddr> for Origin {
+    fn from(ip: IpAddr) -> Self {
+        Origin::Ip(ip)
+    }
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Ip(ip) => write!(f, "{}", ip),
+            Origin::Uid(uid) => write!(f, "uid:{}", uid),
+        }
+    }
+}
+
+/// Wynik próby przyjęcia połączenia.
+pub enum Admission {
+    /// Połączenie przyjęte - zwolnienie zezwolenia zwalnia miejsce.
+    Accepted(Permit),
+    /// Przekroczony limit - klient dostaje uprzejmą odmowę.
+    Rejected(&'static str),
+    /// Adres jest czasowo zablokowany - zamykamy połączenie bez słowa.
+    Banned,
+}
+
+/// Nieudane handshake z jednego adresu.
+struct Failures {
+    count: u32,
+    window_start: Instant,
+    banned_until: Option<Instant>,
+}
+
+impl Failures {
+    /// Czy wpis nic już nie znaczy: blokada minęła albo okno się skończyło.
+    fn expired(&self, now: Instant, window: Duration) -> bool {
+        match self.banned_until {
+            Some(until) => until <= now,
+            None => now.duration_since(self.window_start) > window,
+        }
+    }
+}
+
+/// Przy tylu wpisach o nieudanych handshake usuwamy nieaktualne.
+const PRUNE_MIN: usize = 1024;
+
+#[derive(Default)]
+struct State {
+    active: usize,
+    per_ip: HashMap<Origin, usize>,
+    failures: HashMap<Origin, Failures>,
+    /// Liczba wpisów w `failures`, przy której je przeglądamy.
+    prune_at: usize,
+}
+
+impl State {
+    /// Usunięcie nieaktualnych wpisów o nieudanych handshake, żeby
+    /// próby z wielu adresów nie powiększały mapy bez końca.
+    /// Próg rośnie razem z liczbą aktualnych wpisów, więc koszt jest stały.
+    fn prune(&mut self, now: Instant, window: Duration) {
+        self.failures.retain(|_, failures| !failures.expired(now, window));
+        self.prune_at = PRUNE_MIN.max(2 * self.failures.len());
+    }
+}
+
+/// Strażnik limitów połączeń.
+pub struct Limiter {
+    limits: Mutex<Limits>,
+    state: Mutex<State>,
+    pub counters: Counters,
+}
+
+impl Limiter {
+    pub fn new(limits: Limits) -> Arc<Self> {
+        Arc::new(Limiter {
+            limits: Mutex::new(limits),
+            state: Mutex::new(State::default()),
+            counters: Counters::default(),
+        })
+    }
+
+    /// Decyzja, czy przyjąć połączenie ze źródła `origin`.
+    pub fn admit(self: &Arc<Self>, origin: impl Into<Origin>) -> Admission {
+        let origin = origin.into();
+        let limits = self.limits();
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        if let Some(failures) = state.failures.get(&origin) {
+            match failures.banned_until {
+                Some(until) if until > now => {
+                    self.counters.rejected_banned.fetch_add(1, Relaxed);
+                    return Admission::Banned;
+                }
+                Some(_) => { state.failures.remove(&origin); }
+                None => (),
+            }
+        }
+        if state.active >= limits.max_sessions {
+            self.counters.rejected_busy.fetch_add(1, Relaxed);
+            return Admission::Rejected("Server is busy, try again later.");
+        }
+        let from_ip = state.per_ip.get(&origin).copied().unwrap_or(0);
+        if from_ip >= limits.max_sessions_per_ip {
+            self.counters.rejected_per_ip.fetch_add(1, Relaxed);
+            return Admission::Rejected("Too many connections from your address.");
+        }
+
+        state.active += 1;
+        *state.per_ip.entry(origin).or_insert(0) += 1;
+        self.counters.accepted.fetch_add(1, Relaxed);
+        Admission::Accepted(Permit { limiter: self.clone(), origin })
+    }
+
+    /// Rejestracja nieudanego handshake.
+    /// Po przekroczeniu limitu w oknie czasowym adres zostaje zablokowany.
+    pub fn handshake_failed(&self, origin: impl Into<Origin>) {
+        let origin = origin.into();
+        self.counters.handshake_failures.fetch_add(1, Relaxed);
+        let limits = self.limits();
+        let window = Duration::from_secs(limits.failure_window);
+        let now = Instant::now();
+        
+        let mut state = self.state.lock().unwrap();
+        if state.failures.len() >= state.prune_at {
+            state.prune(now, window);
+        }
+        let failures = state.failures.entry(origin).or_insert(Failures {
+            count: 0,
+            window_start: now,
+            banned_until: None,
+        });
+        if now.duration_since(failures.window_start) > window {
+            failures.count = 0;
+            failures.window_start = now;
+        }
+        failures.count += 1;
+        if failures.count >= limits.max_handshake_failures && failures.banned_until.is_none() {
+            failures.banned_until = Some(now + Duration::from_secs(limits.ban_time));
+            self.counters.bans.fetch_add(1, Relaxed);
+            logging::warn("session.banned")
+                .field("peer", origin)
+                .field("failures", failures.count)
+                .field("ban_s", limits.ban_time)
+                .emit();
+        }
+    }
+
+    /// Liczba aktualnie zablokowanych adresów.
+    pub fn banned(&self) -> usize {
+        let now = Instant::now();
+        self.state.lock().unwrap().failures.values()
+            .filter(|f| f.banned_until.is_some_and(|until| until > now))
+            .count()
+    }
+    
+    fn limits(&self) -> Limits {
+        self.limits.lock().unwrap().clone()
+    }
+
+    /// Nowe limity (po przeładowaniu konfiguracji). Dotyczą kolejnych połączeń,
+    /// przyjęte sesje pozostają.
+    pub fn set_limits(&self, limits: Limits) {
+        *self.limits.lock().unwrap() = limits;
+    }
+
+    pub fn active(&self) -> usize {
+        self.state.lock().unwrap().active
+    }
+
+    /// Opis liczników w postaci tekstowej (dla operatora).
+    pub fn report(&self) -> Vec<String> {
+        let c = &self.counters;
+        let limits = self.limits();
+        vec![
+            format!("active sessions:     {} (max {}, max per ip {})",
+                    self.active(), limits.max_sessions, limits.max_sessions_per_ip),
+            format!("accepted:            {}", c.accepted.load(Relaxed)),
+            format!("rejected (busy):     {}", c.rejected_busy.load(Relaxed)),
+            format!("rejected (per ip):   {}", c.rejected_per_ip.load(Relaxed)),
+            format!("rejected (banned):   {}", c.rejected_banned.load(Relaxed)),
+            format!("handshake failures:  {}", c.handshake_failures.load(Relaxed)),
+            format!("bans:                {} ({} active)", c.bans.load(Relaxed), self.banned()),
+        ]
+    }
+
+    fn release(&self, origin: Origin) {
+        let mut state = self.state.lock().unwrap();
+        state.active -= 1;
+        if let Some(n) = state.per_ip.get_mut(&origin) {
+            *n -= 1;
+            if *n == 0 {
+                state.per_ip.remove(&origin);
+            }
+        }
+    }
+}
+
+/// Zezwolenie na jedną sesję.
+/// Miejsce jest zwalniane, gdy zezwolenie przestaje istnieć.
+pub struct Permit {
+    limiter: Arc<Limiter>,
+    origin: Origin,
+}
+
+impl Permit {
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+    pub fn limiter(&self) -> &Limiter {
+        &self.limiter
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release(self.origin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Limits {
+        Limits {
+            max_sessions: 3,
+            max_sessions_per_ip: 2,
+            max_handshake_failures: 2,
+            ..Limits::default()
+        }
+    }
+
+    #[test]
+    fn test_session_limits() {
+        let limiter = Limiter::new(limits());
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let p1 = limiter.admit(a);
+        let p2 = limiter.admit(a);
+        assert!(matches!(p1, Admission::Accepted(_)));
+        assert!(matches!(p2, Admission::Accepted(_)));
+        assert!(matches!(limiter.admit(a), Admission::Rejected(_)));
+
+        let p3 = limiter.admit(b);
+        assert!(matches!(p3, Admission::Accepted(_)));
+        assert!(matches!(limiter.admit(b), Admission::Rejected(_)));
+        assert_eq!(limiter.active(), 3);
+
+        // Zwolnienie zezwolenia zwalnia miejsce.
+        drop(p1);
+        assert_eq!(limiter.active(), 2);
+        assert!(matches!(limiter.admit(a), Admission::Accepted(_)));
+        assert_eq!(limiter.counters.rejected_per_ip.load(Relaxed), 1);
+        assert_eq!(limiter.counters.rejected_busy.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn test_ban() {
+        let limiter = Limiter::new(limits());
+        let ip: IpAddr = "::1".parse().unwrap();
+
+        limiter.handshake_failed(ip);
+        assert!(matches!(limiter.admit(ip), Admission::Accepted(_)));
+        limiter.handshake_failed(ip);
+        assert!(matches!(limiter.admit(ip), Admission::Banned));
+        assert_eq!(limiter.banned(), 1);
+        assert_eq!(limiter.counters.bans.load(Relaxed), 1);
+        // Blokada dotyczy tylko tego źródła.
+        assert!(matches!(limiter.admit(Origin::Uid(1000)), Admission::Accepted(_)));
+    }
+
+    #[test]
+    fn test_prune() {
+        let limiter = Limiter::new(limits());
+        let (a, b): (IpAddr, IpAddr) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        limiter.handshake_failed(a);
+        limiter.handshake_failed(b);
+        limiter.handshake_failed(b);
+
+        let window = Duration::from_secs(limits().failure_window);
+        let mut state = limiter.state.lock().unwrap();
+        // Po końcu okna zostaje tylko zablokowany adres, po końcu blokady - nic.
+        state.prune(Instant::now() + window + Duration::from_secs(1), window);
+        assert_eq!(state.failures.keys().collect::<Vec<_>>(), [&Origin::Ip(b)]);
+        state.prune(Instant::now() + Duration::from_secs(limits().ban_time + 1), window);
+        assert!(state.failures.is_empty());
+        assert_eq!(state.prune_at, PRUNE_MIN);
+    }
+}
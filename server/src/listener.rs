@@ -0,0 +1,172 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use shared::net::transport::{peer_credentials, Transport};
+use socket2::{Domain, Socket, Type};
+use crate::limits::Origin;
+
+/// Długość kolejki połączeń czekających na accept (jak w `TcpListener::bind`).
+const BACKLOG: i32 = 128;
+
+/// Gniazdo nasłuchujące: TCP albo Unix.
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, SocketFile),
+}
+
+/// Przyjęte połączenie.
+pub struct Incoming {
+    pub transport: Box<dyn Transport>,
+    /// Źródło połączenia dla limitów i blokad.
+    pub origin: Origin,
+    /// Uid procesu klienta (tylko gniazdo Unix).
+    pub uid: Option<u32>,
+}
+
+/// Plik gniazda Unix, usuwany, gdy serwer przestaje nasłuchiwać.
+pub struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl Listener {
+    /// Gniazdo TCP. Gniazdo IPv6 przyjmuje tylko połączenia IPv6,
+    /// dzięki czemu `0.0.0.0` i `[::]` mogą nasłuchiwać na tym samym porcie.
+    pub fn tcp(addr: SocketAddr) -> io::Result<Listener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+        if addr.is_ipv6() {
+            socket.set_only_v6(true)?;
+        }
+        socket.set_reuse_address(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(BACKLOG)?;
+        Ok(Listener::Tcp(socket.into()))
+    }
+
+    /// Gniazdo Unix z prawami dostępu `mode`.
+    /// Plik pozostawiony przez serwer, który już nie działa, jest usuwany.
+    pub fn unix(path: &Path, mode: u32) -> io::Result<Listener> {
+        if path.exists() {
+            if !fs::metadata(path)?.file_type().is_socket() {
+                return Err(io::Error::new(ErrorKind::AlreadyExists, format!("{} is not a socket", path.display())));
+            }
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(ErrorKind::AddrInUse, format!("{} is in use", path.display())));
+            }
+            fs::remove_file(path)?;
+        }
+        // Do czasu ustawienia praw gniazdo jest dostępne tylko dla właściciela.
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(path);
+        unsafe { libc::umask(umask) };
+        let listener = Listener::Unix(listener?, SocketFile(path.to_path_buf()));
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        Ok(listener)
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Listener::Tcp(listener) => listener.set_nonblocking(nonblocking),
+            Listener::Unix(listener, _) => listener.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Przyjęcie połączenia. Połączenie jest zawsze blokujące,
+    /// także gdy gniazdo nasłuchujące nie jest.
+    pub fn accept(&self) -> io::Result<Incoming> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Incoming { transport: Box::new(stream), origin: addr.ip().into(), uid: None })
+            }
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                let uid = peer_credentials(&stream)?.uid;
+                Ok(Incoming { transport: Box::new(stream), origin: Origin::Uid(uid), uid: Some(uid) })
+            }
+        }
+    }
+
+    /// Adres do logu, np. `0.0.0.0:25105` albo `unix:/run/server.sock`.
+    pub fn addr(&self) -> String {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().map(|addr| addr.to_string()).unwrap_or_default(),
+            Listener::Unix(_, file) => format!("unix:{}", file.0.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_listener() {
+        let path = std::env::temp_dir().join(format!("server-test-{}.sock", std::process::id()));
+        let listener = Listener::unix(&path, 0o660).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o660);
+        // Drugi serwer na tym samym gnieździe - błąd, a nie przejęcie pliku.
+        assert_eq!(Listener::unix(&path, 0o600).err().map(|e| e.kind()), Some(ErrorKind::AddrInUse));
+
+        let _client = UnixStream::connect(&path).unwrap();
+        let incoming = listener.accept().unwrap();
+        let uid = unsafe { libc::geteuid() };
+        assert_eq!(incoming.uid, Some(uid));
+        assert_eq!(incoming.origin, Origin::Uid(uid));
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_dual_stack() {
+        let v4 = Listener::tcp(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let port = match &v4 {
+            Listener::Tcp(listener) => listener.local_addr().unwrap().port(),
+            _ => unreachable!(),
+        };
+        // Ten sam port na IPv6 - bez IPV6_V6ONLY byłby zajęty przez IPv4.
+        let v6 = match Listener::tcp(SocketAddr::from(([0u16; 8], port))) {
+            Ok(listener) => listener,
+            Err(e) if e.raw_os_error() == Some(libc::EAFNOSUPPORT) => return,
+            Err(e) => panic!("{}", e),
+        };
+        let _client = std::net::TcpStream::connect(("::1", port)).unwrap();
+        let incoming = v6.accept().unwrap();
+        assert_eq!(incoming.origin, Origin::Ip("::1".parse().unwrap()));
+        let _client = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let incoming = v4.accept().unwrap();
+        assert_eq!(incoming.origin, Origin::Ip("127.0.0.1".parse().unwrap()));
+        assert_eq!(v6.addr(), format!("[::]:{}", port));
+    }
+}
@@ -0,0 +1,136 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU8, Ordering::Relaxed};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+/// Poziom ważności wpisu w logu.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn label(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Relaxed)
+}
+
+pub fn error(event: &'static str) -> Entry { Entry::new(Level::Error, event) }
+pub fn warn(event: &'static str) -> Entry { Entry::new(Level::Warn, event) }
+pub fn info(event: &'static str) -> Entry { Entry::new(Level::Info, event) }
+pub fn debug(event: &'static str) -> Entry { Entry::new(Level::Debug, event) }
+
+/// Jeden wpis w logu: zdarzenie i pola w postaci klucz=wartość.
+/// Wpis jest zapisywany (na stderr) dopiero przez `emit`,
+/// a pola wpisów z wyłączonego poziomu nie są nawet formatowane.
+pub struct Entry {
+    level: Level,
+    event: &'static str,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Entry {
+    fn new(level: Level, event: &'static str) -> Self {
+        Entry { level, event, fields: vec![] }
+    }
+
+    pub fn field(mut self, key: &'static str, value: impl Display) -> Self {
+        if enabled(self.level) {
+            self.fields.push((key, value.to_string()));
+        }
+        self
+    }
+
+    pub fn emit(self) {
+        if !enabled(self.level) {
+            return;
+        }
+        let mut line = format!("{} {:<5} {}",
+                               Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+                               self.level.label(),
+                               self.event);
+        self.fields.iter().for_each(|(key, value)| {
+            line.push(' ');
+            line.push_str(key);
+            line.push('=');
+            line.push_str(&quote(value));
+        });
+        line.push('\n');
+        // Cała linia jednym zapisem, aby wpisy z różnych wątków się nie przeplatały.
+        let _ = io::stderr().lock().write_all(line.as_bytes());
+    }
+}
+
+/// Wartości ze spacjami, cudzysłowami itp. ujmujemy w cudzysłów.
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty() && value.chars().all(|c| c.is_ascii_graphic() && c != '"' && c != '=');
+    match plain {
+        true => value.to_string(),
+        false => format!("{:?}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("127.0.0.1:25105"), "127.0.0.1:25105");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("no such file"), "\"no such file\"");
+        assert_eq!(quote("a=\"b\""), "\"a=\\\"b\\\"\"");
+    }
+
+    #[test]
+    fn test_levels() {
+        assert!(Level::Error < Level::Debug);
+        set_level(Level::Warn);
+        assert!(enabled(Level::Error));
+        assert!(!enabled(Level::Info));
+        assert!(info("x").field("k", 1).fields.is_empty());
+        set_level(Level::Info);
+    }
+}
@@ -22,158 +22,396 @@
 
 extern crate core;
 
+mod account;
+mod admin;
+#[cfg(feature = "async")]
+mod async_server;
+mod audit;
+mod config;
+mod context;
+mod daemon;
+mod forward;
+mod history;
+mod limits;
+mod listener;
+mod logging;
+mod metrics;
+mod session;
+mod worker;
+
 use std::error::Error;
-use std::io::ErrorKind;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering::Relaxed};
-use std::net::*;
-use std::{io, thread};
-use crossbeam_channel::{bounded, select, unbounded, Receiver, Sender};
-use shared::data::answer::Answer;
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam_channel::{bounded, select, Receiver, Sender};
 use shared::net::connector::{ConnectionSide, Connector};
-use shared::executor::Executor;
+use shared::net::transport::Transport;
+use crate::account::Account;
+use crate::config::Config;
+use crate::context::Context;
+use crate::daemon::Pidfile;
+use crate::history::History;
+use crate::limits::Admission;
+use crate::listener::{Incoming, Listener};
+use crate::session::{handle_client, serve_stdio, Sessions, COMMANDS_TOTAL, SESSIONS_DRAINED, SESSIONS_TOTAL};
 
 static STOP: AtomicBool = AtomicBool::new(false);
+static FORCE: AtomicBool = AtomicBool::new(false);
 static TASK_COUNT: AtomicU32 = AtomicU32::new(0);
 static TASK_ID: AtomicU32 = AtomicU32::new(0);
 
-fn ctrlc_handler() -> Result<Receiver<()>, ctrlc::Error> {
-    let (sender, receiver) = unbounded();
+/// Ile odmów może czekać na wysłanie. Przy większym zalewie
+/// połączeń nadmiarowe są zamykane bez odpowiedzi.
+const REJECT_QUEUE: usize = 16;
+/// Czas na odczyt identyfikatora klienta przy odmowie połączenia.
+const REJECT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Co jaki czas wątek accept sprawdza, czy ma kończyć pracę.
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+/// Co jaki czas sprawdzamy, czy wszystkie sesje już się zakończyły.
+const DRAIN_POLL: Duration = Duration::from_millis(50);
+
+/// Obsługa Ctrl-C oraz SIGTERM (i SIGHUP).
+/// Pierwszy sygnał rozpoczyna łagodne zamykanie serwera,
+/// drugi wymusza natychmiastowe zamknięcie wszystkich sesji.
+fn signal_handler() -> Result<Receiver<()>, ctrlc::Error> {
+    let (sender, receiver) = bounded(1);
 
     ctrlc::set_handler(move || {
-        STOP.store(true, std::sync::atomic::Ordering::Relaxed);
-        let n = TASK_COUNT.load(std::sync::atomic::Ordering::Relaxed);
-        eprintln!("Received {} tasks to stop", n);
-        
-        for _ in 0..n {
-            sender.send(()).unwrap();       
-        };
-    }).expect("Error setting Ctrl-C handler");
+        if STOP.swap(true, Relaxed) {
+            FORCE.store(true, Relaxed);
+            logging::warn("server.force_stop").emit();
+        } else {
+            logging::info("server.stop").field("sessions", TASK_COUNT.load(Relaxed)).emit();
+        }
+        let _ = sender.try_send(());
+    })?;
     
     Ok(receiver)
 }
 
-fn accept(listener: &TcpListener, sender: Sender<TcpStream>) {
-    match listener.accept() {
-        Ok(conn) => {
-            sender.send(conn.0).unwrap();
+/// Przyjmowanie połączeń z jednego gniazda do momentu otrzymania sygnału stop.
+/// Każde gniazdo ma własny wątek. Gniazda nasłuchujące są nieblokujące,
+/// więc możemy regularnie sprawdzać flagę STOP. Po wyjściu z pętli gniazdo
+/// jest zamykane i nowi klienci dostają odmowę połączenia.
+fn accept_loop(listener: Listener, sender: Sender<Incoming>) {
+    while !STOP.load(Relaxed) {
+        match listener.accept() {
+            Ok(incoming) => {
+                if sender.send(incoming).is_err() {
+                    return;
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+            Err(e) => logging::error("accept").field("addr", listener.addr()).field("error", e).emit(),
         }
-        Err(e) => {
-            eprintln!("Error accepting connection: {}", e);
+    }
+}
+
+/// Opcje z linii poleceń.
+#[derive(Default)]
+struct Options {
+    /// `--config <plik>`: plik konfiguracyjny.
+    config: Option<String>,
+    /// `--migrate-only`: aktualizacja schematu bazy historii i koniec pracy.
+    migrate_only: bool,
+    /// `--async`: serwer asynchroniczny (wymaga cechy `async`).
+    async_mode: bool,
+    /// `--stdio`: jedna sesja przez standardowe wejście i wyjście.
+    stdio: bool,
+    /// `--daemon`: praca w tle, bez terminala.
+    daemon: bool,
+    /// `--pidfile <plik>`: plik z numerem procesu.
+    pidfile: Option<PathBuf>,
+    /// `--log-file <plik>`: log demona (domyślnie log jest pomijany).
+    log_file: Option<PathBuf>,
+    /// `--user <nazwa>`, `--group <nazwa>`: użytkownik i grupa,
+    /// na które serwer przechodzi po otwarciu gniazd.
+    user: Option<String>,
+    group: Option<String>,
+}
+
+fn options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => match args.next() {
+                Some(value) => options.config = Some(value),
+                None => return Err("--config requires a file name".into()),
+            },
+            "--migrate-only" => options.migrate_only = true,
+            "--stdio" => options.stdio = true,
+            "--daemon" => options.daemon = true,
+            "--pidfile" | "--log-file" | "--user" | "--group" => {
+                let Some(value) = args.next() else {
+                    return Err(format!("{} requires a value", arg));
+                };
+                match arg.as_str() {
+                    "--pidfile" => options.pidfile = Some(absolute(value)?),
+                    "--log-file" => options.log_file = Some(absolute(value)?),
+                    "--user" => options.user = Some(value),
+                    _ => options.group = Some(value),
+                }
+            }
+            #[cfg(feature = "async")]
+            "--async" => options.async_mode = true,
+            #[cfg(not(feature = "async"))]
+            "--async" => return Err("--async requires the server built with --features async".into()),
+            _ => return Err(format!("unknown option: {}", arg)),
         }
     }
+    if options.log_file.is_some() && !options.daemon {
+        return Err("--log-file requires --daemon".into());
+    }
+    if options.daemon && options.stdio {
+        return Err("--daemon can't be used with --stdio".into());
+    }
+    // Demon pracuje w katalogu `/`, a `admin reload` czyta plik ponownie.
+    if let Some(config) = &options.config {
+        options.config = Some(absolute(config)?.to_string_lossy().into_owned());
+    }
+    Ok(options)
+}
+
+fn absolute(path: impl AsRef<Path>) -> Result<PathBuf, String> {
+    std::path::absolute(path.as_ref()).map_err(|e| format!("{}: {}", path.as_ref().display(), e))
+}
+
+/// Przejście na użytkownika i grupę z `--user` i `--group`
+/// (bez `--group` - grupa podstawowa konta).
+fn drop_privileges(account: Option<&Account>, gid: Option<u32>) -> io::Result<()> {
+    match (account, gid) {
+        (Some(account), gid) => account.switch(gid.unwrap_or(account.gid))?,
+        (None, Some(gid)) => account::switch_group(gid)?,
+        (None, None) => (),
+    }
+    logging::info("server.privileges")
+        .field("uid", unsafe { libc::getuid() })
+        .field("gid", unsafe { libc::getgid() })
+        .emit();
+    Ok(())
+}
+
+/// Wątek wysyłający odmowy połączenia.
+/// Odmowa wymaga odczytu identyfikatora klienta, więc nie może
+/// blokować ani wątku accept, ani puli sesji.
+fn rejector() -> Sender<(Box<dyn Transport>, &'static str)> {
+    let (sender, receiver) = bounded::<(Box<dyn Transport>, &'static str)>(REJECT_QUEUE);
+    thread::spawn(move || {
+        for (stream, reason) in receiver {
+            if stream.set_read_timeout(Some(REJECT_TIMEOUT)).is_err() {
+                continue;
+            }
+            let _ = Connector::new(stream, ConnectionSide::Server).reject(reason);
+        }
+    });
+    sender
 }
 
 fn main() -> Result<(), Box<dyn Error>>{
-    let ctrl_receiver = ctrlc_handler()?;
-    let (accept_sender, accept_receiver) = bounded::<TcpStream>(1);
+    // Proces roboczy sesji uruchamiany przez `Worker::start`.
+    if std::env::args().nth(1).as_deref() == Some(worker::WORKER_ARG) {
+        std::process::exit(worker::main());
+    }
+    let started = Instant::now();
+    let options = options()?;
+    let config = Config::load(options.config.as_deref())?;
+    logging::set_level(config.log_level);
+    if options.migrate_only {
+        return migrate_only(&config);
+    }
+    if options.stdio {
+        // Sygnał kończy sesję tak jak w trybie sieciowym - z powiadomieniem klienta.
+        let ctx = Context::new(config, options.config.clone(), None)?;
+        let _ctrl_receiver = signal_handler()?;
+        return Ok(serve_stdio(&ctx)?);
+    }
+    if config.switch_user && options.user.is_some() {
+        return Err("switch_user needs root privileges and can't be used with --user".into());
+    }
+    if config.switch_user && unsafe { libc::geteuid() } != 0 {
+        logging::warn("server.switch_user").field("error", "the server is not running as root").emit();
+    }
+    // Fork przed utworzeniem jakichkolwiek wątków (pula, obsługa sygnałów).
+    // Konto i grupę sprawdzamy, zanim serwer przejdzie w tło.
+    let account = options.user.as_deref().map(Account::by_name).transpose().map_err(|e| e.to_string())?;
+    let gid = options.group.as_deref().map(account::group_id).transpose().map_err(|e| e.to_string())?;
+    let readiness = options.daemon.then(|| daemon::daemonize(options.log_file.as_deref()))
+        .transpose().map_err(|e| e.to_string())?;
+    let _pidfile = options.pidfile.as_deref().map(Pidfile::create).transpose().map_err(|e| e.to_string())?;
+    // Każda sesja zajmuje wątek puli na cały czas trwania,
+    // więc pula musi pomieścić maksymalną liczbę sesji.
+    if !options.async_mode {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(config.limits.max_sessions)
+            .thread_name(|i| format!("session-{}", i))
+            .build_global()?;
+    }
+
+    // Kontekst (w tym migracja bazy historii) przed otwarciem portu,
+    // żeby błąd startu nie zostawiał przyjętych połączeń.
+    let ctx = Context::new(config, options.config.clone(), account.as_ref().map(|account| account.name.clone()))?;
+    let ctrl_receiver = signal_handler()?;
     
-    let addr = SocketAddr::from(([0, 0, 0, 0], 25105));
-    let listener = TcpListener::bind(addr)?;
-    println!("Listening on {}", addr);
+    let listeners = listeners(&ctx.config())?;
+    for listener in &listeners {
+        listener.set_nonblocking(true)?;
+        logging::info("server.listen").field("addr", listener.addr()).field("async", options.async_mode).emit();
+    }
+    if let Some(addr) = &ctx.config().metrics {
+        let addr = metrics::serve(addr, ctx.clone()).map_err(|e| format!("can't listen on {} (metrics): {}", addr, e))?;
+        logging::info("metrics.listen").field("addr", addr).emit();
+    }
+    // Gniazda są już otwarte, więc mogą używać portów uprzywilejowanych.
+    if account.is_some() || gid.is_some() {
+        drop_privileges(account.as_ref(), gid).map_err(|e| format!("can't switch user: {}", e))?;
+    }
+    if let Some(readiness) = readiness {
+        readiness.ready();
+    }
+
+    #[cfg(feature = "async")]
+    let forced = match options.async_mode {
+        true => async_server::run(listeners, &ctx, ctrl_receiver)?,
+        false => run_threads(listeners, &ctx, ctrl_receiver),
+    };
+    #[cfg(not(feature = "async"))]
+    let forced = run_threads(listeners, &ctx, ctrl_receiver);
     
-    // Ponieważ accept jest blokujące, uruchamiamy go w dedykowanych wątku.
-    let _ = thread::spawn(move || {
-        loop {
-            accept(&listener, accept_sender.clone());
-        }
-    });
+    let counters = &ctx.limiter.counters;
+    logging::info("server.stopped")
+        .field("uptime_s", started.elapsed().as_secs())
+        .field("sessions", SESSIONS_TOTAL.load(Relaxed))
+        .field("commands", COMMANDS_TOTAL.load(Relaxed))
+        .field("drained", SESSIONS_DRAINED.load(Relaxed))
+        .field("forced", forced)
+        .field("rejected", counters.rejected_busy.load(Relaxed) + counters.rejected_per_ip.load(Relaxed))
+        .field("banned", counters.rejected_banned.load(Relaxed))
+        .field("handshake_failures", counters.handshake_failures.load(Relaxed))
+        .emit();
+    Ok(())
+}
+
+/// Serwer wielowątkowy: każda sesja w osobnym wątku puli.
+/// Zwraca liczbę siłowo zamkniętych sesji.
+fn run_threads(listeners: Vec<Listener>, ctx: &Arc<Context>, ctrl_receiver: Receiver<()>) -> usize {
+    let (accept_sender, accept_receiver) = bounded::<Incoming>(1);
+    // Ponieważ accept jest blokujące, uruchamiamy go w dedykowanych wątkach.
+    let acceptors: Vec<_> = listeners.into_iter()
+        .map(|listener| {
+            let sender = accept_sender.clone();
+            thread::spawn(move || accept_loop(listener, sender))
+        })
+        .collect();
+    drop(accept_sender);
+    let reject_sender = rejector();
     
-    rayon::scope(|s| {
-        TASK_COUNT.fetch_add(1, Relaxed);
-        let main_task_id = TASK_ID.fetch_add(1, Relaxed);
-        eprintln!("Main task started id: {}", main_task_id);
-        
-        loop {
-            select! {
-                recv(ctrl_receiver) -> _ => {
-                    eprintln!("Received stop signal");
-                    break;
-                }
-                recv(accept_receiver) -> value => {
-                    match value {
-                        Ok(stream) => {
-                            // Jeśli nie przerwano działania programu,
-                            // uruchamiamy nowy task dla obsługi połączenia
-                            // z klientem.
-                            if !STOP.load(Relaxed) {
-                                let ctrl_channel = ctrl_receiver.clone();
-                                let mut stream = stream;
-                                s.spawn(move |_| {
-                                    handle_client(&mut stream, ctrl_channel);
-                                });
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Error receiving: {:?}", e);
+    loop {
+        select! {
+            recv(ctrl_receiver) -> _ => {
+                break;
+            }
+            recv(accept_receiver) -> value => {
+                match value {
+                    Ok(incoming) => {
+                        // Jeśli nie przerwano działania programu,
+                        // uruchamiamy nowy task dla obsługi połączenia
+                        // z klientem.
+                        if !STOP.load(Relaxed) {
+                            admit(incoming, ctx, &reject_sender);
                         }
                     }
+                    Err(e) => {
+                        logging::error("accept").field("error", e).emit();
+                        break;
+                    }
                 }
             }
         }
-    });
-    
-    Ok(())
+    }
+
+    // Połączenia czekające w kanale zostaną zamknięte.
+    drop(accept_receiver);
+    for acceptor in acceptors {
+        let _ = acceptor.join();
+    }
+    drain(&ctx.sessions, ctx.config().grace_period())
 }
 
-fn handle_client(stream: &mut TcpStream, ctrl_receiver: Receiver<()>) {
-    TASK_COUNT.fetch_add(1, Relaxed);
-    let task_id = TASK_ID.fetch_add(1, Relaxed);
-    
-    let mut conn = Connector::new(stream.try_clone().unwrap(), ConnectionSide::Server);
-    let peer = conn.peer_addr();
-    eprintln!("Connected client {} (tid: {})", peer, task_id);
-    
-    match conn.init() {
-        Ok(_) => (),
-        Err(why) => {
-            stream.shutdown(Shutdown::Both).unwrap();
-            eprintln!("Task canceled with error {} (tid:{})", why, task_id);
-            TASK_COUNT.fetch_sub(1, Relaxed);
-            return;
+/// Sprawdzenie limitów i uruchomienie sesji dla nowego połączenia.
+fn admit(incoming: Incoming, ctx: &Arc<Context>, reject_sender: &Sender<(Box<dyn Transport>, &'static str)>) {
+    match ctx.limiter.admit(incoming.origin) {
+        Admission::Accepted(permit) => {
+            let ctx = ctx.clone();
+            rayon::spawn(move || handle_client(incoming, permit, &ctx));
         }
-    }
-    
-    loop {
-        if ctrl_receiver.try_recv().is_ok() {
-            TASK_COUNT.fetch_sub(1, Relaxed);
-            eprintln!("Task canceled id: {}", task_id);
-            return;
+        Admission::Rejected(reason) => {
+            logging::warn("session.rejected").field("peer", incoming.origin).field("reason", reason).emit();
+            // Jeśli kolejka odmów jest pełna, po prostu zamykamy połączenie.
+            let _ = reject_sender.try_send((incoming.transport, reason));
         }
-        
-        match one_loop(&mut conn) {  
-            Ok(_) => (),
-            Err(why) => {
-                if why.kind() == ErrorKind::BrokenPipe || why.kind() == ErrorKind::UnexpectedEof {
-                    eprintln!("Client {} disconnected (tid: {})", peer, task_id);
-                } else {
-                    eprintln!("** Error executing: {}", why);
+        Admission::Banned => (),
+    }
+}
+
+/// Gniazda nasłuchujące według konfiguracji: TCP i (lub) gniazdo Unix.
+fn listeners(config: &Config) -> Result<Vec<Listener>, Box<dyn Error>> {
+    let mut listeners = Vec::new();
+    if config.tcp {
+        for addr in config.listen_addrs()? {
+            match Listener::tcp(addr) {
+                Ok(listener) => listeners.push(listener),
+                // Np. domyślne `[::]` na systemie bez IPv6.
+                Err(e) if e.raw_os_error() == Some(libc::EAFNOSUPPORT) => {
+                    logging::warn("server.listen").field("addr", addr).field("error", e).emit();
                 }
-                TASK_COUNT.fetch_sub(1, Relaxed);
-                return;
+                Err(e) => return Err(format!("can't listen on {}: {}", addr, e).into()),
             }
         }
     }
+    if let Some(path) = &config.unix_socket {
+        listeners.push(Listener::unix(path, config.unix_socket_mode()?)?);
+    }
+    if listeners.is_empty() {
+        return Err("nothing to listen on: no usable tcp address and unix_socket is not set".into());
+    }
+    Ok(listeners)
 }
 
-/// Jedna sekwencja zapytanie-odpowiedź.
-/// Dla na błąd wykonania polecenia nie jest błędem.
-/// Dla nas błędem są problemy komunikacji z klientem.
-fn one_loop(conn: &mut Connector) -> io::Result<()> {
-    let request = conn.read_request()?;
-    eprintln!("-- received request: {}", request.to_pretty_json()?);
+/// Oczekiwanie na zakończenie sesji.
+/// Sesje bezczynne same powiadamiają klientów i kończą pracę,
+/// sesje wykonujące polecenie mają na to `grace_period`.
+/// Po tym czasie (lub po drugim sygnale) połączenia są zamykane siłowo.
+/// Zwraca liczbę siłowo zamkniętych sesji.
+fn drain(sessions: &Sessions, grace_period: Duration) -> usize {
+    let deadline = Instant::now() + grace_period;
+    while sessions.count() > 0 && Instant::now() < deadline && !FORCE.load(Relaxed) {
+        thread::sleep(DRAIN_POLL);
+    }
     
-    match Executor::execute(request) {
-        Ok(answer) => {
-            conn.send_answer(answer)?;
-            eprintln!("-- sent answer: OK");
-            Ok(())
-        },
-        Err(err) => {
-            let answer = Answer::from(err);
-            match conn.send_answer(answer) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(e)
-            }
+    let forced = sessions.close_all();
+    if forced > 0 {
+        logging::warn("server.force_close").field("sessions", forced).emit();
+        // Dajemy sesjom chwilę na posprzątanie po zamknięciu gniazd.
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while sessions.count() > 0 && Instant::now() < deadline {
+            thread::sleep(DRAIN_POLL);
+        }
+    }
+    forced
+}
+
+/// Obsługa `--migrate-only`: aktualizacja schematu bazy historii bez uruchamiania serwera.
+fn migrate_only(config: &Config) -> Result<(), Box<dyn Error>> {
+    match config.history_db.as_deref() {
+        Some(path) => {
+            History::open(Some(path))?;
+            logging::info("server.migrated").field("path", path.display()).emit();
         }
+        None => logging::warn("server.migrated").field("error", "history is disabled").emit(),
     }
+    Ok(())
 }
@@ -0,0 +1,267 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use shared::data::answer::Answer;
+use shared::xerror::{ErrSrc, Error};
+use crate::context::Context;
+use crate::logging;
+use crate::session::{SESSIONS_DRAINED, SESSIONS_TOTAL};
+use crate::{ACCEPT_POLL, STOP, TASK_COUNT};
+
+/// Górne granice przedziałów histogramu czasu wykonania poleceń (sekundy).
+const BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0];
+/// Najwięcej różnych nazw poleceń w metrykach. Nazwę wybiera klient,
+/// więc pozostałe liczymy razem jako "other".
+const MAX_COMMANDS: usize = 64;
+/// Czas na odczyt żądania HTTP.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+/// Największe przyjmowane żądanie HTTP.
+const MAX_REQUEST: usize = 8192;
+
+/// Liczniki udostępniane w formacie Prometheus (`metrics` w konfiguracji).
+#[derive(Default)]
+pub struct Metrics {
+    handshakes_ok: AtomicU64,
+    handshakes_failed: AtomicU64,
+    commands: Mutex<BTreeMap<String, Timing>>,
+    errors: Mutex<BTreeMap<String, u64>>,
+}
+
+/// Liczba i czasy wykonania jednego polecenia.
+#[derive(Default)]
+struct Timing {
+    count: u64,
+    seconds: f64,
+    /// Liczba wykonań w każdym przedziale (bez sumowania narastającego).
+    buckets: [u64; BUCKETS.len() + 1],
+}
+
+impl Metrics {
+    pub fn handshake(&self, ok: bool) {
+        match ok {
+            true => self.handshakes_ok.fetch_add(1, Relaxed),
+            false => self.handshakes_failed.fetch_add(1, Relaxed),
+        };
+    }
+
+    /// Wykonane polecenie; odpowiedź z błędem liczymy według źródła błędu.
+    pub fn command(&self, command: &str, answer: &Answer, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        {
+            let mut commands = self.commands.lock().unwrap();
+            let name = match commands.contains_key(command) || commands.len() < MAX_COMMANDS {
+                true => command,
+                false => "other",
+            };
+            let timing = commands.entry(name.to_string()).or_default();
+            timing.count += 1;
+            timing.seconds += seconds;
+            timing.buckets[BUCKETS.iter().position(|le| seconds <= *le).unwrap_or(BUCKETS.len())] += 1;
+        }
+        if answer.code != 0 {
+            let src = Error::from_json(&answer.message).map(|err| err.src).unwrap_or(ErrSrc::Unknown);
+            *self.errors.lock().unwrap().entry(format!("{:?}", src)).or_default() += 1;
+        }
+    }
+
+    /// Liczniki handshake, poleceń i błędów w formacie tekstowym Prometheus.
+    fn write(&self, out: &mut String) {
+        header(out, "handshakes_total", "counter", "Completed and failed handshakes.");
+        let _ = writeln!(out, "client_server_handshakes_total{{result=\"ok\"}} {}", self.handshakes_ok.load(Relaxed));
+        let _ = writeln!(out, "client_server_handshakes_total{{result=\"failed\"}} {}", self.handshakes_failed.load(Relaxed));
+
+        let commands = self.commands.lock().unwrap();
+        header(out, "requests_total", "counter", "Executed requests by command.");
+        for (command, timing) in commands.iter() {
+            let _ = writeln!(out, "client_server_requests_total{{command=\"{}\"}} {}", escape(command), timing.count);
+        }
+        header(out, "command_duration_seconds", "histogram", "Command execution time.");
+        for (command, timing) in commands.iter() {
+            let command = escape(command);
+            let mut cumulative = 0;
+            for (i, count) in timing.buckets.iter().enumerate() {
+                cumulative += count;
+                let le = BUCKETS.get(i).map_or("+Inf".to_string(), |le| le.to_string());
+                let _ = writeln!(out, "client_server_command_duration_seconds_bucket{{command=\"{}\",le=\"{}\"}} {}", command, le, cumulative);
+            }
+            let _ = writeln!(out, "client_server_command_duration_seconds_sum{{command=\"{}\"}} {}", command, timing.seconds);
+            let _ = writeln!(out, "client_server_command_duration_seconds_count{{command=\"{}\"}} {}", command, timing.count);
+        }
+
+        header(out, "errors_total", "counter", "Error answers by error source.");
+        for (src, count) in self.errors.lock().unwrap().iter() {
+            let _ = writeln!(out, "client_server_errors_total{{source=\"{}\"}} {}", src, count);
+        }
+    }
+}
+
+/// Wszystkie metryki serwera.
+pub fn render(ctx: &Context) -> String {
+    let mut out = String::new();
+    header(&mut out, "sessions_active", "gauge", "Sessions in progress.");
+    let _ = writeln!(out, "client_server_sessions_active {}", TASK_COUNT.load(Relaxed));
+    header(&mut out, "sessions_total", "counter", "Sessions started.");
+    let _ = writeln!(out, "client_server_sessions_total {}", SESSIONS_TOTAL.load(Relaxed));
+    header(&mut out, "sessions_drained_total", "counter", "Sessions ended by server shutdown.");
+    let _ = writeln!(out, "client_server_sessions_drained_total {}", SESSIONS_DRAINED.load(Relaxed));
+
+    let counters = &ctx.limiter.counters;
+    header(&mut out, "connections_rejected_total", "counter", "Connections refused by limits.");
+    for (reason, counter) in [("busy", &counters.rejected_busy), ("per_ip", &counters.rejected_per_ip), ("banned", &counters.rejected_banned)] {
+        let _ = writeln!(out, "client_server_connections_rejected_total{{reason=\"{}\"}} {}", reason, counter.load(Relaxed));
+    }
+
+    let (received, sent) = ctx.sessions.traffic();
+    header(&mut out, "received_bytes_total", "counter", "Bytes received from clients.");
+    let _ = writeln!(out, "client_server_received_bytes_total {}", received);
+    header(&mut out, "sent_bytes_total", "counter", "Bytes sent to clients.");
+    let _ = writeln!(out, "client_server_sent_bytes_total {}", sent);
+
+    ctx.metrics.write(&mut out);
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP client_server_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE client_server_{} {}", name, kind);
+}
+
+/// Wartość etykiety: `\`, `"` i nowa linia muszą być poprzedzone `\`.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Gniazdo HTTP z metrykami (`GET /metrics`), w osobnym wątku do sygnału stop.
+/// Żądania obsługujemy po kolei, odpytywanie przez Prometheus jest rzadkie.
+pub fn serve(addr: &str, ctx: Arc<Context>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    let local = listener.local_addr()?;
+    thread::Builder::new().name("metrics".into()).spawn(move || {
+        while !STOP.load(Relaxed) {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    if let Err(why) = respond(stream, || render(&ctx)) {
+                        logging::debug("metrics.error").field("peer", peer).field("error", why).emit();
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+                Err(e) => {
+                    logging::error("metrics.accept").field("error", e).emit();
+                    thread::sleep(ACCEPT_POLL);
+                }
+            }
+        }
+    })?;
+    Ok(local)
+}
+
+/// Odpowiedź na jedno żądanie HTTP/1.x; połączenie jest potem zamykane.
+fn respond(mut stream: TcpStream, body: impl FnOnce() -> String) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buffer)?;
+        if n == 0 || request.len() + n > MAX_REQUEST {
+            return Err(io::Error::new(ErrorKind::InvalidData, "incomplete HTTP request"));
+        }
+        request.extend_from_slice(&buffer[..n]);
+    }
+    let line = String::from_utf8_lossy(&request);
+    let mut parts = line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", body()),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let metrics = Metrics::default();
+        metrics.handshake(true);
+        metrics.handshake(false);
+        metrics.handshake(true);
+        metrics.command("pwd", &Answer::new(0, "OK", "pwd"), Duration::from_millis(2));
+        metrics.command("pwd", &Answer::new(0, "OK", "pwd"), Duration::from_secs(2));
+        metrics.command("cd", &Answer::from(Error::new(2, "no such directory")), Duration::from_millis(1));
+
+        let mut out = String::new();
+        metrics.write(&mut out);
+        for line in [
+            "client_server_handshakes_total{result=\"ok\"} 2",
+            "client_server_handshakes_total{result=\"failed\"} 1",
+            "client_server_requests_total{command=\"pwd\"} 2",
+            "client_server_command_duration_seconds_bucket{command=\"pwd\",le=\"0.001\"} 0",
+            "client_server_command_duration_seconds_bucket{command=\"pwd\",le=\"0.005\"} 1",
+            "client_server_command_duration_seconds_bucket{command=\"pwd\",le=\"1\"} 1",
+            "client_server_command_duration_seconds_bucket{command=\"pwd\",le=\"5\"} 2",
+            "client_server_command_duration_seconds_bucket{command=\"pwd\",le=\"+Inf\"} 2",
+            "client_server_command_duration_seconds_count{command=\"pwd\"} 2",
+            "client_server_errors_total{source=\"App\"} 1",
+        ] {
+            assert!(out.lines().any(|l| l == line), "missing: {}", line);
+        }
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\n");
+    }
+
+    #[test]
+    fn test_respond() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            for path in ["/metrics", "/other"] {
+                let (stream, _) = listener.accept().unwrap();
+                respond(stream, || format!("path {}\n", path)).unwrap();
+            }
+        });
+        for (path, status) in [("/metrics", "200 OK"), ("/other", "404 Not Found")] {
+            let mut client = TcpStream::connect(addr).unwrap();
+            write!(client, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            client.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with(&format!("HTTP/1.1 {}\r\n", status)));
+            if path == "/metrics" {
+                assert!(response.ends_with("\r\n\r\npath /metrics\n"));
+            }
+        }
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,734 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Scope, ScopedJoinHandle};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering::Relaxed};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use shared::data::{answer::Answer, request::Request};
+use shared::executor::{Executor, SqlParams, SQL_CMD};
+use shared::net::connector::{ConnectionSide, Connector};
+use shared::net::forward::{FORWARD_CMD, LISTEN_CMD};
+use shared::net::mux::{Channel, Mux};
+use shared::net::transport::{Metered, Stdio, Traffic, Transport};
+use shared::ufs::fileinfo::FileInfo;
+use shared::net::protocol::{Hello, CAPABILITIES, CAP_CHANNELS, CAP_COMPRESSION};
+use shared::xerror::Error;
+use crate::account::Account;
+use crate::admin::{self, ADMIN_CMD};
+use crate::config::Config;
+use crate::context::Context;
+use crate::forward::Forwards;
+use crate::limits::Permit;
+use crate::listener::Incoming;
+use crate::logging::{self, Entry};
+use crate::worker::Worker;
+use crate::{STOP, TASK_COUNT, TASK_ID};
+
+/// Co jaki czas bezczynna sesja sprawdza, czy serwer nie kończy pracy.
+const IDLE_POLL: Duration = Duration::from_millis(200);
+
+/// Statystyki pracy serwera wyświetlane przy jego zamykaniu.
+pub static SESSIONS_TOTAL: AtomicU32 = AtomicU32::new(0);
+pub static SESSIONS_DRAINED: AtomicU32 = AtomicU32::new(0);
+pub static COMMANDS_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// Najwięcej ostatnich poleceń pamiętanych dla sesji (`admin commands`).
+const RECENT_COMMANDS: usize = 100;
+
+/// Rejestr aktywnych sesji.
+/// Przechowujemy drugie uchwyty połączeń, aby po upływie czasu na dokończenie
+/// poleceń (albo na `admin kill`) móc wymusić zamknięcie połączeń,
+/// oraz stan sesji pokazywany przez `admin sessions`.
+pub struct Sessions {
+    live: Mutex<HashMap<u32, Live>>,
+    /// Bajty przesłane przez zakończone sesje.
+    finished: Traffic,
+}
+
+/// Sesja w rejestrze.
+struct Live {
+    stream: Box<dyn Transport>,
+    traffic: Arc<Traffic>,
+    peer: String,
+    user: String,
+    started: DateTime<Local>,
+    /// Katalog z ostatniego `cd` lub `pwd` sesji. Katalog roboczy
+    /// jest wspólny dla całego procesu, to tylko to, co widzi klient.
+    cwd: String,
+    /// Wykonywane polecenia (w sesji z kanałami może ich być kilka).
+    running: Vec<(u64, String)>,
+    next_command: u64,
+    /// Ostatnie zakończone polecenia, od najstarszego.
+    recent: VecDeque<String>,
+    killed: bool,
+    /// Proces roboczy wykonujący polecenia sesji (`isolation`, `switch_user`).
+    worker: Option<Arc<Worker>>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Sessions { live: Mutex::new(HashMap::new()), finished: Traffic::default() }
+    }
+
+    pub(crate) fn register(&self, id: &Identity, stream: Box<dyn Transport>, traffic: Arc<Traffic>) {
+        self.live.lock().unwrap().insert(id.sid, Live {
+            stream,
+            traffic,
+            peer: id.peer.clone(),
+            user: id.user.clone(),
+            started: Local::now(),
+            cwd: String::new(),
+            running: Vec::new(),
+            next_command: 0,
+            recent: VecDeque::new(),
+            killed: false,
+            worker: None,
+        });
+    }
+
+    /// Usunięcie sesji z rejestru. Proces roboczy sesji kończymy
+    /// już bez blokady rejestru, bo czekamy na jego zakończenie.
+    pub(crate) fn unregister(&self, sid: u32) {
+        let removed = self.live.lock().unwrap().remove(&sid);
+        if let Some(session) = removed {
+            self.finished.read.fetch_add(session.traffic.read.load(Relaxed), Relaxed);
+            self.finished.written.fetch_add(session.traffic.written.load(Relaxed), Relaxed);
+        }
+    }
+
+    pub(crate) fn set_worker(&self, sid: u32, worker: Worker) {
+        if let Some(session) = self.live.lock().unwrap().get_mut(&sid) {
+            session.worker = Some(Arc::new(worker));
+        }
+    }
+
+    /// Czy `worker` nadal wykonuje polecenia sesji (nie zastąpił go nowy proces).
+    pub(crate) fn is_current_worker(&self, sid: u32, worker: &Arc<Worker>) -> bool {
+        self.worker(sid).is_some_and(|current| Arc::ptr_eq(&current, worker))
+    }
+
+    /// Zastąpienie procesu `old` nowym. Zwraca `false` (i kończy nowy proces),
+    /// gdy sesja ma już inny proces, bo wcześniej zastąpiło go inne polecenie.
+    pub(crate) fn replace_worker(&self, sid: u32, old: &Arc<Worker>, worker: Worker) -> bool {
+        let mut live = self.live.lock().unwrap();
+        match live.get_mut(&sid).filter(|session| session.worker.as_ref().is_some_and(|current| Arc::ptr_eq(current, old))) {
+            Some(session) => {
+                session.worker = Some(Arc::new(worker));
+                true
+            }
+            // Nowy proces kończymy już bez blokady rejestru.
+            None => {
+                drop(live);
+                drop(worker);
+                false
+            }
+        }
+    }
+
+    pub(crate) fn worker(&self, sid: u32) -> Option<Arc<Worker>> {
+        self.live.lock().unwrap().get(&sid)?.worker.clone()
+    }
+
+    /// Katalog z ostatniego `cd` lub `pwd` sesji.
+    pub(crate) fn cwd(&self, sid: u32) -> Option<String> {
+        let live = self.live.lock().unwrap();
+        Some(live.get(&sid)?.cwd.clone()).filter(|cwd| !cwd.is_empty())
+    }
+
+    /// Bajty odebrane od klientów i wysłane do nich przez wszystkie sesje.
+    pub(crate) fn traffic(&self) -> (u64, u64) {
+        let live = self.live.lock().unwrap();
+        live.values().fold(
+            (self.finished.read.load(Relaxed), self.finished.written.load(Relaxed)),
+            |(read, written), session| (read + session.traffic.read.load(Relaxed), written + session.traffic.written.load(Relaxed)))
+    }
+
+    pub fn count(&self) -> usize {
+        self.live.lock().unwrap().len()
+    }
+
+    /// Wymuszone zamknięcie wszystkich połączeń.
+    /// Zablokowane odczyty i zapisy w sesjach kończą się błędem.
+    /// Zwraca liczbę zamkniętych połączeń.
+    pub fn close_all(&self) -> usize {
+        let live = self.live.lock().unwrap();
+        live.values().for_each(|session| {
+            let _ = session.stream.shutdown();
+        });
+        live.len()
+    }
+
+    /// Zamknięcie połączenia jednej sesji (`admin kill`).
+    /// Rozpoczęte polecenie jest dokańczane, ale odpowiedź już nie dotrze.
+    pub(crate) fn kill(&self, sid: u32) -> bool {
+        match self.live.lock().unwrap().get_mut(&sid) {
+            Some(session) => {
+                session.killed = true;
+                let _ = session.stream.shutdown();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn killed(&self, sid: u32) -> bool {
+        self.live.lock().unwrap().get(&sid).is_some_and(|session| session.killed)
+    }
+
+    /// Początek polecenia. Zwraca znacznik dla `command_finished`.
+    pub(crate) fn command_started(&self, sid: u32, request: &Request) -> u64 {
+        let mut live = self.live.lock().unwrap();
+        let Some(session) = live.get_mut(&sid) else {
+            return 0;
+        };
+        session.next_command += 1;
+        session.running.push((session.next_command, command_line(request)));
+        session.next_command
+    }
+
+    /// Koniec polecenia: opis trafia na listę ostatnich poleceń sesji.
+    pub(crate) fn command_finished(&self, sid: u32, token: u64, request: &Request, answer: &Answer, duration: Duration) {
+        let mut live = self.live.lock().unwrap();
+        let Some(session) = live.get_mut(&sid) else {
+            return;
+        };
+        session.running.retain(|(id, _)| *id != token);
+        if matches!(request.command.as_str(), "cd" | "pwd") && answer.code == 0 && let Some(dir) = answer.data.first() {
+            session.cwd = dir.clone();
+        }
+        if session.recent.len() == RECENT_COMMANDS {
+            session.recent.pop_front();
+        }
+        session.recent.push_back(format!("{}  {} -> {} ({} ms)",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            command_line(request),
+            answer.code,
+            duration.as_millis()));
+    }
+
+    /// Opis aktywnych sesji, po jednej w wierszu (`admin sessions`).
+    pub(crate) fn list(&self) -> Vec<String> {
+        let live = self.live.lock().unwrap();
+        let mut sids: Vec<_> = live.keys().copied().collect();
+        sids.sort();
+        let mut lines = vec![format!("{:>5}  {:<21} {:<10} {:<19} {:>10} {:>10}  {:<20} {}",
+            "sid", "peer", "user", "started", "in", "out", "cwd", "command")];
+        lines.extend(sids.iter().map(|sid| {
+            let session = &live[sid];
+            let running: Vec<&str> = session.running.iter().map(|(_, command)| command.as_str()).collect();
+            format!("{:>5}  {:<21} {:<10} {:<19} {:>10} {:>10}  {:<20} {}",
+                sid,
+                session.peer,
+                session.user,
+                session.started.format("%Y-%m-%d %H:%M:%S"),
+                session.traffic.read.load(Relaxed),
+                session.traffic.written.load(Relaxed),
+                session.cwd,
+                running.join(", "))
+        }));
+        lines
+    }
+
+    /// Ostatnie polecenia aktywnej sesji (najwyżej `count`), None dla nieznanej sesji.
+    pub(crate) fn recent(&self, sid: u32, count: usize) -> Option<Vec<String>> {
+        let live = self.live.lock().unwrap();
+        let recent = &live.get(&sid)?.recent;
+        Some(recent.iter().skip(recent.len().saturating_sub(count)).cloned().collect())
+    }
+}
+
+/// Polecenie z parametrami, jak w historii.
+fn command_line(request: &Request) -> String {
+    format!("{} {}", request.command, request.params.join(" ")).trim_end().to_string()
+}
+
+/// Tożsamość sesji, dołączana do wpisów w logu i w dzienniku audytu.
+pub struct Identity {
+    pub sid: u32,
+    pub peer: String,
+    pub user: String,
+    /// Identyfikator sesji w bazie historii.
+    pub history_id: Option<i64>,
+    /// Uid właściciela procesu klienta, gdy to on jest użytkownikiem sesji.
+    pub uid: Option<u32>,
+}
+
+impl Identity {
+    /// Tożsamość nowej sesji. Z `peer_credentials` użytkownikiem sesji
+    /// przez gniazdo Unix jest właściciel procesu klienta (`uid`).
+    pub(crate) fn new(ctx: &Context, sid: u32, peer: String, uid: Option<u32>) -> Identity {
+        let uid = uid.filter(|_| ctx.config().peer_credentials);
+        let user = match uid {
+            Some(uid) => ctx.config().unix_users.get(&uid).cloned()
+                .or_else(|| FileInfo::user_name(uid).ok())
+                .unwrap_or_else(|| format!("uid:{}", uid)),
+            _ => ctx.user.clone(),
+        };
+        Identity { sid, peer, user, history_id: None, uid }
+    }
+
+    /// Czy sesja może wykonywać polecenia `admin`: tylko użytkownik
+    /// uwierzytelniony przez gniazdo Unix (`peer_credentials`) z listy `admins`.
+    pub(crate) fn is_admin(&self, config: &Config) -> bool {
+        self.uid.is_some() && config.is_admin(&self.user)
+    }
+
+    pub(crate) fn log(&self, entry: Entry) -> Entry {
+        entry
+            .field("sid", self.sid)
+            .field("peer", &self.peer)
+            .field("user", &self.user)
+    }
+}
+
+/// Proces roboczy sesji. Z `switch_user` polecenia sesji użytkownika
+/// z `peer_credentials` wykonuje proces z jego uprawnieniami, więc np. pliki
+/// z `put` należą do niego. Z `isolation` każda sesja ma własny proces
+/// (z uprawnieniami serwera, o ile nie przełącza użytkownika).
+/// Pozostałe sesje wykonują polecenia w procesie serwera.
+pub(crate) fn start_worker(ctx: &Context, id: &Identity) -> io::Result<()> {
+    let config = ctx.config();
+    let account = match id.uid.filter(|_| config.switch_user) {
+        Some(uid) => Some(Account::by_uid(uid)?),
+        None if config.isolation => None,
+        None => return Ok(()),
+    };
+    let worker = Worker::start(account.as_ref(), None, config.limits.max_frame_size).map_err(|e| match &account {
+        Some(account) => io::Error::new(e.kind(), format!("can't switch to {} (uid {}): {}", account.name, account.uid, e)),
+        None => e,
+    })?;
+    id.log(logging::debug("session.worker")).field("pid", worker.pid()).emit();
+    ctx.sessions.set_worker(id.sid, worker);
+    Ok(())
+}
+
+/// Polecenie w procesie roboczym sesji. Po awarii procesu klient dostaje
+/// opis jego zakończenia, a kolejne polecenia wykonuje nowy proces,
+/// uruchomiony w ostatnim znanym katalogu sesji. Awaria przerywa wszystkie
+/// bieżące polecenia sesji, a nowy proces uruchamia tylko jedno z nich.
+fn execute_in_worker(ctx: &Context, id: &Identity, worker: &Arc<Worker>, request: &Request) -> Answer {
+    let exit = match worker.execute(request) {
+        Ok(answer) => return answer,
+        Err(exit) => exit,
+    };
+    id.log(logging::warn("session.worker_exit"))
+        .field("pid", worker.pid())
+        .field("cmd", &request.command)
+        .field("exit", &exit)
+        .emit();
+    let restarted = match ctx.sessions.is_current_worker(id.sid, worker) {
+        true => worker.restart(ctx.sessions.cwd(id.sid).as_deref()).map(Some),
+        false => Ok(None),
+    };
+    let message = match restarted {
+        Ok(restarted) => {
+            if let Some(restarted) = restarted {
+                let pid = restarted.pid();
+                if ctx.sessions.replace_worker(id.sid, worker, restarted) {
+                    id.log(logging::debug("session.worker")).field("pid", pid).emit();
+                }
+            }
+            format!("{} while running '{}'; a new worker runs the next commands", exit, request.command)
+        }
+        // Następne polecenie znowu spróbuje uruchomić proces.
+        Err(why) => {
+            id.log(logging::error("session.worker")).field("error", &why).emit();
+            format!("{} while running '{}'; can't start a new worker: {}", exit, request.command, why)
+        }
+    };
+    Answer::from(Error::new(-1, &message))
+}
+
+/// Powód zakończenia sesji.
+pub(crate) enum End {
+    /// Klient się rozłączył.
+    Disconnected,
+    /// Serwer kończy pracę, klient został powiadomiony.
+    Drained,
+}
+
+pub fn handle_client(incoming: Incoming, permit: Permit, ctx: &Context) {
+    TASK_COUNT.fetch_add(1, Relaxed);
+    SESSIONS_TOTAL.fetch_add(1, Relaxed);
+    let task_id = TASK_ID.fetch_add(1, Relaxed);
+    serve(incoming, &permit, ctx, task_id);
+    TASK_COUNT.fetch_sub(1, Relaxed);
+}
+
+fn serve(incoming: Incoming, permit: &Permit, ctx: &Context, task_id: u32) {
+    let traffic = Arc::new(Traffic::default());
+    let conn = Connector::new(Metered::new(incoming.transport, traffic.clone()), ConnectionSide::Server);
+    let id = Identity::new(ctx, task_id, peer(&conn), incoming.uid);
+    register(ctx, &id, &conn, traffic);
+    run(conn, ctx, id, CAPABILITIES, |why| {
+        // Klient w niezgodnej wersji protokołu nie jest powodem do blokady.
+        if why.kind() != ErrorKind::Unsupported {
+            permit.limiter().handshake_failed(permit.origin());
+        }
+    });
+    ctx.sessions.unregister(task_id);
+}
+
+/// Rejestracja drugiego uchwytu połączenia w `Sessions`.
+fn register(ctx: &Context, id: &Identity, conn: &Connector, traffic: Arc<Traffic>) {
+    match conn.try_clone_transport() {
+        Ok(clone) => ctx.sessions.register(id, clone, traffic),
+        Err(why) => id.log(logging::warn("session.register")).field("error", why).emit(),
+    }
+}
+
+fn peer(conn: &Connector) -> String {
+    conn.peer_addr().map(|addr| addr.to_string()).unwrap_or_default()
+}
+
+/// Jedna sesja przez standardowe wejście i wyjście (`server --stdio`),
+/// np. gdy serwer jest uruchamiany przez ssh lub inetd.
+/// Bez kanałów: wątku czytającego z potoku nie da się przerwać.
+pub fn serve_stdio(ctx: &Context) -> io::Result<()> {
+    let traffic = Arc::new(Traffic::default());
+    let conn = Connector::new(Metered::new(Stdio::inherit()?, traffic.clone()), ConnectionSide::Server);
+    TASK_COUNT.fetch_add(1, Relaxed);
+    SESSIONS_TOTAL.fetch_add(1, Relaxed);
+    let id = Identity::new(ctx, TASK_ID.fetch_add(1, Relaxed), peer(&conn), None);
+    let sid = id.sid;
+    register(ctx, &id, &conn, traffic);
+    run(conn, ctx, id, CAPABILITIES & !CAP_CHANNELS, |_| ());
+    ctx.sessions.unregister(sid);
+    TASK_COUNT.fetch_sub(1, Relaxed);
+    Ok(())
+}
+
+/// Handshake i obsługa sesji niezależnie od transportu.
+/// `handshake_failed` jest wołane po nieudanym handshake.
+fn run(mut conn: Connector, ctx: &Context, mut id: Identity, capabilities: u32, handshake_failed: impl FnOnce(&io::Error)) {
+    // Handshake musi się zmieścić w zadanym czasie, inaczej
+    // powolny klient mógłby bez końca zajmować miejsce w puli.
+    if let Err(why) = conn.set_read_timeout(Some(ctx.config().limits.handshake_timeout())) {
+        id.log(logging::error("session.error")).field("error", why).emit();
+        return;
+    }
+    conn.set_max_frame(ctx.config().limits.max_frame_size);
+    let capabilities = match ctx.config().compression {
+        true => capabilities,
+        false => capabilities & !CAP_COMPRESSION,
+    };
+    conn.set_hello(Hello { capabilities, ..Hello::default() });
+    id.log(logging::info("session.start")).emit();
+
+    let handshake = conn.init().and_then(|_| conn.set_read_timeout(None));
+    ctx.metrics.handshake(handshake.is_ok());
+    if let Err(why) = handshake {
+        handshake_failed(&why);
+        id.log(logging::warn("session.handshake_failed")).field("error", why).emit();
+        return;
+    }
+    id.log(logging::debug("session.handshake")).field("protocol", conn.protocol()).emit();
+    if let Err(why) = start_worker(ctx, &id) {
+        id.log(logging::error("session.worker")).field("error", why).emit();
+        return;
+    }
+
+    let started = Instant::now();
+    id.history_id = ctx.history.session_started(&id);
+    let result = match conn.protocol().has(CAP_CHANNELS) {
+        true => Mux::new(conn).and_then(|mux| channels_loop(&mux, ctx, &id)),
+        false => session_loop(&mut conn, ctx, &id),
+    };
+    finish(ctx, &id, started, result);
+}
+
+/// Zapis zakończenia sesji w historii i w logu.
+pub(crate) fn finish(ctx: &Context, id: &Identity, started: Instant, result: io::Result<End>) {
+    let end = match result {
+        _ if ctx.sessions.killed(id.sid) => "killed",
+        Ok(End::Disconnected) => "disconnected",
+        Ok(End::Drained) => {
+            SESSIONS_DRAINED.fetch_add(1, Relaxed);
+            "shutdown"
+        }
+        Err(why) if matches!(why.kind(), ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::UnexpectedEof) => {
+            "disconnected"
+        }
+        Err(why) => {
+            id.log(logging::error("session.error")).field("error", &why).emit();
+            "error"
+        }
+    };
+    ctx.history.session_ended(id, end);
+    id.log(logging::info("session.end"))
+        .field("reason", end)
+        .field("duration_ms", started.elapsed().as_millis())
+        .emit();
+}
+
+/// Obsługa kolejnych żądań klienta.
+/// Między żądaniami sprawdzamy, czy serwer nie kończy pracy.
+/// Rozpoczęte polecenie zawsze jest dokańczane.
+fn session_loop(conn: &mut Connector, ctx: &Context, id: &Identity) -> io::Result<End> {
+    loop {
+        if STOP.load(Relaxed) {
+            conn.send_shutdown_notice()?;
+            return Ok(End::Drained);
+        }
+        match conn.wait_for_data(IDLE_POLL) {
+            Ok(true) => one_loop(conn, ctx, id)?,
+            Ok(false) => (),
+            Err(why) if why.kind() == ErrorKind::UnexpectedEof => return Ok(End::Disconnected),
+            Err(why) => return Err(why),
+        }
+    }
+}
+
+/// Obsługa sesji z kanałami: każde żądanie w osobnym wątku, więc długie
+/// polecenie (np. transfer pliku) nie wstrzymuje pozostałych.
+/// Przy zamykaniu serwera rozpoczęte polecenia są dokańczane,
+/// a przekierowania portów przerywane.
+fn channels_loop(mux: &Mux, ctx: &Context, id: &Identity) -> io::Result<End> {
+    let forwards = Forwards::new(ctx.config().limits.max_channels);
+    thread::scope(|scope| {
+        let result = accept_channels(scope, mux, ctx, id, &forwards);
+        // Inaczej wątki przekierowań nie skończyłyby się przed końcem sesji.
+        forwards.close();
+        result
+    })
+}
+
+fn accept_channels<'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    mux: &'scope Mux,
+    ctx: &'scope Context,
+    id: &'scope Identity,
+    forwards: &'scope Forwards,
+) -> io::Result<End> {
+    let mut running: Vec<ScopedJoinHandle<()>> = Vec::new();
+    loop {
+        running.retain(|handle| !handle.is_finished());
+        if STOP.load(Relaxed) {
+            forwards.close();
+            running.into_iter().for_each(|handle| { let _ = handle.join(); });
+            mux.send_shutdown_notice()?;
+            return Ok(End::Drained);
+        }
+        match mux.accept(IDLE_POLL) {
+            Ok(Some(mut channel)) => match forwards.slots.acquire() {
+                Some(slot) => running.push(scope.spawn(move || {
+                    serve_channel(scope, mux, channel, ctx, id, forwards);
+                    drop(slot);
+                })),
+                None => {
+                    id.log(logging::warn("channel.refused")).field("channel", channel.id()).field("max", forwards.slots.max()).emit();
+                    let _ = channel.send_answer(Answer::from(Error::new(libc::EBUSY, "too many channels in this session")));
+                }
+            },
+            Ok(None) => (),
+            Err(why) if why.kind() == ErrorKind::UnexpectedEof => return Ok(End::Disconnected),
+            Err(why) => return Err(why),
+        }
+    }
+}
+
+fn serve_channel<'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    mux: &'scope Mux,
+    mut channel: Channel,
+    ctx: &'scope Context,
+    id: &'scope Identity,
+    forwards: &'scope Forwards,
+) {
+    let channel_id = channel.id();
+    let result = match channel.read_request() {
+        Ok(request) if request.command == FORWARD_CMD => forwards.connect(ctx, id, channel, &request),
+        Ok(request) if request.command == LISTEN_CMD => forwards.listen(scope, mux, ctx, id, channel, &request),
+        Ok(request) => channel.send_answer(execute(ctx, id, &request)),
+        Err(why) => Err(why),
+    };
+    if let Err(why) = result {
+        id.log(logging::warn("channel.error"))
+            .field("channel", channel_id)
+            .field("error", why)
+            .emit();
+    }
+}
+
+/// Limit otwartych kanałów sesji (`max_channels`). Każdy kanał ma własny
+/// wątek i bufor, więc bez limitu klient mógłby je wyczerpać.
+pub(crate) struct ChannelSlots {
+    open: AtomicUsize,
+    max: usize,
+}
+
+/// Miejsce dla jednego kanału, zwalniane razem z nim.
+pub(crate) struct ChannelSlot<'a>(&'a ChannelSlots);
+
+impl ChannelSlots {
+    pub(crate) fn new(max: usize) -> Self {
+        ChannelSlots { open: AtomicUsize::new(0), max }
+    }
+
+    pub(crate) fn acquire(&self) -> Option<ChannelSlot<'_>> {
+        self.open.fetch_update(Relaxed, Relaxed, |open| (open < self.max).then_some(open + 1))
+            .ok()
+            .map(|_| ChannelSlot(self))
+    }
+
+    pub(crate) fn max(&self) -> usize {
+        self.max
+    }
+}
+
+impl Drop for ChannelSlot<'_> {
+    fn drop(&mut self) {
+        self.0.open.fetch_sub(1, Relaxed);
+    }
+}
+
+/// Jedna sekwencja zapytanie-odpowiedź.
+/// Dla na błąd wykonania polecenia nie jest błędem.
+/// Dla nas błędem są problemy komunikacji z klientem.
+fn one_loop(conn: &mut Connector, ctx: &Context, id: &Identity) -> io::Result<()> {
+    let request = conn.read_request()?;
+    let answer = execute(ctx, id, &request);
+    conn.send_answer(answer)
+}
+
+/// Wykonanie żądania.
+/// Do logu i dziennika audytu trafia polecenie i wynik,
+/// nigdy zawartość przesyłanych plików.
+pub(crate) fn execute(ctx: &Context, id: &Identity, request: &Request) -> Answer {
+    let started = Instant::now();
+    COMMANDS_TOTAL.fetch_add(1, Relaxed);
+    let token = ctx.sessions.command_started(id.sid, request);
+    
+    let answer = match request.command.as_str() {
+        ADMIN_CMD => admin::execute(ctx, id, &request.params),
+        FORWARD_CMD | LISTEN_CMD => Answer::from(Error::new(-1, "port forwarding requires channels")),
+        SQL_CMD if !ctx.config().allow_sql_write && SqlParams::parse(&request.params).is_ok_and(|sql| sql.write) => {
+            Answer::from(Error::new(-1, "sql --write is not allowed by server policy"))
+        }
+        _ => match ctx.sessions.worker(id.sid) {
+            Some(worker) => execute_in_worker(ctx, id, &worker, request),
+            None => Executor::execute(request.clone()).unwrap_or_else(Answer::from),
+        },
+    };
+    let duration = started.elapsed();
+
+    id.log(logging::info("request"))
+        .field("cmd", &request.command)
+        .field("code", answer.code)
+        .field("duration_ms", duration.as_millis())
+        .field("bytes", answer.binary.len() + request.binary.len())
+        .emit();
+    id.log(logging::debug("request.params"))
+        .field("cmd", &request.command)
+        .field("params", format!("{:?}", request.params))
+        .emit();
+    ctx.audit.command(id, request, &answer, duration);
+    ctx.history.command(id, request, &answer, duration);
+    ctx.sessions.command_finished(id.sid, token, request, &answer, duration);
+    ctx.metrics.command(&request.command, &answer, duration);
+    // Plik pobrany przez klienta (`get`) albo przez niego wysłany (`put`).
+    let transferred = match answer.cmd.as_str() {
+        "upload" => Some(answer.binary.len()),
+        "download" => Some(request.binary.len()),
+        _ => None,
+    };
+    if let Some(bytes) = transferred && answer.code == 0 && let Some(path) = answer.data.first() {
+        ctx.audit.transfer(id, &request.command, path, bytes);
+        ctx.history.transfer(id, &request.command, path, bytes);
+    }
+    answer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::net::transport::pipe;
+
+    #[test]
+    fn test_sessions() {
+        let sessions = Sessions::new();
+        let id = Identity { sid: 7, peer: "127.0.0.1:4000".into(), user: "alice".into(), history_id: None, uid: None };
+        let (stream, _other) = pipe();
+        sessions.register(&id, Box::new(stream), Arc::new(Traffic::default()));
+
+        let request = Request::new("cd".into(), vec!["/tmp".into()]);
+        let token = sessions.command_started(7, &request);
+        assert!(sessions.list()[1].ends_with("cd /tmp"));
+        let answer = Answer::new_with_data(0, "OK", "cd", vec!["/tmp".into()]);
+        sessions.command_finished(7, token, &request, &answer, Duration::from_millis(3));
+        assert!(sessions.list()[1].contains("/tmp"));
+        assert!(!sessions.list()[1].ends_with("cd /tmp"));
+
+        let recent = sessions.recent(7, 10).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert!(recent[0].ends_with("cd /tmp -> 0 (3 ms)"));
+        assert!(sessions.recent(8, 10).is_none());
+
+        assert!(!sessions.kill(8));
+        assert!(sessions.kill(7));
+        assert!(sessions.killed(7));
+        sessions.unregister(7);
+        assert_eq!(sessions.count(), 0);
+    }
+
+    #[test]
+    fn test_is_admin() {
+        let config = Config { admins: vec!["*".into()], ..Config::default() };
+        let mut id = Identity { sid: 1, peer: "127.0.0.1:4000".into(), user: "root".into(), history_id: None, uid: None };
+        // Sesja TCP nie ma uwierzytelnionego użytkownika.
+        assert!(!id.is_admin(&config));
+        id.uid = Some(0);
+        assert!(id.is_admin(&config));
+        assert!(!id.is_admin(&Config::default()));
+        assert!(!id.is_admin(&Config { admins: vec!["alice".into()], ..Config::default() }));
+    }
+
+    #[test]
+    fn test_sql_write_policy() {
+        let config = Config { audit_log: None, history_db: None, ..Config::default() };
+        let ctx = Context::new(config, None, None).unwrap();
+        let id = Identity { sid: 1, peer: "127.0.0.1:4000".into(), user: "root".into(), history_id: None, uid: None };
+        let db = std::env::temp_dir().join(format!("session-sql-{}.db", std::process::id()));
+        let db = db.to_str().unwrap().to_string();
+        let request = Request::new(SQL_CMD.into(), vec!["--write".into(), db.clone(), "CREATE TABLE t (a)".into()]);
+        let answer = execute(&ctx, &id, &request);
+        assert!(answer.message.contains("sql --write is not allowed by server policy"), "{}", answer.message);
+        assert!(!std::path::Path::new(&db).exists());
+    }
+
+    #[test]
+    fn test_channel_slots() {
+        let slots = ChannelSlots::new(2);
+        let first = slots.acquire().unwrap();
+        let second = slots.acquire();
+        assert!(second.is_some());
+        assert!(slots.acquire().is_none());
+        drop(first);
+        assert!(slots.acquire().is_some());
+        drop(second);
+        assert_eq!(slots.open.load(Relaxed), 0);
+    }
+}
@@ -0,0 +1,440 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering::Relaxed};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use shared::data::answer::Answer;
+use shared::data::message::{FrameTooLarge, Message, MAX_FRAME};
+use shared::data::request::Request;
+use shared::data::wire::Encoding;
+use shared::executor::Executor;
+use shared::xerror::Error;
+use crate::account::Account;
+
+/// Argument, z którym serwer uruchamia się jako proces roboczy.
+pub const WORKER_ARG: &str = "--worker";
+/// Deskryptor gniazda do procesu głównego w procesie roboczym.
+const WORKER_FD: libc::c_int = 3;
+/// Kod wyjścia procesu roboczego po panice.
+const PANIC_EXIT: libc::c_int = 101;
+/// Czas na uruchomienie procesu i przełączenie użytkownika.
+const START_TIMEOUT: Duration = Duration::from_secs(10);
+/// Rozmiar numeru żądania na początku ramki; limit `max_frame` dotyczy
+/// samego żądania lub odpowiedzi, tak jak w połączeniu z klientem.
+const TAG_SIZE: usize = size_of::<u32>();
+
+/// Proces roboczy sesji: wykonuje polecenia `Executor` poza procesem
+/// serwera, z uprawnieniami użytkownika sesji (`switch_user`) albo
+/// serwera (`isolation`). Awaria polecenia kończy tylko ten proces,
+/// a `cd` zmienia tylko jego katalog roboczy.
+/// Żądania i odpowiedzi przesyłamy przez parę gniazd w formacie binarnym,
+/// z numerem żądania, więc polecenia kanałów sesji wykonują się równolegle.
+/// Proces kończy się po zamknięciu gniazda, czyli razem z sesją.
+pub struct Worker {
+    pid: libc::pid_t,
+    account: Option<Account>,
+    /// Największa ramka przyjmowana od procesu roboczego (`max_frame_size`).
+    max_frame: usize,
+    stream: Mutex<UnixStream>,
+    next_id: AtomicU32,
+    pending: Arc<Mutex<Pending>>,
+    /// Wątek odbierający odpowiedzi (`read_answers`).
+    reader: Option<JoinHandle<()>>,
+}
+
+/// Polecenia czekające na odpowiedź procesu roboczego.
+#[derive(Default)]
+struct Pending {
+    answers: HashMap<u32, Sender<Result<Answer, String>>>,
+    /// Opis zakończenia procesu, gdy już go nie ma.
+    exit: Option<String>,
+}
+
+/// Pierwsza wiadomość do procesu roboczego.
+#[derive(Serialize, Deserialize)]
+struct Setup {
+    account: Option<Account>,
+    dir: Option<String>,
+    max_frame: usize,
+}
+
+impl Worker {
+    /// Uruchomienie procesu roboczego (przełączonego na `account`)
+    /// w katalogu `dir`, a bez niego - w katalogu serwera.
+    /// Serwer jest wielowątkowy, więc zamiast samego forka uruchamiamy
+    /// jego plik wykonywalny od nowa (`WORKER_ARG`) z gniazdem jako stdin.
+    pub fn start(account: Option<&Account>, dir: Option<&str>, max_frame: usize) -> io::Result<Worker> {
+        let (stream, child) = UnixStream::pair()?;
+        let process = command()
+            .stdin(Stdio::from(OwnedFd::from(child)))
+            .stdout(Stdio::null())
+            .spawn()?;
+        let mut worker = Worker {
+            pid: process.id() as libc::pid_t,
+            account: account.cloned(),
+            max_frame,
+            stream: Mutex::new(stream),
+            next_id: AtomicU32::new(0),
+            pending: Arc::default(),
+            reader: None,
+        };
+        let setup = Setup { account: account.cloned(), dir: dir.map(String::from), max_frame };
+        let setup = serde_json::to_vec(&setup).map_err(io::Error::other)?;
+        // Odpowiedzią na ustawienia jest wynik przełączenia użytkownika (errno).
+        let mut errno = [0u8; 4];
+        let stream = worker.stream.get_mut().unwrap();
+        let started = stream.set_read_timeout(Some(START_TIMEOUT))
+            .and_then(|_| Message::write(stream, &setup))
+            .and_then(|_| stream.read_exact(&mut errno))
+            .and_then(|_| stream.set_read_timeout(None))
+            .and_then(|_| stream.try_clone());
+        let reader = match started {
+            Ok(reader) => reader,
+            Err(why) => {
+                // Proces, który nie odpowiada, nie skończy się sam,
+                // a Drop czeka na jego zakończenie.
+                unsafe { libc::kill(worker.pid, libc::SIGKILL) };
+                return Err(why);
+            }
+        };
+        if let errno @ 1.. = i32::from_be_bytes(errno) {
+            return Err(io::Error::from_raw_os_error(errno));
+        }
+        let (pid, pending) = (worker.pid, worker.pending.clone());
+        worker.reader = Some(thread::spawn(move || read_answers(reader, pid, max_frame, pending)));
+        Ok(worker)
+    }
+
+    /// Nowy proces z tymi samymi uprawnieniami, np. po awarii poprzedniego.
+    pub fn restart(&self, dir: Option<&str>) -> io::Result<Worker> {
+        Worker::start(self.account.as_ref(), dir, self.max_frame)
+    }
+
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    /// Wykonanie polecenia w procesie roboczym, równolegle z innymi
+    /// poleceniami sesji. Jeśli proces się zakończył (np. panika lub sygnał),
+    /// zwraca opis jego zakończenia.
+    pub fn execute(&self, request: &Request) -> Result<Answer, String> {
+        let id = self.next_id.fetch_add(1, Relaxed);
+        let (sender, receiver) = mpsc::channel();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if let Some(exit) = &pending.exit {
+                return Err(exit.clone());
+            }
+            pending.answers.insert(id, sender);
+        }
+        let sent = Encoding::Binary.encode_request(request)
+            .and_then(|data| Message::write(&mut *self.stream.lock().unwrap(), &tagged(id, data)));
+        if sent.is_err() {
+            // Odpowiedź (opis zakończenia procesu) przekaże wątek odbierający.
+            self.shutdown();
+        }
+        receiver.recv().unwrap_or_else(|_| Err("session worker lost".to_string()))
+    }
+
+    /// Zamknięte gniazdo kończy proces roboczy po bieżących poleceniach.
+    fn shutdown(&self) {
+        let _ = self.stream.lock().unwrap().shutdown(Shutdown::Both);
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.shutdown();
+        // Bez wątku odbierającego (nieudany start) na proces czekamy tutaj.
+        match self.reader.take() {
+            Some(reader) => {
+                let _ = reader.join();
+            }
+            None => {
+                wait(self.pid);
+            }
+        }
+    }
+}
+
+/// Wątek odbierający odpowiedzi procesu roboczego i przekazujący je
+/// oczekującym poleceniom. Po błędzie lub końcu gniazda czeka na koniec
+/// procesu i jego opisem kończy wszystkie oczekujące polecenia.
+fn read_answers(mut stream: UnixStream, pid: libc::pid_t, max_frame: usize, pending: Arc<Mutex<Pending>>) {
+    let why = loop {
+        let answer = Message::read(&mut stream, max_frame + TAG_SIZE)
+            .and_then(|data| untagged(&data).and_then(|(id, data)| Ok((id, Encoding::Binary.decode_answer(data)?))));
+        match answer {
+            Ok((id, answer)) => {
+                if let Some(sender) = pending.lock().unwrap().answers.remove(&id) {
+                    let _ = sender.send(Ok(answer));
+                }
+            }
+            Err(why) => break why,
+        }
+    };
+    // Zamknięte gniazdo kończy też proces, który jeszcze działa.
+    let _ = stream.shutdown(Shutdown::Both);
+    let exit = match FrameTooLarge::from_io(&why) {
+        Some(too_large) => {
+            wait(pid);
+            format!("session worker sent a too large answer ({} bytes, limit {})", too_large.size - TAG_SIZE, max_frame)
+        }
+        None => wait(pid),
+    };
+    let mut pending = pending.lock().unwrap();
+    for (_, sender) in pending.answers.drain() {
+        let _ = sender.send(Err(exit.clone()));
+    }
+    pending.exit = Some(exit);
+}
+
+/// Ramka z numerem żądania przed danymi.
+fn tagged(id: u32, data: Vec<u8>) -> Vec<u8> {
+    let mut frame = id.to_be_bytes().to_vec();
+    frame.extend(data);
+    frame
+}
+
+fn untagged(frame: &[u8]) -> io::Result<(u32, &[u8])> {
+    match frame.split_first_chunk::<TAG_SIZE>() {
+        Some((id, data)) => Ok((u32::from_be_bytes(*id), data)),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "frame without request id")),
+    }
+}
+
+/// Oczekiwanie na koniec procesu i opis jego zakończenia.
+fn wait(pid: libc::pid_t) -> String {
+    let mut status = 0;
+    if unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+        return format!("session worker lost: {}", io::Error::last_os_error());
+    }
+    if libc::WIFSIGNALED(status) {
+        let signal = libc::WTERMSIG(status);
+        let name = unsafe { CStr::from_ptr(libc::strsignal(signal)) }.to_string_lossy();
+        return format!("session worker killed by signal {} ({})", signal, name);
+    }
+    match libc::WEXITSTATUS(status) {
+        PANIC_EXIT => "session worker panicked".to_string(),
+        code => format!("session worker exited with status {}", code),
+    }
+}
+
+/// Polecenie uruchamiające proces roboczy. W testach plikiem
+/// wykonywalnym jest program testów, więc uruchamiamy w nim `tests::worker_process`.
+#[cfg(not(test))]
+fn command() -> Command {
+    let mut command = Command::new("/proc/self/exe");
+    command.arg(WORKER_ARG);
+    command
+}
+
+#[cfg(test)]
+fn command() -> Command {
+    let mut command = Command::new("/proc/self/exe");
+    command.args(["worker::tests::worker_process", "--exact", "--ignored", "--test-threads=1"]);
+    command.env(tests::WORKER_ENV, "1");
+    command
+}
+
+/// Proces roboczy (`WORKER_ARG`): zamyka odziedziczone deskryptory,
+/// odbiera ustawienia, zmienia użytkownika i wykonuje polecenia, każde
+/// w osobnym wątku. Zwraca kod wyjścia; panika w którymkolwiek wątku
+/// kończy proces z kodem `PANIC_EXIT`.
+pub fn main() -> libc::c_int {
+    let mut stream = unsafe {
+        // Ctrl-C w terminalu dociera do całej grupy procesów;
+        // o końcu pracy decyduje proces główny.
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGHUP, libc::SIG_IGN);
+        libc::close_range(WORKER_FD as libc::c_uint, libc::c_uint::MAX, 0);
+        // Gniazdo przenosimy ze stdin, a procesy uruchamiane przez polecenia
+        // (z uprawnieniami użytkownika) nie mogą go dostać.
+        if libc::fcntl(0, libc::F_DUPFD_CLOEXEC, WORKER_FD) != WORKER_FD {
+            return 1;
+        }
+        let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDONLY);
+        if null < 0 || libc::dup2(null, 0) < 0 {
+            return 1;
+        }
+        libc::close(null);
+        UnixStream::from_raw_fd(WORKER_FD)
+    };
+    let setup = match Message::read(&mut stream, MAX_FRAME).ok().and_then(|data| serde_json::from_slice::<Setup>(&data).ok()) {
+        Some(setup) => setup,
+        None => return 1,
+    };
+    let switched = setup.account.as_ref().map_or(Ok(()), |account| account.switch(account.gid));
+    let errno = switched.err().map_or(0, |why| why.raw_os_error().unwrap_or(libc::EPERM));
+    if stream.write_all(&errno.to_be_bytes()).is_err() || errno != 0 {
+        return 1;
+    }
+    // Katalog, którego już nie ma, nie jest powodem do przerwania sesji.
+    if let Some(dir) = setup.dir {
+        let _ = std::env::set_current_dir(dir);
+    }
+    let writer = match stream.try_clone() {
+        Ok(writer) => Arc::new(Mutex::new(writer)),
+        Err(_) => return 1,
+    };
+    let mut running: Vec<JoinHandle<()>> = Vec::new();
+    // Po zamknięciu gniazda kończymy pracę, gdy skończą się bieżące polecenia.
+    while let Ok(frame) = Message::read(&mut stream, setup.max_frame + TAG_SIZE) {
+        running.retain(|handle| !handle.is_finished());
+        let writer = writer.clone();
+        running.push(thread::spawn(move || {
+            let answer = panic::catch_unwind(AssertUnwindSafe(|| execute(&frame)))
+                .unwrap_or_else(|_| std::process::exit(PANIC_EXIT));
+            let sent = answer.and_then(|(id, answer)| {
+                let data = Encoding::Binary.encode_answer(&answer)?;
+                Message::write(&mut *writer.lock().unwrap(), &tagged(id, data))
+            });
+            // Bez gniazda odpowiedzi nie dotrą; proces główny już nie czeka.
+            if sent.is_err() {
+                std::process::exit(0);
+            }
+        }));
+    }
+    for handle in running {
+        let _ = handle.join();
+    }
+    0
+}
+
+/// Wykonanie polecenia z ramki procesu głównego.
+fn execute(frame: &[u8]) -> io::Result<(u32, Answer)> {
+    let (id, request) = untagged(frame)?;
+    let answer = Encoding::Binary.decode_request(request)
+        .map_err(Error::from)
+        .and_then(Executor::execute)
+        .unwrap_or_else(Answer::from);
+    Ok((id, answer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Zmienna środowiska, z którą program testów działa jako proces roboczy.
+    pub(super) const WORKER_ENV: &str = "CS_WORKER_TEST";
+
+    /// Odpowiednik `WORKER_ARG` w programie testów (patrz `command`).
+    #[test]
+    #[ignore]
+    fn worker_process() {
+        if std::env::var_os(WORKER_ENV).is_some() {
+            std::process::exit(main());
+        }
+    }
+
+    #[test]
+    fn test_worker() {
+        let worker = Worker::start(None, None, MAX_FRAME).unwrap();
+        let answer = worker.execute(&Request::new("cd".into(), vec!["/".into()])).unwrap();
+        assert_eq!(answer.code, 0);
+        let answer = worker.execute(&Request::new("pwd".into(), vec![])).unwrap();
+        assert_eq!(answer.data[0], "/");
+        // Katalog roboczy zmienił się tylko w procesie roboczym.
+        assert_ne!(std::env::current_dir().unwrap(), std::path::Path::new("/"));
+    }
+
+    #[test]
+    fn test_worker_switch_failed() {
+        // Nazwa z bajtem 0 nie przejdzie przez initgroups.
+        let account = Account { name: "no\0body".into(), uid: 65534, gid: 65534 };
+        let why = Worker::start(Some(&account), None, MAX_FRAME).err().unwrap();
+        assert_eq!(why.raw_os_error(), Some(libc::EPERM));
+    }
+
+    #[test]
+    fn test_worker_concurrent() {
+        let worker = Worker::start(None, None, MAX_FRAME).unwrap();
+        thread::scope(|scope| {
+            let slow = scope.spawn(|| worker.execute(&Request::new("sleep".into(), vec!["1".into()])));
+            thread::sleep(Duration::from_millis(100));
+            // `pwd` nie czeka na koniec `sleep`.
+            let answer = worker.execute(&Request::new("pwd".into(), vec![])).unwrap();
+            assert_eq!(answer.code, 0);
+            assert!(!slow.is_finished());
+            assert_eq!(slow.join().unwrap().unwrap().code, 0);
+        });
+    }
+
+    #[test]
+    fn test_worker_exec() {
+        // Proces roboczy to nowy program, a nie kopia procesu testów.
+        let worker = Worker::start(None, None, MAX_FRAME).unwrap();
+        let cmdline = std::fs::read(format!("/proc/{}/cmdline", worker.pid())).unwrap();
+        assert!(String::from_utf8_lossy(&cmdline).contains("worker::tests::worker_process"));
+    }
+
+    #[test]
+    fn test_worker_fd_not_inherited() {
+        let worker = Worker::start(None, None, MAX_FRAME).unwrap();
+        let fd = format!("/proc/self/fd/{}", WORKER_FD);
+        let answer = worker.execute(&Request::new("readlink".into(), vec![fd])).unwrap();
+        assert!(!answer.data[0].starts_with("socket:"), "{:?}", answer.data);
+        assert!(answer.data[0].is_empty());
+    }
+
+    #[test]
+    fn test_worker_exit() {
+        let worker = Worker::start(None, None, MAX_FRAME).unwrap();
+        unsafe { libc::kill(worker.pid(), libc::SIGKILL) };
+        let pwd = Request::new("pwd".into(), vec![]);
+        let exit = worker.execute(&pwd).unwrap_err();
+        assert!(exit.starts_with("session worker killed by signal 9"), "{}", exit);
+        assert_eq!(worker.execute(&pwd).unwrap_err(), exit);
+
+        let worker = worker.restart(Some("/")).unwrap();
+        assert_eq!(worker.execute(&pwd).unwrap().data[0], "/");
+    }
+
+    #[test]
+    fn test_worker_frame_limit() {
+        let worker = Worker::start(None, None, 64).unwrap();
+        let exit = worker.execute(&Request::new("seq".into(), vec!["100".into()])).unwrap_err();
+        assert!(exit.starts_with("session worker sent a too large answer"), "{}", exit);
+        assert!(exit.ends_with("limit 64)"), "{}", exit);
+
+        // Żądanie równe limitowi mieści się mimo numeru w ramce.
+        let mut request = Request::new("put".into(), vec!["/nonexistent/dir/file".into()]);
+        request.binary = vec![0; 1000];
+        let max_frame = Encoding::Binary.encode_request(&request).unwrap().len();
+        let worker = Worker::start(None, None, max_frame).unwrap();
+        let answer = worker.execute(&request).unwrap();
+        assert_ne!(answer.code, 0);
+        assert!(answer.message.contains("No such file"), "{}", answer.message);
+    }
+}
@@ -0,0 +1,113 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Porównanie kodowania JSON i binarnego dla odpowiedzi z zawartością pliku:
+//! samo kodowanie/dekodowanie oraz pełny przesył (szyfrowanie i TCP).
+//! Uruchomienie: `cargo bench -p shared --bench wire`.
+
+use std::hint::black_box;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use shared::data::{answer::Answer, request::Request, wire::Encoding};
+use shared::net::connector::{ConnectionSide, Connector};
+use shared::compress::lz;
+use shared::net::protocol::{Hello, CAPABILITIES, CAP_BINARY, CAP_COMPRESSION};
+
+const PAYLOAD: usize = 256 * 1024;
+
+fn answer() -> Answer {
+    let mut answer = Answer::new_with_data(0, "OK", "upload", vec!["file.bin".into()]);
+    answer.binary = (0..PAYLOAD).map(|i| (i * 31 % 251) as u8).collect();
+    answer
+}
+
+fn codec(c: &mut Criterion) {
+    let answer = answer();
+    let mut group = c.benchmark_group("codec");
+    group.throughput(Throughput::Bytes(PAYLOAD as u64));
+    for encoding in Encoding::ALL {
+        let name = format!("{:?}", encoding);
+        group.bench_function(BenchmarkId::new("encode", &name), |b| {
+            b.iter(|| encoding.encode_answer(black_box(&answer)).unwrap())
+        });
+        let data = encoding.encode_answer(&answer).unwrap();
+        group.bench_function(BenchmarkId::new("decode", &name), |b| {
+            b.iter(|| encoding.decode_answer(black_box(&data)).unwrap())
+        });
+    }
+    let data = answer.binary.clone();
+    group.bench_function("compress", |b| b.iter(|| lz::compress(black_box(&data))));
+    let packed = lz::compress(&data);
+    group.bench_function("decompress", |b| {
+        b.iter(|| lz::decompress(black_box(&packed), PAYLOAD).unwrap())
+    });
+    group.finish();
+}
+
+/// Serwer odpowiadający na każde żądanie tą samą odpowiedzią.
+fn server(listener: TcpListener) {
+    let (stream, _) = listener.accept().unwrap();
+    let mut conn = Connector::new(stream, ConnectionSide::Server);
+    conn.init().unwrap();
+    let answer = answer();
+    while conn.read_request().is_ok() {
+        if conn.send_answer(answer.clone()).is_err() {
+            break;
+        }
+    }
+}
+
+fn transfer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transfer");
+    group.throughput(Throughput::Bytes(PAYLOAD as u64));
+    group.sample_size(20);
+    let variants = [
+        ("Binary", CAPABILITIES & !CAP_COMPRESSION),
+        ("Json", CAPABILITIES & !(CAP_BINARY | CAP_COMPRESSION)),
+        ("Binary+compression", CAPABILITIES),
+    ];
+    for (name, capabilities) in variants {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || server(listener));
+
+        let mut conn = Connector::new(TcpStream::connect(addr).unwrap(), ConnectionSide::Client);
+        conn.set_hello(Hello { capabilities, ..Hello::default() });
+        conn.init().unwrap();
+        assert_eq!(conn.protocol().capabilities, capabilities);
+        let request = Request::new("get".into(), vec!["file.bin".into()]);
+
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                conn.send_request(request.clone()).unwrap();
+                conn.read_answer().unwrap()
+            })
+        });
+        drop(conn);
+        handle.join().unwrap();
+    }
+    group.finish();
+}
+
+criterion_group!(benches, codec, transfer);
+criterion_main!(benches);
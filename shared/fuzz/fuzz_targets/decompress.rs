@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shared::compress::lz;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(plain) = lz::decompress(data, 1 << 20) {
+        assert_eq!(lz::decompress(&lz::compress(&plain), 1 << 20).unwrap(), plain);
+    }
+    assert_eq!(lz::decompress(&lz::compress(data), data.len()).unwrap(), data);
+});
@@ -0,0 +1,37 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shared::crypto::blowfish::Blowfish;
+use shared::crypto::BlockCipher;
+use shared::data::message::{Message, FrameTooLarge};
+use shared::data::wire::Encoding;
+use shared::net::protocol::{Hello, Protocol};
+
+const LIMIT: usize = 1024;
+
+// Ramki czytane z dowolnych bajtów i dekodowanie ich zawartości (także
+// po odszyfrowaniu), tak jak serwer robi to z danymi od nieznanego klienta.
+fuzz_target!(|data: &[u8]| {
+    let blowfish = Blowfish::new(b"fuzzing key").unwrap();
+    let mut input = data;
+    loop {
+        let frame = match Message::read_with_flags(&mut input, LIMIT) {
+            Ok((frame, _)) => frame,
+            Err(err) => {
+                if let Some(too_large) = FrameTooLarge::from_io(&err) {
+                    assert!(too_large.size > LIMIT);
+                }
+                break;
+            }
+        };
+        assert!(frame.len() <= LIMIT);
+        for payload in [frame.clone(), blowfish.decrypt_cbc(&frame)] {
+            let _ = Hello::from_bytes(&payload);
+            let _ = Protocol::from_bytes(&payload);
+            for encoding in Encoding::ALL {
+                let _ = encoding.decode_request(&payload);
+                let _ = encoding.decode_answer(&payload);
+            }
+        }
+    }
+});
@@ -0,0 +1,42 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Dekodowanie dowolnych bajtów jako strumienia rekordów i wartości.
+//! Uruchomienie: `cargo +nightly fuzz run record` (w katalogu shared).
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shared::sqlite::{decode_rows, encode_rows, Value};
+
+fuzz_target!(|data: &[u8]| {
+    // Błędne dane mają dawać błąd, a nie panikę.
+    if let Ok(rows) = decode_rows(data) {
+        // Poprawnie odczytane wiersze muszą dać się zapisać i odczytać ponownie.
+        let bytes = encode_rows(&rows);
+        let again = decode_rows(&bytes).expect("re-encoded rows must decode");
+        assert_eq!(encode_rows(&again), bytes);
+    }
+    if let Ok(value) = Value::from_bytes(data) {
+        assert_eq!(value.to_bytes(), data);
+    }
+});
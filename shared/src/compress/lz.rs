@@ -0,0 +1,242 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Prosta kompresja LZ77 (w stylu LZ4), bez zewnętrznych bibliotek.
+//!
+//! Format:
+//! ```text
+//! u32 (BE)    długość danych po dekompresji
+//! sekwencje:
+//!   u8        token: starsze 4 bity - liczba literałów, młodsze - długość dopasowania - 4
+//!   [u8]      dalsza część liczby literałów (gdy 15): bajty 255... i reszta
+//!   [u8]      literały
+//!   u16 (LE)  odległość dopasowania (1..=65535)          } pomijane w ostatniej
+//!   [u8]      dalsza część długości dopasowania (gdy 15)  } sekwencji
+//! u8          END
+//! ```
+//! Dane kończą się bajtem END, bo odszyfrowanie CBC obcina końcowe
+//! 0x80 0x00..., gdy wyglądają na wypełnienie bloku.
+
+use std::io::{self, Error, ErrorKind};
+
+const MIN_MATCH: usize = 4;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+const END: u8 = 0xe5;
+
+/// Kompresja danych.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let n = input.len();
+    let mut out = Vec::with_capacity(n / 2 + 16);
+    out.extend_from_slice(&(n as u32).to_be_bytes());
+
+    // Pozycje (+1) ostatnich wystąpień 4-bajtowych ciągów, 0 - brak.
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut i = 0;
+    while i + MIN_MATCH <= n {
+        let seq = u32::from_le_bytes(input[i..i + MIN_MATCH].try_into().unwrap());
+        let h = (seq.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize;
+        let candidate = table[h];
+        table[h] = i + 1;
+
+        if candidate > 0 {
+            let c = candidate - 1;
+            if i - c <= MAX_OFFSET && input[c..c + MIN_MATCH] == input[i..i + MIN_MATCH] {
+                let mut len = MIN_MATCH;
+                while i + len < n && input[c + len] == input[i + len] {
+                    len += 1;
+                }
+                sequence(&mut out, &input[anchor..i], Some((i - c, len)));
+                i += len;
+                anchor = i;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    if anchor < n {
+        sequence(&mut out, &input[anchor..], None);
+    }
+    out.push(END);
+    out
+} // fn compress
+
+/// Zapis jednej sekwencji: literały i (opcjonalnie) dopasowanie.
+fn sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let lit = literals.len();
+    let extra = matched.map(|(_, len)| len - MIN_MATCH).unwrap_or(0);
+    out.push(((lit.min(15) as u8) << 4) | extra.min(15) as u8);
+    if lit >= 15 {
+        length(out, lit - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if extra >= 15 {
+            length(out, extra - 15);
+        }
+    }
+} // fn sequence
+
+fn length(out: &mut Vec<u8>, mut rest: usize) {
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+} // fn length
+
+/// Dekompresja danych.
+/// Dane, które po dekompresji byłyby dłuższe niż `limit`, są odrzucane
+/// (zanim zostanie zarezerwowana dla nich pamięć).
+pub fn decompress(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    if data.len() < 5 || data[data.len() - 1] != END {
+        return Err(invalid("invalid compressed data"));
+    }
+    let n = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+    if n > limit {
+        return Err(invalid("decompressed data too large"));
+    }
+    let mut input = Input { data: &data[4..data.len() - 1], pos: 0 };
+    // Długość pochodzi z danych - rezerwujemy tylko tyle,
+    // ile mogą dać dane tej wielkości.
+    let mut out = Vec::with_capacity(n.min(input.data.len().saturating_mul(64)));
+
+    while out.len() < n {
+        let token = input.byte()?;
+        let mut lit = (token >> 4) as usize;
+        if lit == 15 {
+            lit += input.length()?;
+        }
+        if lit > n - out.len() {
+            return Err(invalid("compressed data overruns its length"));
+        }
+        out.extend_from_slice(input.take(lit)?);
+        if out.len() == n {
+            break;
+        }
+
+        let offset = u16::from_le_bytes(input.take(2)?.try_into().unwrap()) as usize;
+        let mut len = (token & 15) as usize;
+        if len == 15 {
+            len += input.length()?;
+        }
+        len += MIN_MATCH;
+        if offset == 0 || offset > out.len() || len > n - out.len() {
+            return Err(invalid("invalid match in compressed data"));
+        }
+        let start = out.len() - offset;
+        if offset >= len {
+            out.extend_from_within(start..start + len);
+        } else {
+            // Dopasowanie nachodzi na kopiowane właśnie bajty.
+            for k in 0..len {
+                out.push(out[start + k]);
+            }
+        }
+    }
+    if input.pos != input.data.len() {
+        return Err(invalid("trailing bytes in compressed data"));
+    }
+    Ok(out)
+} // fn decompress
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if n > self.data.len() - self.pos {
+            return Err(invalid("truncated compressed data"));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn length(&mut self) -> io::Result<usize> {
+        let mut len = 0usize;
+        loop {
+            let b = self.byte()?;
+            len = len.saturating_add(b as usize);
+            if b != 255 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const LIMIT: usize = 1 << 24;
+
+    #[test]
+    fn test_roundtrip() {
+        let text = "drwxr-xr-x  piotr  staff  4096  2025-01-01 12:00  src\n".repeat(200);
+        let packed = compress(text.as_bytes());
+        assert!(packed.len() < text.len() / 10);
+        assert_eq!(decompress(&packed, LIMIT).unwrap(), text.as_bytes());
+
+        for data in [&b""[..], b"a", b"abcd", b"aaaaaaaaaaaaaaaaaaaaaaaa", &[0u8; 100_000]] {
+            assert_eq!(decompress(&compress(data), LIMIT).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let packed = compress(&b"abcabcabcabcabcabcabc".repeat(10));
+        for n in 0..packed.len() {
+            assert!(decompress(&packed[..n], LIMIT).is_err());
+        }
+        assert!(decompress(&packed, 10).is_err());
+        // Odległość dopasowania poza danymi.
+        assert!(decompress(&[0, 0, 0, 8, 0x10, b'a', 9, 0, END], LIMIT).is_err());
+        // Deklarowana ogromna długość nie powoduje rezerwacji pamięci.
+        assert!(decompress(&[0xff, 0xff, 0xff, 0xff, 0, END], usize::MAX).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip(data in proptest::collection::vec(prop_oneof![Just(b'a'), Just(b'b'), any::<u8>()], 0..2000)) {
+            prop_assert_eq!(decompress(&compress(&data), LIMIT).unwrap(), data);
+        }
+
+        #[test]
+        fn garbage_does_not_panic(data in proptest::collection::vec(any::<u8>(), 0..300)) {
+            let _ = decompress(&data, LIMIT);
+        }
+    }
+}
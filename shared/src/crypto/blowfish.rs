@@ -20,23 +20,23 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 #![allow(dead_code)]
-use crate::crypto::tool::*;
+use crate::crypto::mode::{BlockCipher, KeyError};
+use crate::crypto::tool::{block_to_bytes, bytes_to_block};
 
 const BLOCK_SIZE: usize = 8;
 pub const MIN_KEY_SIZE: usize = 4;
 pub const MAX_KEY_SIZE: usize = 56;
 
+#[derive(Clone)]
 pub struct Blowfish {
     p: [u32; 18],
     s: [[u32; 256]; 4],
 }
 
 impl Blowfish {
-    pub fn new(key: &[u8]) -> Result<Blowfish, &'static str> {
+    pub fn new(key: &[u8]) -> Result<Blowfish, KeyError> {
+        KeyError::check(key, MIN_KEY_SIZE, MAX_KEY_SIZE)?;
         let key_len = key.len();
-        if !(4..=56).contains(&key_len) {
-            return Err("invalid key size");
-        }
         
         let mut bf = Blowfish { p: [0u32; 18], s: [[0u32; 256]; 4] };
         bf.s = ORIG_S;
@@ -141,9 +141,6 @@ impl Blowfish {
     *                                                               *
     ****************************************************************/
     
-    fn encrypt_block(&self, x: (u32, u32)) -> (u32, u32) {
-        self.encrypt(x.0, x.1)
-    }
     pub fn encrypt(&self, mut xl: u32, mut xr: u32) -> (u32, u32) {
         xl ^= self.p[0];  xr ^= self.f(xl);
         xr ^= self.p[1];  xl ^= self.f(xr);
@@ -167,10 +164,7 @@ impl Blowfish {
         (xr, xl)
     }
 
-    /// Odszyfrowanie jednego bloku 64 bit = tuple (u32,u32).
-    fn decrypt_block(&self, x: (u32, u32)) -> (u32, u32) {
-        self.decrypt(x.0, x.1)
-    }
+    /// Odszyfrowanie jednego bloku 64 bit = (u32,u32).
     pub fn decrypt(&self, mut xl: u32, mut xr: u32) -> (u32, u32) {
         xl ^= self.p[17];
         xr ^= self.f(xl);
@@ -207,138 +201,23 @@ impl Blowfish {
 
         (xr ^ self.p[0], xl ^ self.p[1])
     }
+}   // end of Blowfish
 
-    /****************************************************************
-    *                                                               *
-    *                            E C B                              *
-    *                                                               *
-    ****************************************************************/
-
-    /// Zaszyfrowanie ciągu bajtów w trybie ECB.
-    /// Wielkość zaszyfrowanego ciągu ma taką samą długość ciągu szyfrowanego.
-    /// Jeśli długość ciągu do zaszyfrowania nie jest wielokrotnością bloku,
-    /// zostanie on uzupełniony paddingiem. 
-    /// UWAGA: ten sam ciąg po zaszyfrowaniu zawsze wygląda tak samo.
-    pub fn encrypt_ecb(&self, input: &[u8]) -> Vec<u8> {
-        if input.is_empty() { return vec![]; }
-        let plain = align_to_block(input, BLOCK_SIZE);
-        let mut cipher = vec![0u8; plain.len()];
-
-        plain.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let plain_block = bytes_to_block(&plain[i..]);
-                let cipher_block = self.encrypt_block(plain_block);
-                block_to_bytes(cipher_block, &mut cipher[i..i+BLOCK_SIZE]);
-            });
-        
-        cipher
-    }
-
-    /// Odszyfrowanie ciągu bajtów w trybie ECB.
-    /// Długość ciągu bajtów musi być wielokrotnością długości bloków.
-    pub fn decrypt_ecb(&self, cipher: &[u8]) -> Vec<u8> {
-        if cipher.is_empty() || cipher.len() % BLOCK_SIZE != 0 {
-            return vec![];
-        }
-        let mut plain = vec![0u8; cipher.len()];
-        
-        cipher.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let cipher_block = bytes_to_block(&cipher[i..]);
-                let plain_block = self.decrypt_block(cipher_block);
-                block_to_bytes(plain_block, &mut plain[i..i+BLOCK_SIZE]);
-            });
-
-        pad_index(&plain)
-            .map(|idx| plain[..idx].to_vec())
-            .unwrap_or(plain)
-    }
-    pub fn decrypt_ecb_ext(&self, cipher: &[u8]) -> Vec<u8> {
-        if cipher.is_empty() || cipher.len() % BLOCK_SIZE != 0 {
-            return vec![];
-        }
-        let mut plain = vec![0u8; cipher.len()];
-
-        cipher.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let cipher_block = bytes_to_block(&cipher[i..]);
-                let plain_block = self.decrypt_block(cipher_block);
-                block_to_bytes(plain_block, &mut plain[i..i+BLOCK_SIZE]);
-            });
-
-        pad_index(&plain)
-            .map(|idx| plain[..idx].to_vec())
-            .unwrap_or(plain)
+impl BlockCipher for Blowfish {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
     }
-    /****************************************************************
-    *                                                               *
-    *                            C B C                              *
-    *                                                               *
-    ****************************************************************/
-
-    /// Zaszyfrowanie ciągu bajtów w trybie CBC.
-    /// Przy szyfrowaniu używa się losowego IV.
-    /// Oznacza to, że nawet jeśli wiele razy szyfrujemy
-    /// ten sam tekst, po zaszyfrowaniu będzie on zawsze wyglądał inaczej.
-    pub fn encrypt_cbc(&self, input: &[u8]) -> Vec<u8> {
-        if input.is_empty() { return vec![]; }
-        
-        let plain = align_to_block(input, BLOCK_SIZE);         
-        let mut cipher = vec![0u8; BLOCK_SIZE + plain.len()];
-        iv_fill(&mut cipher[..BLOCK_SIZE]);
 
-        let mut cipher_block = bytes_to_block(&cipher);
-        plain.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let plain_block = bytes_to_block(&plain[i..]);
-                let w0 = plain_block.0 ^ cipher_block.0;
-                let w1 = plain_block.1 ^ cipher_block.1;
-                cipher_block = self.encrypt(w0, w1);
-                let pos = i + BLOCK_SIZE;
-                block_to_bytes(cipher_block, &mut cipher[pos..pos + BLOCK_SIZE]);
-            });         
-        
-        cipher
+    fn encrypt_block(&self, block: &mut [u8]) {
+        let (xl, xr) = bytes_to_block(block);
+        block_to_bytes(self.encrypt(xl, xr), block);
     }
 
-    /// Odszyfrowanie ciągu bajtów w trybie CBC.
-    /// Długość ciągu bajtów musi być wielokrotnością długości bloku
-    /// i musi zawierać co najmniej 2 bloki.
-    pub fn decrypt_cbc(&self, cipher: &[u8]) -> Vec<u8> {
-        let nbytes = cipher.len();
-        if nbytes / BLOCK_SIZE < 2 || nbytes % BLOCK_SIZE != 0 {
-            return vec![];
-        }
-        let mut plain = vec![0; nbytes - BLOCK_SIZE];
-        
-        let mut prv_cipher_block = bytes_to_block(cipher);
-        cipher[BLOCK_SIZE..].iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let cipher_block = bytes_to_block(&cipher[i+BLOCK_SIZE..]);
-                let tmp = cipher_block;
-                let plain_block = self.decrypt_block(cipher_block);
-                let w0 = plain_block.0 ^ prv_cipher_block.0;
-                let w1 = plain_block.1 ^ prv_cipher_block.1;
-                block_to_bytes((w0, w1), &mut plain[i..i+BLOCK_SIZE]);
-                prv_cipher_block = tmp;
-            });
-        
-        pad_index(&plain)
-            .map(|idx| plain[..idx].to_vec())
-            .unwrap_or(plain)
+    fn decrypt_block(&self, block: &mut [u8]) {
+        let (xl, xr) = bytes_to_block(block);
+        block_to_bytes(self.decrypt(xl, xr), block);
     }
-
-}   // end of Blowfish
+}
 
 /********************************************************************
 *                                                                   *
@@ -634,7 +513,7 @@ mod tests {
         let key = "TESTKEY".as_bytes();
         let bf = Blowfish::new(key).unwrap();
 
-        let encrypted = bf.encrypt_block(plain);
+        let encrypted = bf.encrypt(plain.0, plain.1);
         assert_eq!(encrypted, expected)
     }
 
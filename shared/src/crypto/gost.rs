@@ -20,7 +20,8 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::crypto::tool::*;
+use crate::crypto::mode::{BlockCipher, KeyError};
+use crate::crypto::tool::{block_to_bytes, bytes_to_block};
 
 const BLOCK_SIZE: usize = 8;
 // 8 bytes = 2 u32 = 54 bit
@@ -51,14 +52,12 @@ pub struct Gost {
 }
 
 impl Gost {
-    pub fn new_with_text_key<T: AsRef<str>>(key: T) -> Result<Self, &'static str> {
+    pub fn new_with_text_key<T: AsRef<str>>(key: T) -> Result<Self, KeyError> {
         Self::new(key.as_ref().as_bytes())
     }
     
-    pub fn new(key: &[u8]) -> Result<Self, &'static str> {
-        if key.len() != KEY_SIZE {
-            return Err("invalid key size");
-        }
+    pub fn new(key: &[u8]) -> Result<Self, KeyError> {
+        KeyError::check(key, KEY_SIZE, KEY_SIZE)?;
         
         let mut k = [0u32; 8];
         let mut k87 = [0u8; 256];
@@ -129,10 +128,7 @@ impl Gost {
     *                                                               *
     ****************************************************************/
     
-    pub fn encrypt_block(&self, x: (u32,u32)) -> (u32,u32) {
-        self.encrypt(x.0, x.1)
-    }
-    fn encrypt(&self, mut xl: u32, mut xr: u32) -> (u32, u32) {
+    pub fn encrypt(&self, mut xl: u32, mut xr: u32) -> (u32, u32) {
         xr ^= self.f(xl.wrapping_add(self.k0));
         xl ^= self.f(xr.wrapping_add(self.k1));
         xr ^= self.f(xl.wrapping_add(self.k2));
@@ -172,9 +168,6 @@ impl Gost {
         (xr, xl)
     }
 
-    fn decrypt_block(&self, x: (u32, u32)) -> (u32, u32) {
-        self.decrypt(x.0, x.1)
-    }
     pub fn decrypt(&self, mut xl: u32, mut xr: u32) -> (u32, u32) {
         xr ^= self.f(xl.wrapping_add(self.k0));
         xl ^= self.f(xr.wrapping_add(self.k1));
@@ -214,119 +207,23 @@ impl Gost {
 
         (xr, xl)
     }
+} // Gost
 
-    /****************************************************************
-    *                                                               *
-    *                            E C B                              *
-    *                                                               *
-    ****************************************************************/
-    
-    /// Zaszyfrowanie ciągu bajtów w trybie ECB.
-    /// Wielkość zaszyfrowanego ciągu ma taką samą długość ciągu szyfrowanego.
-    /// Jeśli długość ciągu do zaszyfrowania nie jest wielokrotnością bloku,
-    /// zostanie on uzupełniony paddingiem. 
-    /// UWAGA: ten sam ciąg po zaszyfrowaniu zawsze wygląda tak samo.
-    pub fn encrypt_ecb(&self, input: &[u8]) -> Vec<u8> {
-        if input.is_empty() { return vec![]; }
-        let plain = align_to_block(input, BLOCK_SIZE);
-        let mut cipher = vec![0u8; plain.len()];
-        
-        plain.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let plain_block  = bytes_to_block(&plain[i..]);
-                let cipher_block = self.encrypt_block(plain_block);
-                block_to_bytes(cipher_block, &mut cipher[i..i+BLOCK_SIZE]);
-            });
-        
-        cipher
-    }
-
-    /// Odszyfrowanie ciągu bajtów w trybie ECB.
-    /// Długość ciągu bajtów musi być wielokrotnością długości bloków.
-    pub fn decrypt_ecb(&self, cipher: &[u8]) -> Vec<u8> {
-        if cipher.is_empty() || cipher.len() % BLOCK_SIZE != 0 {
-            return vec![]; 
-        }
-        let mut plain = vec![0u8; cipher.len()];
-
-        cipher.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let cipher_block = bytes_to_block(&cipher[i..]);
-                let plain_block = self.decrypt_block(cipher_block);
-                block_to_bytes(plain_block, &mut plain[i..i+BLOCK_SIZE]);
-            });
-
-        pad_index(&plain)
-            .map(|idx| plain[..idx].to_vec())
-            .unwrap_or(plain)
+impl BlockCipher for Gost {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
     }
 
-    /****************************************************************
-    *                                                               *
-    *                            C B C                              *
-    *                                                               *
-    ****************************************************************/
-
-    /// Zaszyfrowanie ciągu bajtów w trybie CBC.
-    /// Przy szyfrowaniu używa się losowego IV.
-    /// Oznacza to, że nawet jeśli wiele razy szyfrujemy
-    /// ten sam tekst, po zaszyfrowaniu będzie on zawsze wyglądał inaczej.
-    pub fn encrypt_cbc(&self, input: &[u8]) -> Vec<u8> {
-        if input.is_empty() { return vec![]; }
-
-        let plain = align_to_block(input, BLOCK_SIZE);
-        let mut cipher = vec![0u8; BLOCK_SIZE + plain.len()];
-        iv_fill(&mut cipher[0..BLOCK_SIZE]);
-
-        let mut cipher_block = bytes_to_block(&cipher);
-        plain.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let plain_block = bytes_to_block(&plain[i..]);
-                let w0 = plain_block.0 ^ cipher_block.0;
-                let w1 = plain_block.1 ^ cipher_block.1;
-                cipher_block = self.encrypt(w0, w1);
-                let pos = i + BLOCK_SIZE;
-                block_to_bytes(cipher_block, &mut cipher[pos..pos+BLOCK_SIZE]);            
-            });
-        
-        cipher
+    fn encrypt_block(&self, block: &mut [u8]) {
+        let (xl, xr) = bytes_to_block(block);
+        block_to_bytes(self.encrypt(xl, xr), block);
     }
 
-    /// Odszyfrowanie ciągu bajtów w trybie CBC.
-    /// Długość ciągu bajtów musi być wielokrotnością długości bloku
-    /// i musi zawierać co najmniej 2 bloki.
-    pub fn decrypt_cbc(&self, cipher: &[u8]) -> Vec<u8> {
-        let nbytes = cipher.len();
-        if nbytes / BLOCK_SIZE < 2 || nbytes % BLOCK_SIZE != 0 {
-            return vec![];
-        }
-        let mut plain = vec![0u8; nbytes - BLOCK_SIZE];
-
-        let mut prv_cipher_block = bytes_to_block(cipher);
-        cipher[BLOCK_SIZE..].iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let cipher_block = bytes_to_block(&cipher[i+BLOCK_SIZE..]);
-                let tmp = cipher_block;
-                let plain_block = self.decrypt_block(cipher_block);
-                let w0 = plain_block.0 ^ prv_cipher_block.0;
-                let w1 = plain_block.1 ^ prv_cipher_block.1;
-                block_to_bytes((w0, w1), &mut plain[i..i+BLOCK_SIZE]);
-                prv_cipher_block = tmp;
-            });
-
-        pad_index(&plain)
-            .map(|idx| plain[..idx].to_vec())
-            .unwrap_or(plain)
+    fn decrypt_block(&self, block: &mut [u8]) {
+        let (xl, xr) = bytes_to_block(block);
+        block_to_bytes(self.decrypt(xl, xr), block);
     }
-} // Gost
+}
 
 #[cfg(test)]
 mod tests {
@@ -354,10 +251,10 @@ mod tests {
         ];
 
         for i in 0..plain.len() {            
-            let encrypted = gt.encrypt_block(plain[i]);
+            let encrypted = gt.encrypt(plain[i].0, plain[i].1);
             assert_eq!(expected[i], encrypted);
             
-            let decrypted = gt.decrypt_block(encrypted);
+            let decrypted = gt.decrypt(encrypted.0, encrypted.1);
             assert_eq!(plain[i], decrypted);
         }
     }
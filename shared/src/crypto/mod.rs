@@ -1,4 +1,7 @@
 pub mod blowfish;
+pub mod mode;
 pub mod tool;
 pub mod way3;
 pub mod gost;
+
+pub use mode::{BlockCipher, KeyError};
@@ -0,0 +1,370 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Szyfry blokowe i tryby ich pracy.
+//!
+//! Szyfr implementuje tylko `BlockCipher` (rozmiar bloku i szyfrowanie
+//! jednego bloku), a tryby ECB, CBC, CTR, CFB i OFB dostaje z domyślnych
+//! metod tego traitu. Tryby z IV zapisują go na początku szyfrogramu.
+//! ECB i CBC uzupełniają dane do pełnego bloku (0x80 0x00...), CTR, CFB
+//! i OFB działają jak szyfry strumieniowe i nie zmieniają długości danych.
+//! Niepoprawny szyfrogram (np. zła długość) daje pusty wynik.
+
+use std::error::Error;
+use std::fmt;
+use crate::crypto::tool::{align_to_block, iv_fill, pad_index};
+
+/// Szyfr blokowy.
+pub trait BlockCipher {
+    /// Rozmiar bloku w bajtach.
+    fn block_size(&self) -> usize;
+    /// Zaszyfrowanie jednego bloku (`block_size` bajtów) w miejscu.
+    fn encrypt_block(&self, block: &mut [u8]);
+    /// Odszyfrowanie jednego bloku (`block_size` bajtów) w miejscu.
+    fn decrypt_block(&self, block: &mut [u8]);
+
+    /****************************************************************
+    *                                                               *
+    *                            E C B                              *
+    *                                                               *
+    ****************************************************************/
+
+    /// Zaszyfrowanie ciągu bajtów w trybie ECB.
+    /// Jeśli długość ciągu do zaszyfrowania nie jest wielokrotnością bloku,
+    /// zostanie on uzupełniony paddingiem.
+    /// UWAGA: ten sam ciąg po zaszyfrowaniu zawsze wygląda tak samo.
+    fn encrypt_ecb(&self, input: &[u8]) -> Vec<u8> {
+        if input.is_empty() { return vec![]; }
+        let mut cipher = align_to_block(input, self.block_size());
+        cipher.chunks_exact_mut(self.block_size()).for_each(|block| self.encrypt_block(block));
+        cipher
+    }
+
+    /// Odszyfrowanie ciągu bajtów w trybie ECB.
+    /// Długość ciągu bajtów musi być wielokrotnością długości bloku.
+    fn decrypt_ecb(&self, cipher: &[u8]) -> Vec<u8> {
+        if cipher.is_empty() || !cipher.len().is_multiple_of(self.block_size()) {
+            return vec![];
+        }
+        let mut plain = cipher.to_vec();
+        plain.chunks_exact_mut(self.block_size()).for_each(|block| self.decrypt_block(block));
+        unpad(plain)
+    }
+
+    /****************************************************************
+    *                                                               *
+    *                            C B C                              *
+    *                                                               *
+    ****************************************************************/
+
+    /// Zaszyfrowanie ciągu bajtów w trybie CBC z losowym IV.
+    /// Nawet jeśli wiele razy szyfrujemy ten sam tekst,
+    /// po zaszyfrowaniu będzie on zawsze wyglądał inaczej.
+    fn encrypt_cbc(&self, input: &[u8]) -> Vec<u8> {
+        if input.is_empty() { return vec![]; }
+        let bs = self.block_size();
+        let mut cipher = with_iv(bs, &align_to_block(input, bs));
+        for pos in (bs..cipher.len()).step_by(bs) {
+            let (previous, block) = cipher.split_at_mut(pos);
+            let block = &mut block[..bs];
+            xor(block, &previous[pos - bs..]);
+            self.encrypt_block(block);
+        }
+        cipher
+    }
+
+    /// Odszyfrowanie ciągu bajtów w trybie CBC.
+    /// Długość ciągu bajtów musi być wielokrotnością długości bloku
+    /// i musi zawierać co najmniej 2 bloki (IV i dane).
+    fn decrypt_cbc(&self, cipher: &[u8]) -> Vec<u8> {
+        let bs = self.block_size();
+        if cipher.len() / bs < 2 || !cipher.len().is_multiple_of(bs) {
+            return vec![];
+        }
+        let mut plain = cipher[bs..].to_vec();
+        plain.chunks_exact_mut(bs)
+            .zip(cipher.chunks_exact(bs))
+            .for_each(|(block, previous)| {
+                self.decrypt_block(block);
+                xor(block, previous);
+            });
+        unpad(plain)
+    }
+
+    /****************************************************************
+    *                                                               *
+    *                            C T R                              *
+    *                                                               *
+    ****************************************************************/
+
+    /// Zaszyfrowanie w trybie CTR: strumień klucza to zaszyfrowane kolejne
+    /// wartości licznika (IV jako liczba big-endian).
+    fn encrypt_ctr(&self, input: &[u8]) -> Vec<u8> {
+        if input.is_empty() { return vec![]; }
+        let mut cipher = with_iv(self.block_size(), input);
+        let (iv, data) = cipher.split_at_mut(self.block_size());
+        ctr(self, iv, data);
+        cipher
+    }
+
+    fn decrypt_ctr(&self, cipher: &[u8]) -> Vec<u8> {
+        let Some((iv, data)) = split_iv(self.block_size(), cipher) else { return vec![] };
+        let mut plain = data.to_vec();
+        ctr(self, iv, &mut plain);
+        plain
+    }
+
+    /****************************************************************
+    *                                                               *
+    *                            C F B                              *
+    *                                                               *
+    ****************************************************************/
+
+    /// Zaszyfrowanie w trybie CFB (sprzężenie pełnym blokiem szyfrogramu).
+    fn encrypt_cfb(&self, input: &[u8]) -> Vec<u8> {
+        if input.is_empty() { return vec![]; }
+        let bs = self.block_size();
+        let mut cipher = with_iv(bs, input);
+        for pos in (bs..cipher.len()).step_by(bs) {
+            let (previous, data) = cipher.split_at_mut(pos);
+            let mut stream = previous[pos - bs..].to_vec();
+            self.encrypt_block(&mut stream);
+            let n = data.len().min(bs);
+            xor(&mut data[..n], &stream);
+        }
+        cipher
+    }
+
+    fn decrypt_cfb(&self, cipher: &[u8]) -> Vec<u8> {
+        let bs = self.block_size();
+        let Some((_, data)) = split_iv(bs, cipher) else { return vec![] };
+        let mut plain = data.to_vec();
+        plain.chunks_mut(bs)
+            .zip(cipher.chunks(bs))
+            .for_each(|(block, previous)| {
+                let mut stream = previous.to_vec();
+                self.encrypt_block(&mut stream);
+                xor(block, &stream);
+            });
+        plain
+    }
+
+    /****************************************************************
+    *                                                               *
+    *                            O F B                              *
+    *                                                               *
+    ****************************************************************/
+
+    /// Zaszyfrowanie w trybie OFB: strumień klucza to kolejno
+    /// szyfrowany IV, niezależny od danych.
+    fn encrypt_ofb(&self, input: &[u8]) -> Vec<u8> {
+        if input.is_empty() { return vec![]; }
+        let mut cipher = with_iv(self.block_size(), input);
+        let (iv, data) = cipher.split_at_mut(self.block_size());
+        ofb(self, iv, data);
+        cipher
+    }
+
+    fn decrypt_ofb(&self, cipher: &[u8]) -> Vec<u8> {
+        let Some((iv, data)) = split_iv(self.block_size(), cipher) else { return vec![] };
+        let mut plain = data.to_vec();
+        ofb(self, iv, &mut plain);
+        plain
+    }
+}
+
+/// Niepoprawna długość klucza przy tworzeniu szyfru.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyError {
+    /// Długość podanego klucza.
+    pub size: usize,
+    /// Dopuszczalne długości klucza (od, do).
+    pub expected: (usize, usize),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expected {
+            (min, max) if min == max => write!(f, "invalid key size: {} bytes (expected {})", self.size, min),
+            (min, max) => write!(f, "invalid key size: {} bytes (expected {}..={})", self.size, min, max),
+        }
+    }
+}
+
+impl Error for KeyError {}
+
+impl KeyError {
+    /// Sprawdzenie długości klucza.
+    pub(crate) fn check(key: &[u8], min: usize, max: usize) -> Result<(), KeyError> {
+        match (min..=max).contains(&key.len()) {
+            true => Ok(()),
+            false => Err(KeyError { size: key.len(), expected: (min, max) }),
+        }
+    }
+}
+
+/// Losowy IV (jeden blok), a za nim dane.
+fn with_iv(bs: usize, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; bs + data.len()];
+    iv_fill(&mut out[..bs]);
+    out[bs..].copy_from_slice(data);
+    out
+}
+
+/// IV i dane szyfrogramu trybu strumieniowego.
+fn split_iv(bs: usize, cipher: &[u8]) -> Option<(&[u8], &[u8])> {
+    (cipher.len() > bs).then(|| cipher.split_at(bs))
+}
+
+fn ctr<C: BlockCipher + ?Sized>(cipher: &C, iv: &[u8], data: &mut [u8]) {
+    let mut counter = iv.to_vec();
+    let mut stream = vec![0u8; iv.len()];
+    for block in data.chunks_mut(iv.len()) {
+        stream.copy_from_slice(&counter);
+        cipher.encrypt_block(&mut stream);
+        xor(block, &stream);
+        // Licznik to cały blok, z przeniesieniem między bajtami.
+        for byte in counter.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+    }
+}
+
+fn ofb<C: BlockCipher + ?Sized>(cipher: &C, iv: &[u8], data: &mut [u8]) {
+    let mut stream = iv.to_vec();
+    for block in data.chunks_mut(iv.len()) {
+        cipher.encrypt_block(&mut stream);
+        xor(block, &stream);
+    }
+}
+
+/// `data ^= key` na długości `data` (ostatni blok może być krótszy).
+fn xor(data: &mut [u8], key: &[u8]) {
+    data.iter_mut().zip(key).for_each(|(d, k)| *d ^= k);
+}
+
+fn unpad(mut plain: Vec<u8>) -> Vec<u8> {
+    if let Some(idx) = pad_index(&plain) {
+        plain.truncate(idx);
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::blowfish::Blowfish;
+    use crate::crypto::gost::Gost;
+    use crate::crypto::tool::rnd_bytes;
+    use crate::crypto::way3::Way3;
+    use super::*;
+
+    type Mode = fn(&(dyn BlockCipher + 'static), &[u8]) -> Vec<u8>;
+
+    const TEXT: &str = "Artur, Błażej, Jolanta i Piotr Pszczółkowscy";
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn ciphers() -> Vec<Box<dyn BlockCipher>> {
+        vec![
+            Box::new(Blowfish::new(b"TESTKEY").unwrap()),
+            Box::new(Gost::new(&(0u8..32).collect::<Vec<_>>()).unwrap()),
+            Box::new(Way3::new(&(0u8..12).collect::<Vec<_>>()).unwrap()),
+            Box::new(Xor16(rnd_bytes(16))),
+        ]
+    }
+
+    /// Szyfr do testów z innym rozmiarem bloku niż szyfry z tego modułu.
+    struct Xor16(Vec<u8>);
+
+    impl BlockCipher for Xor16 {
+        fn block_size(&self) -> usize { 16 }
+        fn encrypt_block(&self, block: &mut [u8]) { xor(block, &self.0); block.rotate_left(1); }
+        fn decrypt_block(&self, block: &mut [u8]) { block.rotate_right(1); xor(block, &self.0); }
+    }
+
+    #[test]
+    fn test_modes() {
+        for cipher in ciphers() {
+            let bs = cipher.block_size();
+            for len in [0, 1, bs - 1, bs, bs + 1, 3 * bs, TEXT.len()] {
+                let plain = &TEXT.as_bytes()[..len.min(TEXT.len())];
+                assert_eq!(cipher.decrypt_ecb(&cipher.encrypt_ecb(plain)), plain);
+                assert_eq!(cipher.decrypt_cbc(&cipher.encrypt_cbc(plain)), plain);
+                let modes: [(Mode, Mode); 3] = [
+                    (<dyn BlockCipher>::encrypt_ctr, <dyn BlockCipher>::decrypt_ctr),
+                    (<dyn BlockCipher>::encrypt_cfb, <dyn BlockCipher>::decrypt_cfb),
+                    (<dyn BlockCipher>::encrypt_ofb, <dyn BlockCipher>::decrypt_ofb),
+                ];
+                for (encrypt, decrypt) in modes {
+                    let encrypted = encrypt(cipher.as_ref(), plain);
+                    // Tryby strumieniowe: IV i dane bez paddingu.
+                    assert_eq!(encrypted.len(), if plain.is_empty() { 0 } else { bs + plain.len() });
+                    assert_eq!(decrypt(cipher.as_ref(), &encrypted), plain);
+                }
+            }
+            assert!(cipher.decrypt_cbc(&[0u8; 3]).is_empty());
+            assert!(cipher.decrypt_ctr(&vec![0u8; bs]).is_empty());
+        }
+    }
+
+    /// Szyfrogramy z implementacji sprzed wspólnego traitu (wire format).
+    #[test]
+    fn test_compatibility() {
+        let ciphers = ciphers();
+        let vectors = [
+            ("9ad59d5adbeabc518081408f00a7a169dcef6ec55cd0789fb105a5e91b94f996f9700c64c42d10923e46e4567f152921",
+             "13221898154c18f60f3bc6f7cc398861c61759cac357bbfb148a14799d0290eaaa35a0c0fea050e8d47f888f0e5841b4bb56ce4662d5fe6e"),
+            ("4b3db63c055e9f16451886dc1d2df03d24a42bb8696bfa3e179f3483df53334d1245e100346228843de637500ac98a71",
+             "fb31dd38e28fba955eab84885da0f3666fd988c0cff1f4e0230241a91f34e5da88cdc65fef7d0324ef39906a10736c303244b9cfb02c1757"),
+            ("91724c177bf921026a32d4e0ee418bdfd1655c92983aeba6f809afb5fdbe42aeab33053768e38b6280b11aa28e3c5442",
+             "7b5f891fbbd690d076315d7fa10b36a7dee4022be47cd18ab652e6c2bbaa315e8d6ec690f5eeb412b7e51f96f7ddf1ac6626dd7bf7c85e0be0075363"),
+        ];
+        for (cipher, (ecb, cbc)) in ciphers.iter().zip(vectors) {
+            assert_eq!(cipher.encrypt_ecb(TEXT.as_bytes()), from_hex(ecb));
+            assert_eq!(cipher.decrypt_cbc(&from_hex(cbc)), TEXT.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_ctr_counter() {
+        // Przeniesienie licznika: IV ff..ff daje kolejny blok z licznikiem 00..00.
+        let cipher = Xor16(vec![0u8; 16]);
+        let mut data = vec![0u8; 32];
+        ctr(&cipher, &[0xff; 16], &mut data);
+        let mut second = [0u8; 16];
+        cipher.encrypt_block(&mut second);
+        assert_eq!(&data[16..], &second);
+    }
+
+    #[test]
+    fn test_key_error() {
+        let err = Blowfish::new(b"abc").err().unwrap();
+        assert_eq!(err, KeyError { size: 3, expected: (4, 56) });
+        assert_eq!(err.to_string(), "invalid key size: 3 bytes (expected 4..=56)");
+        assert_eq!(Gost::new(b"short").err().unwrap().to_string(), "invalid key size: 5 bytes (expected 32)");
+    }
+}
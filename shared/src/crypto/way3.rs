@@ -23,7 +23,8 @@
 // https://github.com/stamparm/cryptospecs/blob/master/symmetrical/sources/3-way.c
 // http://www.users.zetnet.co.uk/hopwood/crypto/scan/cs.html#3-Way
 
-use crate::crypto::tool::*;
+use crate::crypto::mode::{BlockCipher, KeyError};
+use crate::crypto::tool::{block3_to_bytes, bytes_to_block3};
 
 const NMBR: usize = 11;             // Liczba rund
 const BLOCK_SIZE: usize = 12;       // 3xu32: 12 bajtów
@@ -37,16 +38,14 @@ pub struct Way3 {
 }
 
 impl Way3 {
-    pub fn new_with_key_block(block: (u32, u32, u32)) -> Result<Self, &'static str> {
+    pub fn new_with_key_block(block: (u32, u32, u32)) -> Result<Self, KeyError> {
         let mut key = vec![0u8; KEY_SIZE];
         block3_to_bytes(block, &mut key);
         Self::new(key.as_slice())
     }
 
-    pub fn new(key: &[u8]) -> Result<Self, &'static str> {
-        if key.len() != KEY_SIZE {
-            return Err("invalid key size");
-        }
+    pub fn new(key: &[u8]) -> Result<Self, KeyError> {
+        KeyError::check(key, KEY_SIZE, KEY_SIZE)?;
 
         let k: [u32; 3] = [
             u32::from_be_bytes(key[0..4].try_into().unwrap()),
@@ -69,7 +68,7 @@ impl Way3 {
     *                                                               *
     ****************************************************************/
 
-    pub fn encrypt_block(&self, src: (u32,u32,u32)) -> (u32,u32,u32) {
+    pub fn encrypt(&self, src: (u32,u32,u32)) -> (u32,u32,u32) {
         let mut a = [src.0, src.1, src.2];
 
         ERCON[..NMBR]
@@ -88,7 +87,7 @@ impl Way3 {
         (a[0], a[1], a[2])
     }
 
-    pub fn decrypt_block(&self, src: (u32,u32,u32)) -> (u32,u32,u32) {
+    pub fn decrypt(&self, src: (u32,u32,u32)) -> (u32,u32,u32) {
         let mut a = [src.0, src.1, src.2];
         Self::mu(&mut a);
 
@@ -108,123 +107,6 @@ impl Way3 {
         (a[0], a[1], a[2])
     }
 
-    /****************************************************************
-    *                                                               *
-    *                            E C B                              *
-    *                                                               *
-    ****************************************************************/
-
-    /// Zaszyfrowanie ciągu bajtów w trybie ECB.
-    /// Wielkość zaszyfrowanego ciągu ma taką samą długość ciągu szyfrowanego.
-    /// Jeśli długość ciągu do zaszyfrowania nie jest wielokrotnością bloku,
-    /// zostanie on uzupełniony paddingiem. 
-    /// UWAGA: ten sam ciąg po zaszyfrowaniu zawsze wygląda tak samo.
-    pub fn encrypt_ecb(&self, input: &[u8]) -> Vec<u8> {
-        if input.is_empty() { return vec![]; }
-
-        let plain = align_to_block(input, BLOCK_SIZE);
-        let nbytes = plain.len();
-        let mut cipher = vec![0u8; nbytes];
-
-        plain.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let plain_block = bytes_to_block3(&plain[i..]);
-                let cipher_block = self.encrypt_block(plain_block);
-                block3_to_bytes(cipher_block, &mut cipher[i..i+BLOCK_SIZE]);                
-            });
-        
-        cipher
-    }
-
-    /// Odszyfrowanie ciągu bajtów w trybie ECB.
-    /// Długość ciągu bajtów musi być wielokrotnością długości bloków.
-    pub fn decrypt_ecb(&self, cipher: &[u8]) -> Vec<u8> {
-        if cipher.is_empty() { return vec![]; }
-
-        let nbytes = cipher.len();
-        let mut plain = vec![0u8; nbytes];
-
-        cipher.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let cipher_block = bytes_to_block3(&cipher[i..]);
-                let plain_block = self.decrypt_block(cipher_block);
-                block3_to_bytes(plain_block, &mut plain[i..i+BLOCK_SIZE]);
-            });
-
-        pad_index(&plain)
-            .map(|idx| plain[..idx].to_vec())
-            .unwrap_or(plain)
-    }
-
-    /****************************************************************
-    *                                                               *
-    *                            C B C                              *
-    *                                                               *
-    ****************************************************************/
-
-    /// Zaszyfrowanie ciągu bajtów w trybie CBC.
-    /// Przy szyfrowaniu używa się losowego IV.
-    /// Oznacza to, że nawet jeśli wiele razy szyfrujemy
-    /// ten sam tekst, po zaszyfrowaniu będzie on zawsze wyglądał inaczej.
-    pub fn encrypt_cbc(&self, input: &[u8]) -> Vec<u8> {
-        if input.is_empty() { return vec![]; }
-        
-        let plain = align_to_block(input, BLOCK_SIZE);
-        let mut cipher = vec![0u8; BLOCK_SIZE + plain.len()];
-        iv_fill(&mut cipher[0..BLOCK_SIZE]);
-        
-        let mut cipher_block = bytes_to_block3(&cipher);
-        plain.iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let plain_block = bytes_to_block3(&plain[i..]);
-                let w0 = plain_block.0 ^ cipher_block.0;
-                let w1 = plain_block.1 ^ cipher_block.1;
-                let w2 = plain_block.2 ^ cipher_block.2;
-                cipher_block = self.encrypt_block((w0, w1, w2));
-                let pos = i + BLOCK_SIZE;
-                block3_to_bytes(cipher_block, &mut cipher[pos..pos+BLOCK_SIZE]);
-            });
-        
-        cipher
-    }
-
-    /// Odszyfrowanie ciągu bajtów w trybie CBC.
-    /// Długość ciągu bajtów musi być wielokrotnością długości bloku
-    /// i musi zawierać co najmniej 2 bloki.
-    pub fn decrypt_cbc(&self, cipher: &[u8]) -> Vec<u8> {
-        let nbytes = cipher.len();
-        if nbytes / BLOCK_SIZE < 2 || nbytes % BLOCK_SIZE != 0 {
-            return vec![]; 
-        }
-        let mut plain = vec![0u8; nbytes - BLOCK_SIZE];
-        
-        let mut ptv_cipher_block = bytes_to_block3(cipher);
-        cipher[BLOCK_SIZE..].iter()
-            .enumerate()
-            .step_by(BLOCK_SIZE)
-            .for_each(|(i, _)| {
-                let cipher_block = bytes_to_block3(&cipher[i+BLOCK_SIZE..]);
-                let tmp = cipher_block;
-                let plain_block = self.decrypt_block(cipher_block);
-                let w0 = plain_block.0 ^ ptv_cipher_block.0;
-                let w1 = plain_block.1 ^ ptv_cipher_block.1;
-                let w2 = plain_block.2 ^ ptv_cipher_block.2;
-                block3_to_bytes((w0, w1, w2), &mut plain[i..i+BLOCK_SIZE]);
-                
-                ptv_cipher_block = tmp;
-            });
-        
-        pad_index(&plain)
-            .map(|idx| plain[..idx].to_vec())
-            .unwrap_or(plain)
-    }
-
     /********************************************************************
     *                                                                   *
     *                         H E L P E R S                             *
@@ -316,6 +198,20 @@ impl Way3 {
 
 }   // Way3
 
+impl BlockCipher for Way3 {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        block3_to_bytes(self.encrypt(bytes_to_block3(block)), block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        block3_to_bytes(self.decrypt(bytes_to_block3(block)), block);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::crypto::tool::rnd_bytes;
@@ -340,7 +236,7 @@ mod tests {
             let w3 = Way3::new_with_key_block(tt.key);
             assert!(w3.is_ok());
             let w3 = w3.unwrap();
-            let encrypted = w3.encrypt_block(tt.plain);
+            let encrypted = w3.encrypt(tt.plain);
             assert_eq!(encrypted, tt.cipher);
         }
     }
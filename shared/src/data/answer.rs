@@ -22,8 +22,10 @@
 #![allow(dead_code)]
 
 use serde::{Serialize, Deserialize};
+use super::wire::{Reader, Writer};
 use serde_json::Result;
 use std::fmt::Debug;
+use std::io;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -80,6 +82,34 @@ impl Answer {
     pub fn from_json(json: &[u8]) -> Result<Self> {
         serde_json::from_str(String::from_utf8_lossy(json).as_ref())
     }
+
+    /// Zapis w formacie binarnym (patrz `wire`).
+    /// Zawartość pliku jest zapisywana bez zmian.
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut w = Writer::with_capacity(64 + self.message.len() + self.binary.len());
+        w.u64(self.id);
+        w.u64(self.timestamp);
+        w.i32(self.code);
+        w.str(&self.message);
+        w.str(&self.cmd);
+        w.strings(&self.data);
+        w.bytes(&self.binary);
+        w.finish()
+    }
+    pub fn from_binary(data: &[u8]) -> io::Result<Self> {
+        let mut r = Reader::new(data);
+        let answer = Self {
+            id: r.u64()?,
+            timestamp: r.u64()?,
+            code: r.i32()?,
+            message: r.str()?,
+            cmd: r.str()?,
+            data: r.strings()?,
+            binary: r.bytes()?,
+        };
+        r.finish()?;
+        Ok(answer)
+    }
 }
 
 impl Debug for Answer {
@@ -20,24 +20,162 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use std::error;
+use std::fmt::{self, Display};
 use std::io;
 use std::io::{Read, Write};
-use std::net::*;
 use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Bit długości ramki oznaczający, że dane są skompresowane.
+/// Długość ramki nigdy nie zbliża się do 2 GiB, więc najstarszy bit
+/// jest wolny także w starszych wersjach protokołu.
+pub const COMPRESSED: u32 = 1 << 31;
+
+/// Domyślny największy rozmiar ramki (i wiadomości po dekompresji).
+pub const MAX_FRAME: usize = 256 * 1024 * 1024;
+/// Największy rozmiar ramki przed zakończeniem handshake - ramki
+/// handshake mają po kilkaset bajtów, a druga strona nie jest jeszcze znana.
+pub const MAX_HANDSHAKE_FRAME: usize = 4096;
+
+/// Błąd: ramka większa niż dopuszczalna.
+/// Zgłaszany jako `io::Error` rodzaju `InvalidData`,
+/// można go z niego wydobyć przez `FrameTooLarge::from_io`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTooLarge {
+    pub size: usize,
+    pub limit: usize,
+}
+
+impl FrameTooLarge {
+    pub fn from_io(err: &io::Error) -> Option<FrameTooLarge> {
+        err.get_ref()?.downcast_ref::<FrameTooLarge>().copied()
+    }
+}
+
+impl Display for FrameTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Frame too large: {} bytes (limit {}).", self.size, self.limit)
+    }
+}
+
+impl error::Error for FrameTooLarge {}
+
+impl From<FrameTooLarge> for io::Error {
+    fn from(err: FrameTooLarge) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
 
 pub struct Message;
 
 impl Message {
-    pub fn write(conn: &mut TcpStream, buffer: &[u8]) -> io::Result<()> {
-        conn.write_u32::<NetworkEndian>(buffer.len() as u32)?;
-        conn.write_all(buffer)?;
-        Ok(())
+    /// Zapis ramki: długość (u32) i dane.
+    /// Całą ramkę wysyłamy jednym zapisem - osobny zapis samej długości
+    /// (algorytm Nagle'a + opóźnione ACK) wstrzymywał każdą ramkę o ~40 ms.
+    pub fn write<W: Write>(conn: &mut W, buffer: &[u8]) -> io::Result<()> {
+        Message::write_with_flags(conn, buffer, 0)
+    }
+
+    /// Zapis ramki z flagami (np. `COMPRESSED`) w najstarszych bitach długości.
+    pub fn write_with_flags<W: Write>(conn: &mut W, buffer: &[u8], flags: u32) -> io::Result<()> {
+        if buffer.len() >= COMPRESSED as usize {
+            return Err(FrameTooLarge { size: buffer.len(), limit: COMPRESSED as usize - 1 }.into());
+        }
+        let mut frame = Vec::with_capacity(size_of::<u32>() + buffer.len());
+        frame.write_u32::<NetworkEndian>(buffer.len() as u32 | flags)?;
+        frame.extend_from_slice(buffer);
+        conn.write_all(&frame)
     }
     
-    pub fn read(conn: &mut TcpStream) -> io::Result<Vec<u8>> {
-        let message_length = conn.read_u32::<NetworkEndian>()? as usize;
-        let mut buffer = vec![0; message_length];
-        conn.read_exact(&mut buffer)?;
-        Ok(buffer)
+    /// Odczyt ramki nie większej niż `limit` bajtów.
+    pub fn read<R: Read>(conn: &mut R, limit: usize) -> io::Result<Vec<u8>> {
+        match Message::read_with_flags(conn, limit)? {
+            (buffer, 0) => Ok(buffer),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected frame flags")),
+        }
+    }
+
+    /// Odczyt ramki razem z flagami z nagłówka.
+    /// Długość z nagłówka sprawdzamy przed rezerwacją pamięci, a bufor
+    /// rośnie w miarę napływu danych - sama deklaracja długości
+    /// nie zajmuje pamięci.
+    pub fn read_with_flags<R: Read>(conn: &mut R, limit: usize) -> io::Result<(Vec<u8>, u32)> {
+        let header = conn.read_u32::<NetworkEndian>()?;
+        let size = (header & !COMPRESSED) as usize;
+        if size > limit {
+            return Err(FrameTooLarge { size, limit }.into());
+        }
+        let mut buffer = Vec::with_capacity(size.min(64 * 1024));
+        conn.take(size as u64).read_to_end(&mut buffer)?;
+        if buffer.len() < size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame"));
+        }
+        Ok((buffer, header & COMPRESSED))
+    }
+}
+
+/// Te same ramki dla połączeń asynchronicznych (tokio).
+#[cfg(feature = "async")]
+impl Message {
+    pub async fn write_async<W: AsyncWrite + Unpin>(conn: &mut W, buffer: &[u8], flags: u32) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+        if buffer.len() >= COMPRESSED as usize {
+            return Err(FrameTooLarge { size: buffer.len(), limit: COMPRESSED as usize - 1 }.into());
+        }
+        let mut frame = Vec::with_capacity(size_of::<u32>() + buffer.len());
+        frame.extend_from_slice(&(buffer.len() as u32 | flags).to_be_bytes());
+        frame.extend_from_slice(buffer);
+        conn.write_all(&frame).await?;
+        conn.flush().await
+    }
+
+    pub async fn read_async<R: AsyncRead + Unpin>(conn: &mut R, limit: usize) -> io::Result<(Vec<u8>, u32)> {
+        use tokio::io::AsyncReadExt;
+        let header = conn.read_u32().await?;
+        let size = (header & !COMPRESSED) as usize;
+        if size > limit {
+            return Err(FrameTooLarge { size, limit }.into());
+        }
+        let mut buffer = Vec::with_capacity(size.min(64 * 1024));
+        conn.take(size as u64).read_to_end(&mut buffer).await?;
+        if buffer.len() < size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame"));
+        }
+        Ok((buffer, header & COMPRESSED))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        let mut data = Vec::new();
+        Message::write(&mut data, b"abc").unwrap();
+        Message::write_with_flags(&mut data, b"xyz", COMPRESSED).unwrap();
+        let mut input = data.as_slice();
+        assert_eq!(Message::read(&mut input, 3).unwrap(), b"abc");
+        assert_eq!(Message::read_with_flags(&mut input, 3).unwrap(), (b"xyz".to_vec(), COMPRESSED));
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn test_limits() {
+        // Deklarowane prawie 2 GiB - błąd, zanim cokolwiek zostanie zarezerwowane.
+        let mut input = &[0x7f, 0xff, 0xff, 0xff, 1, 2, 3][..];
+        let err = Message::read(&mut input, MAX_HANDSHAKE_FRAME).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(FrameTooLarge::from_io(&err), Some(FrameTooLarge { size: 0x7fff_ffff, limit: MAX_HANDSHAKE_FRAME }));
+
+        let mut input = &[0, 0, 0, 5, 1, 2][..];
+        let err = Message::read(&mut input, 10).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(FrameTooLarge::from_io(&err), None);
+
+        let mut input = &[0x80, 0, 0, 1, 7][..];
+        assert_eq!(Message::read(&mut input, 10).unwrap_err().kind(), io::ErrorKind::InvalidData);
     }
 }
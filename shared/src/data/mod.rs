@@ -1,3 +1,5 @@
 pub mod message;
 pub mod request;
 pub mod answer;
+pub mod table;
+pub mod wire;
@@ -22,8 +22,10 @@
 #![allow(dead_code)]
 
 use serde::{Serialize, Deserialize};
+use super::wire::{Reader, Writer};
 use serde_json::Result;
 use std::fmt::Debug;
+use std::io;
 use std::time::{ SystemTime, UNIX_EPOCH };
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -31,7 +33,10 @@ pub struct Request {
     id: u64,
     timestamp: u64,
     pub command: String,
-    pub params: Vec<String>
+    pub params: Vec<String>,
+    /// Zawartość pliku wysyłanego na serwer (`put`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binary: Vec<u8>,
 }
 
 impl Request {
@@ -40,7 +45,8 @@ impl Request {
             id: 0,
             timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
             command,
-            params
+            params,
+            binary: Vec::new(),
         }
     }
     pub fn set_id(&mut self, id: u64) {
@@ -59,6 +65,36 @@ impl Request {
     pub fn from_json(json: &[u8]) -> Result<Self> {
         serde_json::from_str(String::from_utf8_lossy(json).as_ref())
     }
+
+    /// Zapis w formacie binarnym (patrz `wire`).
+    /// Zawartość pliku dopisujemy tylko wtedy, gdy jest, więc pozostałe
+    /// żądania mają taki sam format jak w starszych wersjach.
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut w = Writer::with_capacity(64 + self.command.len() + self.binary.len());
+        w.u64(self.id);
+        w.u64(self.timestamp);
+        w.str(&self.command);
+        w.strings(&self.params);
+        if !self.binary.is_empty() {
+            w.bytes(&self.binary);
+        }
+        w.finish()
+    }
+    pub fn from_binary(data: &[u8]) -> io::Result<Self> {
+        let mut r = Reader::new(data);
+        let request = Self {
+            id: r.u64()?,
+            timestamp: r.u64()?,
+            command: r.str()?,
+            params: r.strings()?,
+            binary: match r.has_more() {
+                true => r.bytes()?,
+                false => Vec::new(),
+            },
+        };
+        r.finish()?;
+        Ok(request)
+    }
 }
 
 impl Debug for Request {
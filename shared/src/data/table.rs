@@ -0,0 +1,129 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::fmt::{self, Display};
+use serde::{Serialize, Deserialize};
+use crate::sqlite::Value;
+
+/// Wynik polecenia `sql`: kolumny i wiersze z wartościami w kolejności kolumn.
+/// Przesyłany jako JSON w `Answer::data`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// Liczba wierszy zmienionych przez polecenie (dla INSERT/UPDATE/DELETE).
+    pub changes: usize,
+    /// Wynik został obcięty do maksymalnej liczby wierszy.
+    pub truncated: bool,
+}
+
+impl Table {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    fn cell(value: &Value) -> String {
+        match value {
+            Value::Null => "NULL".to_string(),
+            Value::Integer(v) => v.to_string(),
+            Value::Real(v) => v.to_string(),
+            Value::Text(v) => v.replace('\n', "\\n"),
+            Value::Blob(v) => format!("<{} bytes>", v.len()),
+        }
+    }
+}
+
+/// Tabela wyrównana do kolumn, liczby do prawej, pozostałe wartości do lewej.
+impl Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.columns.is_empty() {
+            return write!(f, "{} row(s) changed", self.changes);
+        }
+        let cells = self.rows.iter()
+            .map(|row| row.iter().map(Self::cell).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let widths = self.columns.iter()
+            .enumerate()
+            .map(|(i, name)| {
+                cells.iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.chars().count())
+                    .fold(name.chars().count(), usize::max)
+            })
+            .collect::<Vec<_>>();
+
+        let header = self.columns.iter()
+            .zip(&widths)
+            .map(|(name, &width)| format!("{:<width$}", name))
+            .collect::<Vec<_>>();
+        writeln!(f, "{}", header.join(" | ").trim_end())?;
+        let line = widths.iter().map(|&width| "-".repeat(width)).collect::<Vec<_>>();
+        write!(f, "{}", line.join("-+-"))?;
+
+        for (row, cells) in self.rows.iter().zip(&cells) {
+            let line = row.iter()
+                .zip(cells)
+                .zip(&widths)
+                .map(|((value, cell), &width)| match value {
+                    Value::Integer(_) | Value::Real(_) => format!("{:>width$}", cell),
+                    _ => format!("{:<width$}", cell),
+                })
+                .collect::<Vec<_>>();
+            write!(f, "\n{}", line.join(" | ").trim_end())?;
+        }
+        write!(f, "\n({} row{}{})",
+               self.rows.len(),
+               if self.rows.len() == 1 { "" } else { "s" },
+               if self.truncated { ", truncated" } else { "" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let table = Table {
+            columns: vec!["id".into(), "name".into(), "data".into()],
+            rows: vec![
+                vec![1.into(), "Piotr".into(), Value::Null],
+                vec![1000.into(), "Anna".into(), vec![1u8, 2, 3].into()],
+            ],
+            ..Default::default()
+        };
+        let json = table.to_json().unwrap();
+        let text = Table::from_json(&json).unwrap().to_string();
+        assert_eq!(text, "\
+id   | name  | data
+-----+-------+----------
+   1 | Piotr | NULL
+1000 | Anna  | <3 bytes>
+(2 rows)");
+
+        let changed = Table { changes: 3, ..Default::default() };
+        assert_eq!(changed.to_string(), "3 row(s) changed");
+    }
+}
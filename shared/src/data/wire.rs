@@ -0,0 +1,233 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Kodowanie żądań i odpowiedzi przesyłanych w ramkach.
+//!
+//! Obok JSON (format pierwotny, zawsze dostępny) jest zwarty format binarny:
+//! kolejne pola zapisane big-endian, teksty i bajty poprzedzone
+//! długością u32, listy poprzedzone liczbą elementów u32.
+//! Zawartość pliku (`Answer::binary`, `Request::binary`) trafia do ramki
+//! bez zmian, a nie jako tablica liczb JSON. W żądaniu jest opcjonalnym
+//! polem na końcu, zapisywanym tylko dla `put`.
+//!
+//! Format binarny kończy się bajtem TRAILER. Szyfrowanie CBC usuwa
+//! z odszyfrowanych danych końcowe 0x80 0x00..., jeśli wyglądają na
+//! wypełnienie bloku, więc dane nie mogą się tak kończyć.
+
+use std::io::{self, Error, ErrorKind};
+use super::{answer::Answer, request::Request};
+
+/// Ostatni bajt każdej ramki w formacie binarnym.
+pub(crate) const TRAILER: u8 = 0xb1;
+const U32_SIZE: usize = size_of::<u32>();
+
+/// Sposób kodowania żądań i odpowiedzi, wynikający z protokołu
+/// uzgodnionego w `Connector::init` (patrz `net::protocol`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    Binary,
+}
+
+impl Encoding {
+    /// Wszystkie obsługiwane kodowania.
+    pub const ALL: [Encoding; 2] = [Encoding::Binary, Encoding::Json];
+
+    pub fn encode_request(self, request: &Request) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Json => Ok(request.to_json()?.into_bytes()),
+            Encoding::Binary => Ok(request.to_binary()),
+        }
+    }
+
+    pub fn decode_request(self, data: &[u8]) -> io::Result<Request> {
+        match self {
+            Encoding::Json => Ok(Request::from_json(data)?),
+            Encoding::Binary => Request::from_binary(data),
+        }
+    }
+
+    pub fn encode_answer(self, answer: &Answer) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Json => Ok(answer.to_json()?.into_bytes()),
+            Encoding::Binary => Ok(answer.to_binary()),
+        }
+    }
+
+    pub fn decode_answer(self, data: &[u8]) -> io::Result<Answer> {
+        match self {
+            Encoding::Json => Ok(Answer::from_json(data)?),
+            Encoding::Binary => Answer::from_binary(data),
+        }
+    }
+}
+
+/// Zapis pól w formacie binarnym.
+pub(crate) struct Writer {
+    buffer: Vec<u8>,
+}
+
+impl Writer {
+    pub fn with_capacity(n: usize) -> Self {
+        Writer { buffer: Vec::with_capacity(n + 1) }
+    }
+    pub fn u32(&mut self, v: u32) {
+        self.buffer.extend_from_slice(&v.to_be_bytes());
+    }
+    pub fn u64(&mut self, v: u64) {
+        self.buffer.extend_from_slice(&v.to_be_bytes());
+    }
+    pub fn i32(&mut self, v: i32) {
+        self.buffer.extend_from_slice(&v.to_be_bytes());
+    }
+    pub fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.buffer.extend_from_slice(v);
+    }
+    pub fn str(&mut self, v: &str) {
+        self.bytes(v.as_bytes());
+    }
+    pub fn strings(&mut self, v: &[String]) {
+        self.u32(v.len() as u32);
+        v.iter().for_each(|s| self.str(s));
+    }
+    pub fn finish(mut self) -> Vec<u8> {
+        self.buffer.push(TRAILER);
+        self.buffer
+    }
+}
+
+/// Odczyt pól w formacie binarnym. Każdy błąd danych to `InvalidData`.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if n > self.data.len() - self.pos {
+            return Err(invalid("truncated binary frame"));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+    pub fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(U32_SIZE)?.try_into().unwrap()))
+    }
+    pub fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take(size_of::<u64>())?.try_into().unwrap()))
+    }
+    pub fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_be_bytes(self.take(size_of::<i32>())?.try_into().unwrap()))
+    }
+    pub fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let n = self.u32()? as usize;
+        Ok(self.take(n)?.to_vec())
+    }
+    pub fn str(&mut self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?).map_err(|_| invalid("text field is not valid UTF-8"))
+    }
+    pub fn strings(&mut self) -> io::Result<Vec<String>> {
+        let n = self.u32()? as usize;
+        // Każdy tekst to co najmniej jego długość - nie rezerwujemy
+        // pamięci na więcej elementów, niż może się zmieścić w danych.
+        if n > (self.data.len() - self.pos) / U32_SIZE {
+            return Err(invalid("truncated binary frame"));
+        }
+        (0..n).map(|_| self.str()).collect()
+    }
+    /// Czy przed znacznikiem końca są jeszcze pola (opcjonalne pola na końcu).
+    pub fn has_more(&self) -> bool {
+        self.data.len() - self.pos > 1
+    }
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.take(1)? != [TRAILER] || self.pos != self.data.len() {
+            return Err(invalid("invalid end of binary frame"));
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut request = Request::new("ls".into(), vec!["-l".into(), "zażółć".into()]);
+        request.set_id(7);
+        let mut answer = Answer::new_with_data(0, "OK", "upload", vec!["a.bin".into()]);
+        answer.set_id(8);
+        // Zawartość kończąca się jak wypełnienie bloku.
+        answer.binary = vec![1, 2, 3, 0x80, 0, 0, 0, 0];
+
+        for encoding in Encoding::ALL {
+            let data = encoding.encode_request(&request).unwrap();
+            let decoded = encoding.decode_request(&data).unwrap();
+            assert_eq!((decoded.id(), decoded.command.as_str()), (7, "ls"));
+            assert_eq!(decoded.params, request.params);
+            assert!(decoded.binary.is_empty());
+
+            let data = encoding.encode_answer(&answer).unwrap();
+            let decoded = encoding.decode_answer(&data).unwrap();
+            assert_eq!((decoded.id(), decoded.code, decoded.cmd.as_str()), (8, 0, "upload"));
+            assert_eq!(decoded.data, answer.data);
+            assert_eq!(decoded.binary, answer.binary);
+        }
+        // Żądanie bez pliku ma format sprzed pola `binary`.
+        let data = Encoding::Binary.encode_request(&request).unwrap();
+        request.binary = vec![0, 1, 2, TRAILER];
+        let with_file = Encoding::Binary.encode_request(&request).unwrap();
+        assert_eq!(with_file.len(), data.len() + U32_SIZE + 4);
+        for encoding in Encoding::ALL {
+            let data = encoding.encode_request(&request).unwrap();
+            assert_eq!(encoding.decode_request(&data).unwrap().binary, request.binary);
+        }
+
+        // Format binarny nie rozdmuchuje zawartości pliku.
+        answer.binary = vec![0xff; 1000];
+        assert!(Encoding::Binary.encode_answer(&answer).unwrap().len() < 1100);
+        assert!(Encoding::Json.encode_answer(&answer).unwrap().len() > 3000);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let answer = Answer::new_with_data(0, "OK", "ls", vec!["x".into()]);
+        let data = answer.to_binary();
+        for n in 0..data.len() {
+            assert!(Answer::from_binary(&data[..n]).is_err());
+        }
+        let mut longer = data.clone();
+        longer.push(0);
+        assert!(Answer::from_binary(&longer).is_err());
+        assert!(Request::from_binary(&[0xff; 40]).is_err());
+    }
+}
@@ -21,14 +21,45 @@
 // SOFTWARE.
 
 use std::{ env, process::Command };
-use crate::data::{answer::Answer, request::Request };
+use crate::data::{answer::Answer, request::Request, table::Table };
+use crate::sqlite::Connection;
 use crate::ufs::dir::Dir;
 use crate::ufs::file::File;
 use crate::ufs::fileinfo::FileInfo;
 use crate::xerror::{Result, Error };
 
+/// Polecenie wykonania zapytania SQL na bazie SQLite.
+pub const SQL_CMD: &str = "sql";
+/// Opcja polecenia `sql` zezwalająca na zmiany w bazie.
+pub const SQL_WRITE: &str = "--write";
+/// Maksymalna liczba wierszy zwracanych przez polecenie `sql`.
+const SQL_MAX_ROWS: usize = 10_000;
+/// Czas (w milisekundach) oczekiwania na blokadę bazy.
+const SQL_BUSY_TIMEOUT: i32 = 2000;
+
 pub struct Executor;
 
+/// Parametry polecenia `sql`: `[--write] <db-path> <query>`.
+/// Serwer sprawdza `write` z tej samej analizy, którą wykonuje polecenie.
+pub struct SqlParams<'a> {
+    pub write: bool,
+    pub path: &'a str,
+    pub query: String,
+}
+
+impl<'a> SqlParams<'a> {
+    pub fn parse(params: &'a [String]) -> Result<SqlParams<'a>> {
+        let (write, params) = match params.first().map(String::as_str) {
+            Some(SQL_WRITE) => (true, &params[1..]),
+            _ => (false, params),
+        };
+        if params.len() < 2 {
+            return Err(Error::new(-1, "usage: sql [--write] <db-path> <query>"));
+        }
+        Ok(SqlParams { write, path: &params[0], query: params[1..].join(" ") })
+    }
+}
+
 impl Executor {
     pub fn execute(request: Request) -> Result<Answer> {
         match request.command.as_str() {
@@ -38,11 +69,12 @@ impl Executor {
             // Polecenie cd obsługujemy osobno, aby obsłużyć cd bez parametrów.
             "cd" => Self::cd(request.params.as_slice()),
             // Polecenie wysłania pliku
-            "put" => Self::download(request.params.as_slice()),
+            "put" => Self::download(request.params.as_slice(), &request.binary),
             // Polecenie pobrania pliku.
             "get" => Self::upload(request.params.as_slice()),
             // Własne pomysły
             "stat" => Self::stat(request.params.as_slice()),
+            SQL_CMD => Self::sql(request.params.as_slice()),
             // Reszta standardowo.
             _ => Self::execute_command(request.command.as_str(), request.params.as_slice())
         }
@@ -66,7 +98,6 @@ impl Executor {
 
     /// Odczyt zawartości katalogu, ze wskazaniem czy uwzględniać pliki ukryte.
     fn readdir(params: &[String], hidden_too: bool) -> Result<Vec<String>> {
-        eprintln!("readdir: {:?}", params);
         let dir = match params.is_empty() {
             // Jeśli nie podano katalogu (brak parametru) to czytamy aktualny katalog.
             true => ".".to_string(),
@@ -136,18 +167,110 @@ impl Executor {
         }
     }
     
-    fn download(params: &[String]) -> Result<Answer> {
-        Ok(Answer::new(0, "OK", "download"))
+    /// Zapis pliku przysłanego przez klienta (`put <plik>`, zawartość w `binary`).
+    /// Plik należy do użytkownika procesu, który wykonuje polecenie;
+    /// istniejącego pliku nie nadpisujemy.
+    fn download(params: &[String], data: &[u8]) -> Result<Answer> {
+        let Some(name) = params.first() else {
+            return Err(Error::new(-1, "usage: put <local-file> [remote-path]"));
+        };
+        let mut fh = File::new(name.as_str());
+        fh.create()?;
+        fh.write(data)?;
+        fh.close()?;
+        Ok(Answer::new_with_data(0, "OK", "download", vec![name.clone()]))
     }
     
     fn upload(params: &[String]) -> Result<Answer> {
         let name = params[0].clone();
-        eprintln!("upload: {:?}", params);
         
-        let fh = File::new(name.as_str());
+        let mut fh = File::new(name.as_str());
+        fh.open_read_only()?;
         let data = fh.read_all_vec()?;
         let mut answer = Answer::new_with_data(0, "OK", "upload", params.to_vec());
         answer.binary = data;
         Ok(answer)
     }
+
+    /// Wykonanie jednego polecenia SQL na istniejącej bazie SQLite.
+    /// Parametry: [--write] <baza> <polecenie...>
+    /// Bez opcji --write baza jest otwierana tylko do odczytu,
+    /// a polecenia zmieniające bazę są odrzucane.
+    fn sql(params: &[String]) -> Result<Answer> {
+        let SqlParams { write, path, query } = SqlParams::parse(params)?;
+        let conn = match write {
+            true => Connection::open_read_write(path)?,
+            false => Connection::open_read_only(path)?,
+        };
+        conn.busy_timeout(SQL_BUSY_TIMEOUT)?;
+
+        let mut stmt = conn.prepare(&query)?;
+        if !write && !stmt.is_read_only() {
+            return Err(Error::new(-1, "statement modifies the database, use: sql --write"));
+        }
+        let mut table = Table { columns: stmt.column_names(), ..Default::default() };
+        if table.columns.is_empty() {
+            table.changes = stmt.execute()?;
+        } else {
+            while stmt.step()? {
+                if table.rows.len() == SQL_MAX_ROWS {
+                    table.truncated = true;
+                    break;
+                }
+                table.rows.push((0..table.columns.len()).map(|i| stmt.column_value(i)).collect());
+            }
+        }
+        Ok(Answer::new_with_data(0, "OK", SQL_CMD, vec![table.to_json()?]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let path = env::temp_dir().join(format!("executor-test-{}.txt", std::process::id()));
+        std::fs::write(&path, "Piotr Pszczółkowski").unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let answer = Executor::execute(Request::new("get".into(), vec![path.clone()])).unwrap();
+        assert_eq!(answer.cmd, "upload");
+        assert_eq!(answer.binary, "Piotr Pszczółkowski".as_bytes());
+        std::fs::remove_file(&path).unwrap();
+        assert!(Executor::execute(Request::new("get".into(), vec![path])).is_err());
+    }
+
+    #[test]
+    fn test_put() {
+        let path = env::temp_dir().join(format!("executor-put-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut request = Request::new("put".into(), vec![path.clone()]);
+        request.binary = "Piotr Pszczółkowski".as_bytes().to_vec();
+
+        let answer = Executor::execute(request.clone()).unwrap();
+        assert_eq!((answer.cmd.as_str(), answer.data[0].as_str()), ("download", path.as_str()));
+        assert_eq!(std::fs::read(&path).unwrap(), request.binary);
+        // Istniejący plik zostaje bez zmian.
+        assert!(Executor::execute(request).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(Executor::execute(Request::new("put".into(), vec![])).is_err());
+    }
+
+    #[test]
+    fn test_sql_params() {
+        let params: Vec<String> = ["--write", "db.sqlite", "DELETE", "FROM t"].map(String::from).to_vec();
+        let sql = SqlParams::parse(&params).unwrap();
+        assert!(sql.write);
+        assert_eq!(sql.path, "db.sqlite");
+        assert_eq!(sql.query, "DELETE FROM t");
+
+        // Opcja tylko na początku; dalej to już ścieżka albo zapytanie.
+        let params: Vec<String> = ["db.sqlite", "--write", "x"].map(String::from).to_vec();
+        let sql = SqlParams::parse(&params).unwrap();
+        assert!(!sql.write);
+        assert_eq!(sql.path, "db.sqlite");
+
+        assert!(SqlParams::parse(&["--write".to_string(), "db.sqlite".to_string()]).is_err());
+    }
 }
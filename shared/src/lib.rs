@@ -7,6 +7,7 @@ pub mod crypto;
 pub mod net;
 pub mod ufs;
 pub mod xerror;
+pub mod compress;
 pub mod executor;
 // #[macro_export]
 // macro_rules! fpos {
@@ -0,0 +1,280 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::io::{self, Error, ErrorKind};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader};
+use crate::crypto::blowfish::Blowfish;
+use crate::crypto::BlockCipher;
+use crate::data::{message::{Message, MAX_FRAME, MAX_HANDSHAKE_FRAME}, request::Request, answer::Answer, wire::Encoding};
+use crate::net::connector::{self, ConnectionSide, Keys, Sequence, REJECT_CMD, SHUTDOWN_CMD, SHUTDOWN_MESSAGE, VERSION_CMD};
+use crate::net::protocol::{Hello, Protocol, CAPABILITIES, CAP_CHANNELS, CAP_PIPELINING};
+
+/// Asynchroniczny odpowiednik `Connector` (tokio): ten sam handshake,
+/// te same ramki i ta sama numeracja żądań, ale po `AsyncRead`/`AsyncWrite`.
+/// Bezczynna sesja nie zajmuje wątku, więc serwer może utrzymywać
+/// tysiące połączeń.
+/// Kanały (`CAP_CHANNELS`) nie są obsługiwane - nie ma ich w ofercie.
+pub struct AsyncConnector<S> {
+    conn: BufReader<S>,
+    side: ConnectionSide,
+    blowfish: Blowfish,
+    keys: Option<Keys>,
+    seq: Sequence,
+    hello: Hello,
+    protocol: Protocol,
+    max_frame: usize,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncConnector<S> {
+    pub fn new(conn: S, side: ConnectionSide) -> Self {
+        AsyncConnector {
+            conn: BufReader::new(conn),
+            side,
+            blowfish: connector::frame_cipher(),
+            keys: None,
+            seq: Sequence::default(),
+            hello: Hello { capabilities: CAPABILITIES & !CAP_CHANNELS, ..Hello::default() },
+            protocol: Protocol::LEGACY,
+            max_frame: MAX_FRAME,
+        }
+    }
+
+    /// Zmiana oferowanych wersji protokołu i możliwości (przed `init`).
+    /// `CAP_CHANNELS` jest zawsze usuwane z oferty.
+    pub fn set_hello(&mut self, hello: Hello) {
+        self.hello = Hello { capabilities: hello.capabilities & !CAP_CHANNELS, ..hello };
+    }
+
+    pub fn set_max_frame(&mut self, max_frame: usize) {
+        self.max_frame = max_frame;
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.protocol.encoding()
+    }
+
+    pub fn pending(&self) -> usize {
+        self.seq.pending()
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.conn.get_ref()
+    }
+
+    pub async fn init(&mut self) -> io::Result<()> {
+        match self.side {
+            ConnectionSide::Server => self.init_server().await,
+            ConnectionSide::Client => self.init_client().await,
+        }
+    } // fn init
+
+    async fn init_server(&mut self) -> io::Result<()> {
+        let client_id = self.read_plain().await?;
+        let client = connector::parse_client_id(&client_id)?;
+        match self.hello.negotiate(client.as_ref().unwrap_or(&Hello::LEGACY)) {
+            Ok(protocol) => {
+                // Klient w wersji 1 nie oczekuje niczego poza kluczami.
+                let (data, keys) = Keys::generate(client.map(|_| protocol));
+                self.write_plain(&data).await?;
+                self.keys = Some(keys);
+                self.set_protocol(protocol);
+                Ok(())
+            }
+            Err(message) => {
+                self.write_plain(&connector::refusal(VERSION_CMD, &message)?).await?;
+                Err(Error::new(ErrorKind::Unsupported, message))
+            }
+        }
+    } // fn init_server
+
+    async fn init_client(&mut self) -> io::Result<()> {
+        self.write_plain(&connector::client_id(&self.hello)).await?;
+        let (keys, protocol) = Keys::parse(self.read_plain().await?)?;
+        self.set_protocol(self.hello.accept(protocol)?);
+        self.keys = Some(keys);
+        Ok(())
+    } // fn init_client
+
+    fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = protocol;
+        self.seq.pipelined = protocol.has(CAP_PIPELINING);
+    }
+
+    /// Uprzejma odmowa przyjęcia połączenia (patrz `Connector::reject`).
+    pub async fn reject(&mut self, reason: &str) -> io::Result<()> {
+        let _ = self.read_plain().await?;
+        self.write_plain(&connector::refusal(REJECT_CMD, reason)?).await
+    } // fn reject
+
+    //------- Serwer ------------------------------------------------
+
+    /// Oczekiwanie na dane od drugiej strony.
+    /// Można bezpiecznie przerwać (np. w `select!`) - odczytane dane
+    /// zostają w buforze. Zamknięcie połączenia to błąd UnexpectedEof.
+    pub async fn wait_for_data(&mut self) -> io::Result<()> {
+        match self.conn.fill_buf().await?.is_empty() {
+            true => Err(Error::new(ErrorKind::UnexpectedEof, "Connection closed by peer.")),
+            false => Ok(()),
+        }
+    } // fn wait_for_data
+
+    pub async fn send_shutdown_notice(&mut self) -> io::Result<()> {
+        self.send_answer(Answer::new(0, SHUTDOWN_MESSAGE, SHUTDOWN_CMD)).await
+    } // fn send_shutdown_notice
+
+    pub async fn read_request(&mut self) -> io::Result<Request> {
+        let request = self.read_frame().await?;
+        let request = self.encoding().decode_request(&request)?;
+        self.seq.request_read(request.id())?;
+        Ok(request)
+    } // fn read_request
+
+    pub async fn send_answer(&mut self, answer: Answer) -> io::Result<()> {
+        self.send_answer_to(self.seq.last_request(), answer).await
+    } // fn send_answer
+
+    pub async fn send_answer_to(&mut self, request_id: u64, mut answer: Answer) -> io::Result<()> {
+        answer.set_id(self.seq.answer_id(request_id));
+        let data = self.encoding().encode_answer(&answer)?;
+        self.write_frame(&data).await?;
+        self.seq.answer_sent(answer.id());
+        Ok(())
+    } // fn send_answer_to
+
+    //------- Klient ------------------------------------------------
+
+    pub async fn send_request(&mut self, mut request: Request) -> io::Result<u64> {
+        let id = self.seq.request_id();
+        request.set_id(id);
+        let data = self.encoding().encode_request(&request)?;
+        self.write_frame(&data).await?;
+        self.seq.request_sent(id);
+        Ok(id)
+    } // fn send_request
+
+    pub async fn read_answer(&mut self) -> io::Result<Answer> {
+        let answer = self.read_frame().await?;
+        let answer = self.encoding().decode_answer(&answer)?;
+        if answer.cmd == SHUTDOWN_CMD {
+            return Err(Error::new(ErrorKind::ConnectionAborted, answer.message));
+        }
+        self.seq.answer_read(answer.id())?;
+        Ok(answer)
+    } // fn read_answer
+
+    /// Ramki handshake: tylko Blowfish, bez flag.
+    async fn write_plain(&mut self, data: &[u8]) -> io::Result<()> {
+        Message::write_async(&mut self.conn, &self.blowfish.encrypt_cbc(data), 0).await
+    }
+
+    async fn read_plain(&mut self) -> io::Result<Vec<u8>> {
+        match Message::read_async(&mut self.conn, MAX_HANDSHAKE_FRAME).await? {
+            (data, 0) => Ok(self.blowfish.decrypt_cbc(&data)),
+            _ => Err(Error::new(ErrorKind::InvalidData, "unexpected frame flags")),
+        }
+    }
+
+    async fn write_frame(&mut self, data: &[u8]) -> io::Result<()> {
+        let (data, flags) = connector::seal(&self.blowfish, self.protocol, data);
+        Message::write_async(&mut self.conn, &data, flags).await
+    }
+
+    async fn read_frame(&mut self) -> io::Result<Vec<u8>> {
+        let (data, flags) = Message::read_async(&mut self.conn, self.max_frame).await?;
+        connector::unseal(&self.blowfish, self.protocol, &data, flags, self.max_frame)
+    }
+} // AsyncConnector
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+    use crate::net::connector::Connector;
+    use crate::net::protocol::CAP_COMPRESSION;
+    use super::*;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    /// Klient synchroniczny, serwer asynchroniczny.
+    #[test]
+    fn test_async_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut conn = Connector::new(std::net::TcpStream::connect(addr).unwrap(), ConnectionSide::Client);
+            conn.init().unwrap();
+            conn.send_request(Request::new("echo".into(), vec!["x".repeat(4096)])).unwrap();
+            let answer = conn.read_answer().unwrap();
+            assert_eq!(answer.message, "x".repeat(4096));
+            conn.protocol()
+        });
+        let protocol = runtime().block_on(async {
+            listener.set_nonblocking(true).unwrap();
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = AsyncConnector::new(stream, ConnectionSide::Server);
+            conn.init().await.unwrap();
+            conn.wait_for_data().await.unwrap();
+            let request = conn.read_request().await.unwrap();
+            conn.send_answer(Answer::new(0, &request.params[0], "echo")).await.unwrap();
+            conn.protocol()
+        });
+        assert_eq!(client.join().unwrap(), protocol);
+        assert!(protocol.has(CAP_COMPRESSION) && protocol.has(CAP_PIPELINING));
+        assert!(!protocol.has(CAP_CHANNELS));
+    }
+
+    /// Klient asynchroniczny (z kilkoma żądaniami naraz), serwer synchroniczny.
+    #[test]
+    fn test_async_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut conn = Connector::new(listener.accept().unwrap().0, ConnectionSide::Server);
+            conn.init().unwrap();
+            let requests: Vec<Request> = (0..3).map(|_| conn.read_request().unwrap()).collect();
+            for request in requests.iter().rev() {
+                conn.send_answer_to(request.id(), Answer::new(0, &request.params[0], "echo")).unwrap();
+            }
+        });
+        runtime().block_on(async {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let mut conn = AsyncConnector::new(stream, ConnectionSide::Client);
+            conn.init().await.unwrap();
+            for i in 0..3 {
+                conn.send_request(Request::new("echo".into(), vec![i.to_string()])).await.unwrap();
+            }
+            assert_eq!(conn.pending(), 3);
+            for i in (0..3).rev() {
+                assert_eq!(conn.read_answer().await.unwrap().message, i.to_string());
+            }
+            assert_eq!(conn.pending(), 0);
+        });
+        server.join().unwrap();
+    }
+}
@@ -21,12 +21,17 @@
 // SOFTWARE.
 
 
+use std::collections::HashSet;
 use std::io;
 use std::io::{Error, ErrorKind};
-use std::net::TcpStream;
-use crate::crypto::{blowfish, blowfish::Blowfish, gost, gost::Gost, way3, way3::Way3};
+use std::time::Duration;
+use crate::crypto::{blowfish, blowfish::Blowfish, gost, gost::Gost, way3, way3::Way3, BlockCipher};
 use crate::crypto::tool::rnd_bytes;
-use crate::data::{message::Message, request::Request, answer::Answer };
+use crate::compress::lz;
+use crate::data::{message::{Message, COMPRESSED, MAX_FRAME, MAX_HANDSHAKE_FRAME}, request::Request, answer::Answer, wire::Encoding };
+use crate::net::protocol::{Hello, Protocol, CAP_COMPRESSION, CAP_PIPELINING};
+use crate::net::replay::ReplayWindow;
+use crate::net::transport::{Endpoint, Transport};
 
 const BF_KEY: [u8; blowfish::MAX_KEY_SIZE] = [
     0xbe, 0x2f, 0xe0, 0xa8, 0xd9, 0xc9, 0xec, 0x31, 0x06, 0x67,
@@ -51,35 +56,95 @@ const CLIENT_ID: [u8; 128] = [
     0xba, 0x6b, 0x74, 0xd8, 0x13, 0xdc, 0x16, 0x85, 0xd5, 0x4e,
     0x6e, 0x08, 0xf1, 0xa2, 0x4f, 0x94, 0x88, 0xa3];
 
+/// Polecenie w odpowiedzi, którą serwer wysyła bez żądania,
+/// gdy kończy pracę. Klient zgłasza ją jako błąd `ConnectionAborted`.
+pub const SHUTDOWN_CMD: &str = "shutdown";
+pub(crate) const SHUTDOWN_MESSAGE: &str = "Server is shutting down.";
+/// Polecenie w ramce, którą serwer wysyła zamiast kluczy,
+/// gdy odmawia przyjęcia połączenia (np. przekroczony limit sesji).
+/// Klient zgłasza ją jako błąd `ConnectionRefused`.
+pub const REJECT_CMD: &str = "busy";
+/// Polecenie w ramce, którą serwer wysyła zamiast kluczy, gdy nie ma
+/// wersji protokołu wspólnej z klientem. Klient zgłasza ją jako błąd `Unsupported`.
+pub const VERSION_CMD: &str = "version";
+
+/// Mniejszych danych nie kompresujemy - zysk nie pokryje kosztu.
+const MIN_COMPRESS: usize = 512;
+
 pub enum ConnectionSide {
     Server,
     Client
 }
 
 pub struct Connector {
-    conn: TcpStream,
+    conn: Box<dyn Transport>,
     side: ConnectionSide,
     blowfish: Blowfish,
-    gost: Option<Gost>,
-    way3: Option<Way3>,
-    prv_request: Option<Request>,
-    prv_answer: Option<Answer>,
-        
+    keys: Option<Keys>,
+    seq: Sequence,
+    /// Wersje i możliwości, które ta strona oferuje.
+    hello: Hello,
+    /// Protokół uzgodniony w handshake.
+    protocol: Protocol,
+    /// Największa ramka przyjmowana po handshake.
+    max_frame: usize,
 }
 
 impl Connector {
-    pub fn new(conn: TcpStream, side: ConnectionSide ) -> Self {
+    pub fn new(conn: impl Transport + 'static, side: ConnectionSide ) -> Self {
         Connector {
-            conn,
+            conn: Box::new(conn),
             side,
-            blowfish: Blowfish::new(BF_KEY.as_slice()).unwrap(),
-            gost: None,
-            way3: None,
-            prv_request: None,
-            prv_answer: None,
+            blowfish: frame_cipher(),
+            keys: None,
+            seq: Sequence::default(),
+            hello: Hello::default(),
+            protocol: Protocol::LEGACY,
+            max_frame: MAX_FRAME,
         }
     }
 
+    /// Zmiana oferowanych wersji protokołu i możliwości (przed `init`).
+    /// Klient oferujący tylko wersję 1 wykonuje handshake w pierwotnej
+    /// postaci, zrozumiałej także dla starszych serwerów.
+    pub fn set_hello(&mut self, hello: Hello) {
+        self.hello = hello;
+    }
+
+    /// Zmiana największej ramki (i wiadomości po dekompresji) przyjmowanej
+    /// po handshake. Przed jego zakończeniem obowiązuje `MAX_HANDSHAKE_FRAME`.
+    pub fn set_max_frame(&mut self, max_frame: usize) {
+        self.max_frame = max_frame;
+    }
+
+    pub fn max_frame(&self) -> usize {
+        self.max_frame
+    }
+
+    /// Protokół uzgodniony w handshake.
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Czy żądania mogą być wysyłane bez czekania na odpowiedzi.
+    pub fn pipelined(&self) -> bool {
+        self.protocol.has(CAP_PIPELINING)
+    }
+
+    /// Liczba wysłanych żądań, na które nie przyszła jeszcze odpowiedź.
+    pub fn pending(&self) -> usize {
+        self.seq.pending()
+    }
+
+    pub(crate) fn is_client(&self) -> bool {
+        matches!(self.side, ConnectionSide::Client)
+    }
+
+    /// Kodowanie żądań i odpowiedzi uzgodnione w handshake.
+    pub fn encoding(&self) -> Encoding {
+        self.protocol.encoding()
+    }
+
     pub fn init(&mut self) -> io::Result<()> {
         match self.side {
             ConnectionSide::Server => self.init_server(),
@@ -88,9 +153,39 @@ impl Connector {
     } // fn init
     
     fn init_server(&mut self) -> io::Result<()> {
-        self.read_client_id()?;
-        self.send_keys()
+        let client = self.read_client_id()?;
+        match self.hello.negotiate(client.as_ref().unwrap_or(&Hello::LEGACY)) {
+            Ok(protocol) => {
+                // Klient w wersji 1 nie oczekuje niczego poza kluczami.
+                self.send_keys(client.map(|_| protocol))?;
+                self.set_protocol(protocol);
+                Ok(())
+            }
+            Err(message) => {
+                self.send_refusal(VERSION_CMD, &message)?;
+                Err(Error::new(ErrorKind::Unsupported, message))
+            }
+        }
     } // fn init_sever
+
+    fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = protocol;
+        self.seq.pipelined = protocol.has(CAP_PIPELINING);
+    }
+
+    /// Uprzejma odmowa przyjęcia połączenia.
+    /// Odczytujemy identyfikator klienta (aby klient zdążył go wysłać,
+    /// zanim zamkniemy połączenie) i zamiast kluczy wysyłamy odpowiedź
+    /// z powodem odmowy.
+    pub fn reject(&mut self, reason: &str) -> io::Result<()> {
+        let _ = Message::read(&mut self.conn, MAX_HANDSHAKE_FRAME)?;
+        self.send_refusal(REJECT_CMD, reason)
+    } // fn reject
+
+    fn send_refusal(&mut self, cmd: &str, reason: &str) -> io::Result<()> {
+        let data = self.blowfish.encrypt_cbc(&refusal(cmd, reason)?);
+        Message::write(&mut self.conn, data.as_slice())
+    } // fn send_refusal
     
     fn init_client(&mut self) -> io::Result<()> {
         self.send_client_id()?;
@@ -99,130 +194,511 @@ impl Connector {
     } // fn init_client
 
     fn send_client_id(&mut self) -> io::Result<()> {
-        let data = self.blowfish.encrypt_cbc(&CLIENT_ID);
+        let data = self.blowfish.encrypt_cbc(&client_id(&self.hello));
         Message::write(&mut self.conn, &data)
     } // fn send_client_id
     
-    fn read_client_id(&mut self) -> io::Result<()> {
-        let client_id = Message::read(&mut self.conn)?;
-        let client_id = self.blowfish.decrypt_cbc(&client_id);
-        if client_id != CLIENT_ID {
-            return Err(Error::new(ErrorKind::InvalidData, "Invalid client-id.")); 
-        }
-        Ok(())
+    fn read_client_id(&mut self) -> io::Result<Option<Hello>> {
+        let client_id = Message::read(&mut self.conn, MAX_HANDSHAKE_FRAME)?;
+        parse_client_id(&self.blowfish.decrypt_cbc(&client_id))
     } // fn read_client_id
 
-    /// Serwer wysyła klucze szyfrowania dla GOST i 3-Way.
-    /// Te klucze są losowo generowane dla jednej, tej konkretnej, sesji.
-    fn send_keys(&mut self) -> io::Result<()> {
-        let gost_key = rnd_bytes(gost::KEY_SIZE);
-        let way3_key = rnd_bytes(way3::KEY_SIZE);
-        let mut keys = vec![];
-        keys.extend_from_slice(gost_key.as_slice());
-        keys.extend_from_slice(way3_key.as_slice());
-        // Klucze szyfrujemy Blowfishem i wysyłamy do klienta.
-        let data = self.blowfish.encrypt_cbc(keys.as_slice());
+    fn send_keys(&mut self, protocol: Option<Protocol>) -> io::Result<()> {
+        let (data, keys) = Keys::generate(protocol);
+        let data = self.blowfish.encrypt_cbc(data.as_slice());
         Message::write(&mut self.conn, data.as_slice())?;
         // Po udanym wysłaniu kluczy używamy ich do
         // utworzenia silników szyfrowania po stronie serwera.
-        self.gost = Some(Gost::new(gost_key.as_slice()).unwrap());
-        self.way3 = Some(Way3::new(way3_key.as_slice()).unwrap());
+        self.keys = Some(keys);
         Ok(())
     } // fn send_keys
 
-    /// Klient odczytuje klucze szyfrowania dla GOST i 3-Way.
-    /// Serwer je losowo wygenerował na użytek tej sesji.
     fn read_keys(&mut self) -> io::Result<()> {
-        // Odczyt kluczy i ich odszyfrowanie Blowfishem.
-        let keys = Message::read(&mut self.conn)?;
-        let keys = self.blowfish.decrypt_cbc(keys.as_slice());
-        if keys.len() != gost::KEY_SIZE + way3::KEY_SIZE {
-            return Err(Error::new(ErrorKind::InvalidData, "Invalid keys length."));
-        }
-        // Po udanych odczycie kluczy używamy ich do
-        // utworzenia silników szyfrowania po stronie klienta.
-        let gost_key = keys[..gost::KEY_SIZE].to_vec();
-        let way3_key = keys[gost::KEY_SIZE..].to_vec();
-        self.gost = Some(Gost::new(gost_key.as_slice()).unwrap());
-        self.way3 = Some(Way3::new(way3_key.as_slice()).unwrap());
+        let data = Message::read(&mut self.conn, MAX_HANDSHAKE_FRAME)?;
+        let (keys, protocol) = Keys::parse(self.blowfish.decrypt_cbc(data.as_slice()))?;
+        self.set_protocol(self.hello.accept(protocol)?);
+        self.keys = Some(keys);
         Ok(())
     } // fn read_keys
 
-    pub fn peer_addr(&self) -> String {
-        self.conn.peer_addr().unwrap().to_string()
+    pub fn peer_addr(&self) -> io::Result<Endpoint> {
+        self.conn.peer_addr()
+    }
+    pub fn local_addr(&self) -> io::Result<Endpoint> {
+        self.conn.local_addr()
+    }
+
+    /// Limit czasu odczytu, np. na czas handshake (None - bez limitu).
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.conn.set_read_timeout(timeout)
     }
-    pub fn local_addr(&self) -> String {
-        self.conn.local_addr().unwrap().to_string()
+
+    /// Drugi uchwyt połączenia, przez który można z zewnątrz je zamknąć.
+    pub fn try_clone_transport(&self) -> io::Result<Box<dyn Transport>> {
+        self.conn.try_clone()
     }
     
     //------- Serwer ------------------------------------------------
+
+    /// Oczekiwanie (co najwyżej `timeout`) na dane od drugiej strony.
+    /// Zwraca true, gdy można czytać, false gdy czas upłynął.
+    /// Zamknięcie połączenia przez drugą stronę to błąd UnexpectedEof.
+    pub fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.conn.wait_for_data(timeout)
+    } // fn wait_for_data
+
+    /// Powiadomienie klienta, że serwer kończy pracę.
+    /// Po wysłaniu powiadomienia sesja powinna zostać zamknięta.
+    pub fn send_shutdown_notice(&mut self) -> io::Result<()> {
+        self.send_answer(Answer::new(0, SHUTDOWN_MESSAGE, SHUTDOWN_CMD))
+    } // fn send_shutdown_notice
     
-    /// Odczytanie żądania.
-    /// Żądanie zapamiętujemy?
+    /// Odczytanie żądania (numer sprawdza `Sequence::request_read`).
     pub fn read_request(&mut self) -> io::Result<Request> {
-        let data = Message::read(&mut self.conn)?;
-        let request = self.blowfish.decrypt_cbc(&data);
-        let request = Request::from_json(&request)?;
-        if self.prv_answer.is_some() && request.id() != (self.prv_answer.as_ref().unwrap().id() + 1) {
-            return Err(Error::new(ErrorKind::InvalidData, "Invalid request id."));
-        }
-        self.prv_request = Some(request.clone());
+        let request = self.read_frame()?;
+        let request = self.encoding().decode_request(&request)?;
+        self.seq.request_read(request.id())?;
         Ok(request)
     } // fn read_request
     
-    /// Wysłanie zapytania.
-    /// Przed wysłaniem zapytania musimy uzupełnić ID.
-    /// Numer ID pobieramy z poprzedniego zapytania. 
-    /// Wysłane zapytanie zapamiętujemy.
-    pub fn send_answer(&mut self, mut answer: Answer) -> io::Result<()> {
-        let id = match self.prv_request {
-            Some(ref request) => request.id(),
-            None => 0 };
-        answer.set_id(id + 1);
-        let data = self.blowfish.encrypt_cbc(answer.to_json()?.as_bytes());
-        Message::write(&mut self.conn, data.as_slice())?;
-        self.prv_answer = Some(answer);
-        Ok(())
+    /// Wysłanie odpowiedzi na ostatnio odczytane żądanie.
+    pub fn send_answer(&mut self, answer: Answer) -> io::Result<()> {
+        self.send_answer_to(self.seq.last_request(), answer)
     } // fn send_answer
 
+    /// Wysłanie odpowiedzi na żądanie o numerze `request_id`.
+    pub fn send_answer_to(&mut self, request_id: u64, mut answer: Answer) -> io::Result<()> {
+        answer.set_id(self.seq.answer_id(request_id));
+        let data = self.encoding().encode_answer(&answer)?;
+        self.write_frame(&data)?;
+        self.seq.answer_sent(answer.id());
+        Ok(())
+    } // fn send_answer_to
+
     //------- Klient ------------------------------------------------
     
-    pub fn send_request(&mut self, mut request: Request) -> io::Result<()> {
-        let id = match self.prv_answer {
-            Some(ref request) => request.id(),
-            None => 0 };
-        request.set_id(id + 1);
-        let data = self.blowfish.encrypt_cbc(request.to_json()?.as_bytes());
-        Message::write(&mut self.conn, data.as_slice())?;
+    /// Wysłanie żądania. Zwraca numer nadany żądaniu.
+    /// Z `CAP_PIPELINING` można wysłać kolejne żądania przed odczytaniem
+    /// odpowiedzi - odpowiedzi mają numery swoich żądań.
+    pub fn send_request(&mut self, mut request: Request) -> io::Result<u64> {
+        let id = self.seq.request_id();
+        request.set_id(id);
+        let data = self.encoding().encode_request(&request)?;
+        if let Err(err) = self.write_frame(&data) {
+            // Serwer mógł zamknąć połączenie po wysłaniu powiadomienia
+            // o zakończeniu pracy - wtedy zgłaszamy właśnie to.
+            return Err(self.pending_shutdown_notice().unwrap_or(err));
+        }
         // Jeśli zapis się zakończył sukcesem, zapamiętujemy to żądanie. 
-        self.prv_request = Some(request);
-        Ok(())
+        self.seq.request_sent(id);
+        Ok(id)
     } // fn send_request
+
+    /// Odczyt powiadomienia o zamykaniu serwera, które mogło
+    /// czekać w buforze gniazda, gdy nie udało się wysłać żądania.
+    fn pending_shutdown_notice(&mut self) -> Option<Error> {
+        match self.wait_for_data(Duration::from_millis(100)) {
+            Ok(true) => (),
+            _ => return None,
+        }
+        let data = self.read_frame().ok()?;
+        let answer = self.encoding().decode_answer(&data).ok()?;
+        match answer.cmd == SHUTDOWN_CMD {
+            true => Some(Error::new(ErrorKind::ConnectionAborted, answer.message)),
+            false => None,
+        }
+    } // fn pending_shutdown_notice
     
     pub fn read_answer(&mut self) -> io::Result<Answer> {
-        let data = Message::read(&mut self.conn)?;
-        let answer = self.blowfish.decrypt_cbc(&data);
-        let answer = Answer::from_json(&answer)?;
-        if self.prv_answer.is_some() && answer.id() != (self.prv_request.as_ref().unwrap().id() + 1) {
-            return Err(Error::new(ErrorKind::InvalidData, "Invalid answer id."));
+        let answer = self.read_frame()?;
+        let answer = self.encoding().decode_answer(&answer)?;
+        // Serwer kończy pracę - to nie jest odpowiedź na nasze żądanie.
+        if answer.cmd == SHUTDOWN_CMD {
+            return Err(Error::new(ErrorKind::ConnectionAborted, answer.message));
         }
-        // Jeśli wszystko poszło dobrze, zapamiętujemy odpowiedź.
-        self.prv_answer = Some(answer.clone());
+        self.seq.answer_read(answer.id())?;
         Ok(answer)
     } // fn read_answer
      
+    fn write_frame(&mut self, data: &[u8]) -> io::Result<()> {
+        let (data, flags) = seal(&self.blowfish, self.protocol, data);
+        Message::write_with_flags(&mut self.conn, &data, flags)
+    }
+
+    fn read_frame(&mut self) -> io::Result<Vec<u8>> {
+        let (data, flags) = Message::read_with_flags(&mut self.conn, self.max_frame)?;
+        unseal(&self.blowfish, self.protocol, &data, flags, self.max_frame)
+    }
+
+    /// Podział połączenia po handshake na niezależne strony odczytu i zapisu
+    /// ramek, np. do obsługi w osobnych wątkach (patrz `net::mux`).
+    pub fn split(self) -> io::Result<(Frames, Frames)> {
+        let reader = Frames {
+            conn: self.conn.try_clone()?,
+            blowfish: self.blowfish.clone(),
+            protocol: self.protocol,
+            max_frame: self.max_frame,
+        };
+        let writer = Frames { conn: self.conn, blowfish: self.blowfish, protocol: self.protocol, max_frame: self.max_frame };
+        Ok((reader, writer))
+    } // fn split
+
     /// Szyfrowanie: encrypt-decrypt-encrypt (Blowfish-GOST-Way3).
     fn encrypt(&mut self, data: &[u8]) -> Vec<u8> {
+        let keys = self.keys.as_ref().unwrap();
         let data = self.blowfish.encrypt_cbc(data);
-        let data = self.gost.as_ref().unwrap().decrypt_cbc(data.as_slice());
-        self.way3.as_ref().unwrap().encrypt_cbc(data.as_slice())
+        let data = keys.gost.decrypt_cbc(data.as_slice());
+        keys.way3.encrypt_cbc(data.as_slice())
     } // fn encrypt
     
     /// Odszyfrowanie: decrypt-encrypt-decrypt (Way3-GOST-Blowfish). 
     fn decrypt(&mut self, data: &[u8]) -> Vec<u8> {
-        let data = self.way3.as_ref().unwrap().decrypt_cbc(data);
-        let data = self.gost.as_ref().unwrap().encrypt_cbc(data.as_slice());
+        let keys = self.keys.as_ref().unwrap();
+        let data = keys.way3.decrypt_cbc(data);
+        let data = keys.gost.encrypt_cbc(data.as_slice());
         self.blowfish.decrypt_cbc(data.as_slice())
     } // fn decrypt
     
 } // Connector
+
+/// Jedna strona (odczyt albo zapis) ramek sesji po handshake.
+pub struct Frames {
+    conn: Box<dyn Transport>,
+    blowfish: Blowfish,
+    protocol: Protocol,
+    max_frame: usize,
+}
+
+impl Frames {
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let (data, flags) = seal(&self.blowfish, self.protocol, data);
+        Message::write_with_flags(&mut self.conn, &data, flags)
+    }
+
+    pub fn read(&mut self) -> io::Result<Vec<u8>> {
+        let (data, flags) = Message::read_with_flags(&mut self.conn, self.max_frame)?;
+        unseal(&self.blowfish, self.protocol, &data, flags, self.max_frame)
+    }
+
+    pub fn max_frame(&self) -> usize {
+        self.max_frame
+    }
+}
+
+//------- Wspólne dla Connector i AsyncConnector ----------------
+
+/// Szyfr ramek handshake i sesji.
+pub(crate) fn frame_cipher() -> Blowfish {
+    Blowfish::new(BF_KEY.as_slice()).unwrap()
+}
+
+/// Identyfikator klienta, a za nim oferta wersji i możliwości (`Hello`).
+pub(crate) fn client_id(hello: &Hello) -> Vec<u8> {
+    let mut client_id = CLIENT_ID.to_vec();
+    if hello.max_version > 1 {
+        client_id.extend(hello.to_bytes());
+    }
+    client_id
+} // fn client_id
+
+/// Sprawdzenie identyfikatora klienta.
+/// Zwraca ofertę klienta (None, jeśli klient wysłał sam identyfikator).
+pub(crate) fn parse_client_id(client_id: &[u8]) -> io::Result<Option<Hello>> {
+    if !client_id.starts_with(&CLIENT_ID) {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid client-id.")); 
+    }
+    match client_id.len() == CLIENT_ID.len() {
+        true => Ok(None),
+        false => Hello::from_bytes(&client_id[CLIENT_ID.len()..]).map(Some),
+    }
+} // fn parse_client_id
+
+/// Odpowiedź (zawsze JSON) wysyłana zamiast kluczy.
+pub(crate) fn refusal(cmd: &str, reason: &str) -> io::Result<Vec<u8>> {
+    Ok(Answer::new(-1, reason, cmd).to_json()?.into_bytes())
+}
+
+/// Klucze szyfrowania dla GOST i 3-Way.
+/// Te klucze są losowo generowane dla jednej, tej konkretnej, sesji.
+pub(crate) struct Keys {
+    gost: Gost,
+    way3: Way3,
+}
+
+impl Keys {
+    /// Serwer losuje klucze. Zwraca dane do wysłania: klucze,
+    /// a za nimi uzgodniony protokół (jeśli klient przysłał ofertę).
+    pub(crate) fn generate(protocol: Option<Protocol>) -> (Vec<u8>, Keys) {
+        let gost_key = rnd_bytes(gost::KEY_SIZE);
+        let way3_key = rnd_bytes(way3::KEY_SIZE);
+        let mut data = vec![];
+        data.extend_from_slice(gost_key.as_slice());
+        data.extend_from_slice(way3_key.as_slice());
+        data.extend(protocol.map(|p| p.to_bytes()).unwrap_or_default());
+        let keys = Keys {
+            gost: Gost::new(gost_key.as_slice()).unwrap(),
+            way3: Way3::new(way3_key.as_slice()).unwrap(),
+        };
+        (data, keys)
+    } // fn generate
+
+    /// Klient odczytuje klucze i protokół wybrany przez serwer.
+    pub(crate) fn parse(mut data: Vec<u8>) -> io::Result<(Keys, Protocol)> {
+        let size = gost::KEY_SIZE + way3::KEY_SIZE;
+        if data.len() != size && data.len() != size + Protocol::SIZE {
+            // Zamiast kluczy serwer mógł przysłać odmowę połączenia.
+            if let Ok(answer) = Answer::from_json(&data) {
+                match answer.cmd.as_str() {
+                    REJECT_CMD => return Err(Error::new(ErrorKind::ConnectionRefused, answer.message)),
+                    VERSION_CMD => return Err(Error::new(ErrorKind::Unsupported, answer.message)),
+                    _ => (),
+                }
+            }
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid keys length."));
+        }
+        // Serwer w wersji 1 przysyła same klucze.
+        let protocol = match data.len() == size {
+            true => Protocol::LEGACY,
+            false => Protocol::from_bytes(&data.split_off(size))?,
+        };
+        let keys = Keys {
+            gost: Gost::new(&data[..gost::KEY_SIZE]).unwrap(),
+            way3: Way3::new(&data[gost::KEY_SIZE..]).unwrap(),
+        };
+        Ok((keys, protocol))
+    } // fn parse
+}
+
+/// Numeracja żądań i odpowiedzi.
+/// Bez `CAP_PIPELINING` żądania i odpowiedzi występują na przemian, a każdy
+/// numer jest o jeden większy od poprzedniego. Z `CAP_PIPELINING` klient
+/// numeruje żądania kolejno, odpowiedź ma numer swojego żądania, a serwer
+/// przyjmuje każdy numer tylko raz (patrz `ReplayWindow`).
+#[derive(Default)]
+pub(crate) struct Sequence {
+    pub(crate) pipelined: bool,
+    /// Numer ostatnio wysłanego lub odczytanego żądania.
+    last_request: u64,
+    /// Numer ostatnio wysłanej lub odczytanej odpowiedzi.
+    last_answer: Option<u64>,
+    /// Żądania czekające na odpowiedź (z `CAP_PIPELINING`).
+    pending: HashSet<u64>,
+    /// Numery przyjętych żądań (z `CAP_PIPELINING`).
+    replay: ReplayWindow,
+}
+
+impl Sequence {
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub(crate) fn last_request(&self) -> u64 {
+        self.last_request
+    }
+
+    /// Numer dla kolejnego żądania.
+    pub(crate) fn request_id(&self) -> u64 {
+        match (self.pipelined, self.last_answer) {
+            (true, _) => self.last_request + 1,
+            (false, Some(id)) => id + 1,
+            (false, None) => 1,
+        }
+    }
+
+    pub(crate) fn request_sent(&mut self, id: u64) {
+        self.last_request = id;
+        if self.pipelined {
+            self.pending.insert(id);
+        }
+    }
+
+    pub(crate) fn request_read(&mut self, id: u64) -> io::Result<()> {
+        if self.pipelined {
+            if !self.replay.accept(id) {
+                return Err(Error::new(ErrorKind::InvalidData, "Replayed request id."));
+            }
+        } else if self.last_answer.is_some_and(|answer| id != answer + 1) {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid request id."));
+        }
+        self.last_request = id;
+        Ok(())
+    }
+
+    /// Numer odpowiedzi na żądanie `request_id`.
+    pub(crate) fn answer_id(&self, request_id: u64) -> u64 {
+        match self.pipelined {
+            true => request_id,
+            false => request_id + 1,
+        }
+    }
+
+    pub(crate) fn answer_sent(&mut self, id: u64) {
+        self.last_answer = Some(id);
+    }
+
+    pub(crate) fn answer_read(&mut self, id: u64) -> io::Result<()> {
+        let valid = match self.pipelined {
+            true => self.pending.remove(&id),
+            false => self.last_answer.is_none() || id == self.last_request + 1,
+        };
+        if !valid {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid answer id."));
+        }
+        self.last_answer = Some(id);
+        Ok(())
+    }
+}
+
+/// Przygotowanie ramki z danymi sesji: kompresja (jeśli uzgodniona
+/// i opłacalna) i szyfrowanie. Zwraca dane i flagi nagłówka ramki.
+pub(crate) fn seal(blowfish: &Blowfish, protocol: Protocol, data: &[u8]) -> (Vec<u8>, u32) {
+    if protocol.has(CAP_COMPRESSION) && data.len() >= MIN_COMPRESS {
+        let packed = lz::compress(data);
+        // Dane nieściśliwe (np. już skompresowane pliki) wysyłamy bez zmian.
+        if packed.len() + packed.len() / 16 < data.len() {
+            return (blowfish.encrypt_cbc(&packed), COMPRESSED);
+        }
+    }
+    (blowfish.encrypt_cbc(data), 0)
+} // fn seal
+
+/// Odczytanie ramki z danymi sesji: odszyfrowanie i ewentualna dekompresja.
+pub(crate) fn unseal(blowfish: &Blowfish, protocol: Protocol, data: &[u8], flags: u32, max_frame: usize) -> io::Result<Vec<u8>> {
+    let data = blowfish.decrypt_cbc(data);
+    if flags & COMPRESSED == 0 {
+        return Ok(data);
+    }
+    if !protocol.has(CAP_COMPRESSION) {
+        return Err(Error::new(ErrorKind::InvalidData, "Unexpected compressed frame."));
+    }
+    lz::decompress(&data, max_frame)
+} // fn unseal
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use crate::net::protocol::{CAP_BLOWFISH, CAPABILITIES, VERSION};
+
+    /// Serwer obsługujący jedno żądanie: odsyła parametry żądania i zawartość.
+    fn echo_server(hello: Hello) -> (String, thread::JoinHandle<io::Result<Protocol>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = Connector::new(stream, ConnectionSide::Server);
+            conn.set_hello(hello);
+            conn.init()?;
+            let request = conn.read_request()?;
+            let mut answer = Answer::new_with_data(0, "OK", &request.command, request.params);
+            answer.binary = vec![7, 0x80, 0, 0, 0, 0, 0, 0];
+            conn.send_answer(answer)?;
+            Ok(conn.protocol())
+        });
+        (addr, handle)
+    }
+
+    fn exchange(client: Hello, server: Hello) -> io::Result<(Protocol, Protocol)> {
+        let (addr, handle) = echo_server(server);
+        let mut conn = Connector::new(TcpStream::connect(addr).unwrap(), ConnectionSide::Client);
+        conn.set_hello(client);
+        if let Err(err) = conn.init() {
+            let _ = handle.join();
+            return Err(err);
+        }
+        // Drugi parametr jest dość duży i ściśliwy, by trafić do kompresji.
+        let params = vec!["a b".to_string(), "-rw-r--r-- 1 piotr staff 4096\n".repeat(100)];
+        conn.send_request(Request::new("echo".into(), params.clone()))?;
+        let answer = conn.read_answer()?;
+        assert_eq!(answer.data, params);
+        assert_eq!(answer.binary, [7, 0x80, 0, 0, 0, 0, 0, 0]);
+        Ok((conn.protocol(), handle.join().unwrap()?))
+    }
+
+    fn hello(min_version: u32, max_version: u32, capabilities: u32) -> Hello {
+        Hello { min_version, max_version, capabilities }
+    }
+
+    #[test]
+    fn test_negotiation() {
+        let current = Protocol { version: VERSION, capabilities: CAPABILITIES };
+        let (client, server) = exchange(Hello::default(), Hello::default()).unwrap();
+        assert_eq!((client, server), (current, current));
+        assert_eq!(client.encoding(), Encoding::Binary);
+
+        // Klient bez kodowania binarnego.
+        let (client, _) = exchange(hello(1, VERSION, CAP_BLOWFISH), Hello::default()).unwrap();
+        assert_eq!(client.encoding(), Encoding::Json);
+
+        // Kompresja tylko po jednej stronie - wyłączona.
+        let without = CAPABILITIES & !CAP_COMPRESSION;
+        let (client, _) = exchange(Hello::default(), hello(1, VERSION, without)).unwrap();
+        assert!(!client.has(CAP_COMPRESSION));
+        let (client, _) = exchange(hello(1, VERSION, without), Hello::default()).unwrap();
+        assert!(!client.has(CAP_COMPRESSION));
+
+        // Handshake w wersji 1 (jak u starszych klientów i serwerów).
+        assert_eq!(exchange(Hello::LEGACY, Hello::default()).unwrap().0, Protocol::LEGACY);
+        assert_eq!(exchange(Hello::default(), Hello::LEGACY).unwrap().0, Protocol::LEGACY);
+    }
+
+    #[test]
+    fn test_pipelining() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = Connector::new(stream, ConnectionSide::Server);
+            conn.init().unwrap();
+            // Odpowiedzi w odwrotnej kolejności.
+            let requests = (0..3).map(|_| conn.read_request().unwrap()).collect::<Vec<_>>();
+            for request in requests.into_iter().rev() {
+                let answer = Answer::new_with_data(0, "OK", &request.command, request.params.clone());
+                conn.send_answer_to(request.id(), answer).unwrap();
+            }
+        });
+        let mut conn = Connector::new(TcpStream::connect(addr).unwrap(), ConnectionSide::Client);
+        conn.init().unwrap();
+        assert!(conn.pipelined());
+        let ids = (0..3)
+            .map(|i| conn.send_request(Request::new("echo".into(), vec![i.to_string()])).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(conn.pending(), 3);
+        for (id, param) in [(3, "2"), (2, "1"), (1, "0")] {
+            let answer = conn.read_answer().unwrap();
+            assert_eq!((answer.id(), answer.data[0].as_str()), (id, param));
+        }
+        assert_eq!(conn.pending(), 0);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_oversized_frame() {
+        use std::io::Write;
+        use crate::data::message::FrameTooLarge;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        // Sam nagłówek deklarujący 1 GiB, przed handshake.
+        client.write_all(&(1u32 << 30).to_be_bytes()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut conn = Connector::new(stream, ConnectionSide::Server);
+        let err = conn.init().unwrap_err();
+        assert_eq!(FrameTooLarge::from_io(&err), Some(FrameTooLarge { size: 1 << 30, limit: MAX_HANDSHAKE_FRAME }));
+    }
+
+    #[test]
+    fn test_incompatible() {
+        let err = exchange(hello(3, 4, CAP_BLOWFISH), Hello::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(err.to_string(), format!(
+            "Incompatible protocol versions: client supports 3-4, server supports 1-{}.", VERSION));
+
+        // Serwer w wersji 1, klient wymaga nowszej.
+        let err = exchange(hello(2, 2, CAP_BLOWFISH), Hello::LEGACY).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(err.to_string(), "Incompatible protocol versions: client supports 2, server supports 1.");
+    }
+}
@@ -0,0 +1,144 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Nawiązywanie połączeń TCP z hostem o wielu adresach ("happy eyeballs", RFC 8305).
+//!
+//! Adresy z resolvera są układane na przemian IPv6 i IPv4. Próby zaczynamy
+//! po kolei, co `ATTEMPT_DELAY`, nie czekając na zakończenie poprzednich
+//! (a od razu po nieudanej próbie). Wygrywa pierwsze udane połączenie,
+//! późniejsze są zamykane.
+
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Odstęp między kolejnymi próbami (zalecany przez RFC 8305).
+pub const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+/// Czas na nawiązanie jednego połączenia.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Połączenie z `host:port` (nazwa hosta, `1.2.3.4:port` albo `[::1]:port`).
+pub fn connect(addr: &str) -> io::Result<TcpStream> {
+    let addrs = interleave(addr.to_socket_addrs()?.collect());
+    connect_any(&addrs, ATTEMPT_DELAY)
+}
+
+/// Połączenie z pierwszym odpowiadającym adresem z listy.
+/// Zwraca błąd ostatniej próby, jeśli żadna się nie udała.
+pub fn connect_any(addrs: &[SocketAddr], delay: Duration) -> io::Result<TcpStream> {
+    if addrs.is_empty() {
+        return Err(io::Error::new(ErrorKind::NotFound, "no addresses to connect to"));
+    }
+    let (sender, receiver) = mpsc::channel();
+    let mut started = 0;
+    let mut failed = 0;
+    loop {
+        if started < addrs.len() {
+            let addr = addrs[started];
+            let sender = sender.clone();
+            // Wynik spóźnionej próby trafia do zamkniętego kanału i połączenie jest zamykane.
+            thread::spawn(move || {
+                let _ = sender.send(TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT));
+            });
+            started += 1;
+        }
+        let result = match started < addrs.len() {
+            true => receiver.recv_timeout(delay),
+            false => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match result {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => {
+                failed += 1;
+                if failed == addrs.len() {
+                    return Err(e);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => unreachable!("sender is held by this function"),
+        }
+    }
+} // fn connect_any
+
+/// Adresy na przemian z obu rodzin, zaczynając od rodziny pierwszego adresu
+/// (resolver zwraca je w kolejności preferencji systemu).
+pub fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_v6 = first.is_ipv6();
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addrs.into_iter()
+        .partition(|addr| addr.is_ipv6() == first_v6);
+    preferred.reverse();
+    other.reverse();
+    let mut result = Vec::with_capacity(preferred.len() + other.len());
+    loop {
+        match (preferred.pop(), other.pop()) {
+            (None, None) => return result,
+            (a, b) => result.extend(a.into_iter().chain(b)),
+        }
+    }
+} // fn interleave
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    #[test]
+    fn test_interleave() {
+        let v4 = |n: u8| SocketAddr::from(([10, 0, 0, n], 1));
+        let v6 = |n: u16| SocketAddr::from(([n, 0, 0, 0, 0, 0, 0, 1], 1));
+        assert_eq!(interleave(vec![v6(1), v6(2), v6(3), v4(1)]), vec![v6(1), v4(1), v6(2), v6(3)]);
+        assert_eq!(interleave(vec![v4(1), v4(2), v6(1), v6(2)]), vec![v4(1), v6(1), v4(2), v6(2)]);
+        assert!(interleave(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_connect_any() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let good = listener.local_addr().unwrap();
+        // Zamknięty port: odmowa połączenia.
+        let refused = {
+            let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+            closed.local_addr().unwrap()
+        };
+        // Adres z TEST-NET-1: próba wisi (albo od razu kończy się błędem),
+        // kolejny adres zaczynamy po `delay`.
+        let blackhole = SocketAddr::from(([192, 0, 2, 1], 25105));
+
+        let started = Instant::now();
+        let stream = connect_any(&[refused, blackhole, good], Duration::from_millis(50)).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let e = connect_any(&[refused], ATTEMPT_DELAY).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::ConnectionRefused);
+        assert_eq!(connect_any(&[], ATTEMPT_DELAY).unwrap_err().kind(), ErrorKind::NotFound);
+
+        let stream = connect(&format!("localhost:{}", good.port())).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), good);
+    }
+}
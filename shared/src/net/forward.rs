@@ -0,0 +1,163 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Przekierowanie portów TCP przez sesję (jak tunele ssh).
+//!
+//! `L:[adres:]port:host:port_hosta` - klient nasłuchuje lokalnie, a każde
+//! połączenie przechodzi przez serwer do `host:port_hosta`.
+//! `R:[adres:]port:host:port_hosta` - serwer nasłuchuje, a połączenia
+//! przechodzą przez klienta do `host:port_hosta`.
+//! Każde przekierowane połączenie to osobny kanał (`Channel::tunnel`),
+//! więc przekierowanie wymaga `CAP_CHANNELS`.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Klient -> serwer: połączenie z celem, parametry: host, port.
+pub const FORWARD_CMD: &str = "forward";
+/// Klient -> serwer: nasłuchiwanie dla `R:`, parametry: adres, port.
+/// Odpowiedź zawiera adres, na którym serwer nasłuchuje.
+pub const LISTEN_CMD: &str = "forward-listen";
+/// Serwer -> klient: nowe połączenie dla `R:`, parametry: port serwera, adres klienta.
+pub const FORWARDED_CMD: &str = "forwarded";
+
+/// Domyślny adres nasłuchiwania (tylko połączenia lokalne).
+pub const DEFAULT_BIND: &str = "127.0.0.1";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Nasłuchuje klient, łączy serwer.
+    Local,
+    /// Nasłuchuje serwer, łączy klient.
+    Remote,
+}
+
+/// Opis przekierowania, np. `L:8080:db.internal:5432`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Spec {
+    pub direction: Direction,
+    pub bind: String,
+    pub port: u16,
+    pub host: String,
+    pub host_port: u16,
+}
+
+impl FromStr for Spec {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid forward {}, expected L:[addr:]port:host:port or R:[addr:]port:host:port", text);
+        let (direction, rest) = match text.split_once(':') {
+            Some(("L" | "l", rest)) => (Direction::Local, rest),
+            Some(("R" | "r", rest)) => (Direction::Remote, rest),
+            _ => return Err(invalid()),
+        };
+        let fields = split_fields(rest).ok_or_else(invalid)?;
+        let (bind, port, host, host_port) = match fields.as_slice() {
+            [port, host, host_port] => (DEFAULT_BIND, port, host, host_port),
+            [bind, port, host, host_port] => (*bind, port, host, host_port),
+            _ => return Err(invalid()),
+        };
+        if bind.is_empty() || host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Spec {
+            direction,
+            bind: bind.to_string(),
+            port: port.parse().map_err(|_| invalid())?,
+            host: host.to_string(),
+            host_port: host_port.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Local => 'L',
+            Direction::Remote => 'R',
+        };
+        write!(f, "{}:{}:{}:{}", direction, host_port(&self.bind, self.port), bracket(&self.host), self.host_port)
+    }
+}
+
+/// `host:port`, adres IPv6 w nawiasach.
+pub fn host_port(host: &str, port: u16) -> String {
+    format!("{}:{}", bracket(host), port)
+}
+
+fn bracket(host: &str) -> String {
+    match host.contains(':') {
+        true => format!("[{}]", host),
+        false => host.to_string(),
+    }
+}
+
+/// Podział na pola rozdzielone `:`, adresy IPv6 w nawiasach (bez nawiasów w wyniku).
+fn split_fields(text: &str) -> Option<Vec<&str>> {
+    let mut fields = Vec::new();
+    let mut rest = text;
+    loop {
+        let (field, tail) = match rest.strip_prefix('[') {
+            Some(inner) => {
+                let (field, tail) = inner.split_once(']')?;
+                if !(tail.is_empty() || tail.starts_with(':')) {
+                    return None;
+                }
+                (field, tail)
+            }
+            None => rest.find(':').map_or((rest, ""), |i| (&rest[..i], &rest[i..])),
+        };
+        fields.push(field);
+        match tail.strip_prefix(':') {
+            Some(tail) => rest = tail,
+            None => return Some(fields),
+        }
+    }
+} // fn split_fields
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec() {
+        let spec: Spec = "L:8080:db.internal:5432".parse().unwrap();
+        assert_eq!(spec, Spec {
+            direction: Direction::Local,
+            bind: "127.0.0.1".into(),
+            port: 8080,
+            host: "db.internal".into(),
+            host_port: 5432,
+        });
+        assert_eq!(spec.to_string(), "L:127.0.0.1:8080:db.internal:5432");
+
+        let spec: Spec = "R:[::]:9000:[::1]:3000".parse().unwrap();
+        assert_eq!((spec.direction, spec.bind.as_str(), spec.host.as_str()), (Direction::Remote, "::", "::1"));
+        assert_eq!(spec.to_string(), "R:[::]:9000:[::1]:3000");
+        assert_eq!(spec.to_string().parse::<Spec>().unwrap(), spec);
+
+        for bad in ["X:1:a:2", "L:8080:db", "L:80800:db:1", "L:1:[::1:2", "L:1:[::1]x:2", "L::1:a:2", "L:1:a:b:c:2"] {
+            assert!(bad.parse::<Spec>().is_err(), "{}", bad);
+        }
+    }
+}
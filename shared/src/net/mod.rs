@@ -1 +1,10 @@
 pub mod connector;
+pub mod dial;
+pub mod forward;
+pub mod protocol;
+pub mod mux;
+pub mod replay;
+pub mod socks;
+pub mod transport;
+#[cfg(feature = "async")]
+pub mod async_connector;
@@ -0,0 +1,675 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Kanały logiczne w jednym połączeniu.
+//!
+//! Każda ramka sesji niesie nagłówek kanału:
+//! ```text
+//! u32 (BE)  numer kanału (klient: nieparzyste, serwer: parzyste, 0: powiadomienia)
+//! u8        rodzaj: DATA, END (ostatnia część wiadomości), WINDOW, CLOSE
+//! [u8]      dane (dla WINDOW: u32 - przyznany kredyt w bajtach)
+//! u8        TRAILER
+//! ```
+//! Wiadomość (zakodowane żądanie lub odpowiedź) jest dzielona na części
+//! nie większe niż `MAX_CHUNK`. Nadawca nie wysyła w kanale więcej niż
+//! `WINDOW` bajtów nieodebranych przez odbiorcę, więc długi transfer
+//! nie blokuje pozostałych kanałów ani nie zapełnia pamięci drugiej strony.
+//!
+//! Kanał może też przenosić połączenie TCP (`Channel::tunnel`): każda
+//! wiadomość to porcja danych, pusta wiadomość - koniec danych w tym kierunku.
+
+use std::collections::HashMap;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicU32, Ordering::Relaxed};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::data::{answer::Answer, message::FrameTooLarge, request::Request, wire::{Encoding, TRAILER}};
+use crate::net::connector::{Connector, Frames, SHUTDOWN_CMD, SHUTDOWN_MESSAGE};
+use crate::net::protocol::CAP_CHANNELS;
+use crate::net::replay::ReplayWindow;
+use crate::net::transport::Transport;
+
+/// Kanał powiadomień (np. o zamykaniu serwera).
+const CONTROL: u32 = 0;
+
+const DATA: u8 = 0;
+const END: u8 = 1;
+const WINDOW: u8 = 2;
+const CLOSE: u8 = 3;
+
+/// Największa część wiadomości wysyłana w jednej ramce.
+const MAX_CHUNK: usize = 32 * 1024;
+/// Okno kanału - tyle bajtów można wysłać bez potwierdzenia.
+const WINDOW_SIZE: usize = 256 * 1024;
+/// Odebrane dane potwierdzamy porcjami, nie każdą ramkę z osobna.
+const CREDIT_STEP: usize = WINDOW_SIZE / 4;
+
+/// Połączenie z kanałami. Wątek odczytu rozdziela przychodzące ramki
+/// do kanałów, zapis jest wspólny (chroniony mutexem).
+pub struct Mux {
+    shared: Arc<Shared>,
+    incoming: Mutex<Receiver<Channel>>,
+    stream: Mutex<Box<dyn Transport>>,
+    reader: Option<JoinHandle<()>>,
+    next_id: AtomicU32,
+}
+
+struct Shared {
+    writer: Mutex<Frames>,
+    state: Mutex<State>,
+    /// Zmiana okna lub zamknięcie kanału.
+    changed: Condvar,
+    encoding: Encoding,
+    /// Największa przyjmowana wiadomość.
+    max_message: usize,
+    /// Parzystość numerów kanałów otwieranych przez tę stronę.
+    parity: u32,
+}
+
+#[derive(Default)]
+struct State {
+    channels: HashMap<u32, Slot>,
+    /// Numery kanałów otwartych przez drugą stronę. Kanały otwierane
+    /// równolegle mogą wysłać pierwszą ramkę w innej kolejności niż numery.
+    peer_ids: ReplayWindow,
+    /// Błąd połączenia - po nim żaden kanał już nie działa.
+    error: Option<(ErrorKind, String)>,
+    /// Powiadomienie o zamykaniu serwera.
+    notice: Option<String>,
+}
+
+struct Slot {
+    /// Ile jeszcze możemy wysłać.
+    send_window: usize,
+    /// Ile jeszcze może przysłać druga strona.
+    recv_window: usize,
+    inbox: Sender<Chunk>,
+}
+
+impl Slot {
+    fn new(inbox: Sender<Chunk>) -> Self {
+        Slot { send_window: WINDOW_SIZE, recv_window: WINDOW_SIZE, inbox }
+    }
+}
+
+struct Chunk {
+    data: Vec<u8>,
+    end: bool,
+}
+
+impl Mux {
+    /// Uruchomienie kanałów na połączeniu po handshake.
+    /// Obie strony muszą obsługiwać `CAP_CHANNELS`.
+    pub fn new(conn: Connector) -> io::Result<Mux> {
+        if !conn.protocol().has(CAP_CHANNELS) {
+            return Err(Error::new(ErrorKind::Unsupported, "Channels were not negotiated."));
+        }
+        let parity = conn.is_client() as u32;
+        let encoding = conn.encoding();
+        let max_message = conn.max_frame();
+        let stream = conn.try_clone_transport()?;
+        // Limit czasu handshake nie może dotyczyć wątku odczytu.
+        stream.set_read_timeout(None)?;
+        let (reader, writer) = conn.split()?;
+
+        let shared = Arc::new(Shared {
+            writer: Mutex::new(writer),
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+            encoding,
+            max_message,
+            parity,
+        });
+        let (incoming, accepted) = mpsc::channel();
+        let handle = {
+            let shared = shared.clone();
+            thread::spawn(move || read_loop(reader, shared, incoming))
+        };
+        Ok(Mux {
+            shared,
+            incoming: Mutex::new(accepted),
+            stream: Mutex::new(stream),
+            reader: Some(handle),
+            next_id: AtomicU32::new(2 - parity),
+        })
+    } // fn new
+
+    /// Otwarcie nowego kanału.
+    pub fn open(&self) -> io::Result<Channel> {
+        let id = self.next_id.fetch_add(2, Relaxed);
+        let (inbox, rx) = mpsc::channel();
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some((kind, message)) = &state.error {
+            return Err(Error::new(*kind, message.clone()));
+        }
+        state.channels.insert(id, Slot::new(inbox));
+        Ok(Channel::new(id, self.shared.clone(), rx))
+    } // fn open
+
+    /// Wykonanie żądania w osobnym kanale.
+    /// Można wywoływać jednocześnie z wielu wątków.
+    pub fn request(&self, request: Request) -> io::Result<Answer> {
+        let mut channel = self.open()?;
+        channel.send_request(request)?;
+        channel.read_answer()
+    } // fn request
+
+    /// Oczekiwanie (co najwyżej `timeout`) na kanał otwarty przez drugą stronę.
+    /// Zwraca None, gdy czas upłynął. Zamknięcie połączenia to błąd.
+    pub fn accept(&self, timeout: Duration) -> io::Result<Option<Channel>> {
+        match self.incoming.lock().unwrap().recv_timeout(timeout) {
+            Ok(channel) => Ok(Some(channel)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(self.shared.error()),
+        }
+    } // fn accept
+
+    /// Powiadomienie klienta, że serwer kończy pracę.
+    pub fn send_shutdown_notice(&self) -> io::Result<()> {
+        let answer = Answer::new(0, SHUTDOWN_MESSAGE, SHUTDOWN_CMD);
+        let data = self.shared.encoding.encode_answer(&answer)?;
+        self.shared.send(CONTROL, END, &data)
+    } // fn send_shutdown_notice
+}
+
+impl Drop for Mux {
+    fn drop(&mut self) {
+        let _ = self.stream.lock().unwrap().shutdown();
+        if let Some(handle) = self.reader.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Odczyt ramek i rozdzielanie ich do kanałów, aż do zamknięcia połączenia.
+fn read_loop(mut frames: Frames, shared: Arc<Shared>, incoming: Sender<Channel>) {
+    loop {
+        let result = frames.read().and_then(|frame| dispatch(&shared, &incoming, &frame));
+        if let Err(err) = result {
+            shared.fail(err);
+            return;
+        }
+    }
+} // fn read_loop
+
+fn dispatch(shared: &Arc<Shared>, incoming: &Sender<Channel>, frame: &[u8]) -> io::Result<()> {
+    if frame.len() < 6 || frame[frame.len() - 1] != TRAILER {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid channel frame."));
+    }
+    let id = u32::from_be_bytes(frame[..4].try_into().unwrap());
+    let kind = frame[4];
+    let payload = &frame[5..frame.len() - 1];
+
+    let mut state = shared.state.lock().unwrap();
+    match kind {
+        END if id == CONTROL => {
+            let answer = shared.encoding.decode_answer(payload)?;
+            if answer.cmd == SHUTDOWN_CMD {
+                state.notice = Some(answer.message);
+            }
+        }
+        DATA | END => {
+            if !state.channels.contains_key(&id) {
+                // Ramka zamkniętego kanału albo nowy kanał drugiej strony.
+                // Numery drugiej strony to co drugi numer - w oknie kolejne.
+                if id == CONTROL || id % 2 == shared.parity || !state.peer_ids.accept((id as u64).div_ceil(2)) {
+                    return Ok(());
+                }
+                let (inbox, rx) = mpsc::channel();
+                state.channels.insert(id, Slot::new(inbox));
+                let _ = incoming.send(Channel::new(id, shared.clone(), rx));
+            }
+            let slot = state.channels.get_mut(&id).unwrap();
+            if payload.len() > slot.recv_window {
+                return Err(Error::new(ErrorKind::InvalidData, "Channel window exceeded."));
+            }
+            slot.recv_window -= payload.len();
+            let _ = slot.inbox.send(Chunk { data: payload.to_vec(), end: kind == END });
+        }
+        WINDOW => {
+            let credit = payload.try_into().map(u32::from_be_bytes)
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid window update."))?;
+            if let Some(slot) = state.channels.get_mut(&id) {
+                slot.send_window = slot.send_window.saturating_add(credit as usize);
+                shared.changed.notify_all();
+            }
+        }
+        CLOSE => {
+            state.channels.remove(&id);
+            shared.changed.notify_all();
+        }
+        _ => return Err(Error::new(ErrorKind::InvalidData, "Unknown channel frame.")),
+    }
+    Ok(())
+} // fn dispatch
+
+impl Shared {
+    fn send(&self, id: u32, kind: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(payload.len() + 6);
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.push(kind);
+        frame.extend_from_slice(payload);
+        frame.push(TRAILER);
+        self.writer.lock().unwrap().write(&frame)
+    }
+
+    /// Rezerwacja miejsca w oknie kanału (co najwyżej `want` bajtów).
+    /// Czeka, aż druga strona potwierdzi odebranie wcześniejszych danych.
+    fn reserve(&self, id: u32, want: usize) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if want == 0 {
+                return Ok(0);
+            }
+            if let Some((kind, message)) = &state.error {
+                return Err(Error::new(*kind, message.clone()));
+            }
+            match state.channels.get_mut(&id) {
+                None => return Err(Error::new(ErrorKind::ConnectionReset, "Channel closed by peer.")),
+                Some(slot) if slot.send_window > 0 => {
+                    let n = want.min(slot.send_window);
+                    slot.send_window -= n;
+                    return Ok(n);
+                }
+                Some(_) => state = self.changed.wait(state).unwrap(),
+            }
+        }
+    } // fn reserve
+
+    /// Potwierdzenie `n` odebranych bajtów kanału `id`. Kredyt wysyłamy,
+    /// gdy `consumed` (jeszcze niepotwierdzone) osiągnie `CREDIT_STEP`.
+    fn credit(&self, id: u32, consumed: &mut usize, n: usize) -> io::Result<()> {
+        *consumed += n;
+        if *consumed < CREDIT_STEP {
+            return Ok(());
+        }
+        let credit = std::mem::take(consumed);
+        match self.state.lock().unwrap().channels.get_mut(&id) {
+            Some(slot) => slot.recv_window += credit,
+            None => return Ok(()),
+        }
+        self.send(id, WINDOW, &(credit as u32).to_be_bytes())
+    } // fn credit
+
+    /// Błąd, który zakończył połączenie (lub zamknięcie kanału).
+    fn error(&self) -> Error {
+        match &self.state.lock().unwrap().error {
+            Some((kind, message)) => Error::new(*kind, message.clone()),
+            None => Error::new(ErrorKind::ConnectionReset, "Channel closed by peer."),
+        }
+    }
+
+    /// Zerwane połączenie kończy wszystkie kanały.
+    /// Jeśli serwer uprzedził o zamknięciu, zgłaszamy właśnie to.
+    fn fail(&self, err: Error) {
+        let mut state = self.state.lock().unwrap();
+        state.error = Some(match state.notice.take() {
+            Some(notice) => (ErrorKind::ConnectionAborted, notice),
+            None if err.kind() == ErrorKind::UnexpectedEof => (err.kind(), "Connection closed by peer.".into()),
+            None => (err.kind(), err.to_string()),
+        });
+        state.channels.clear();
+        self.changed.notify_all();
+    }
+}
+
+/// Kanał: dwukierunkowy strumień wiadomości.
+pub struct Channel {
+    id: u32,
+    shared: Arc<Shared>,
+    inbox: Receiver<Chunk>,
+    /// Odebrane dane, jeszcze niepotwierdzone drugiej stronie.
+    consumed: usize,
+    sent_end: bool,
+    recv_end: bool,
+}
+
+impl Channel {
+    fn new(id: u32, shared: Arc<Shared>, inbox: Receiver<Chunk>) -> Self {
+        Channel { id, shared, inbox, consumed: 0, sent_end: false, recv_end: false }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Wysłanie wiadomości (w częściach, w miarę wolnego miejsca w oknie).
+    pub fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut rest = data;
+        loop {
+            let n = self.shared.reserve(self.id, rest.len().min(MAX_CHUNK))?;
+            let (chunk, tail) = rest.split_at(n);
+            let kind = if tail.is_empty() { END } else { DATA };
+            self.shared.send(self.id, kind, chunk)?;
+            rest = tail;
+            if rest.is_empty() {
+                self.sent_end = true;
+                return Ok(());
+            }
+        }
+    } // fn send
+
+    /// Odebranie całej wiadomości.
+    pub fn recv(&mut self) -> io::Result<Vec<u8>> {
+        let mut message = Vec::new();
+        loop {
+            let chunk = self.inbox.recv().map_err(|_| self.shared.error())?;
+            let size = message.len() + chunk.data.len();
+            if size > self.shared.max_message {
+                return Err(FrameTooLarge { size, limit: self.shared.max_message }.into());
+            }
+            message.extend_from_slice(&chunk.data);
+            self.credit(chunk.data.len())?;
+            if chunk.end {
+                self.recv_end = true;
+                return Ok(message);
+            }
+        }
+    } // fn recv
+
+    /// Potwierdzenie odebranych danych - druga strona może wysłać kolejne.
+    fn credit(&mut self, n: usize) -> io::Result<()> {
+        self.shared.credit(self.id, &mut self.consumed, n)
+    }
+
+    /// Przeniesienie połączenia TCP przez kanał (przekierowanie portu),
+    /// aż obie strony zakończą wysyłanie danych. Koniec danych jednej strony
+    /// zamyka zapis do `stream` po drugiej stronie, błąd zamyka kanał.
+    /// Zwraca liczbę bajtów wysłanych i odebranych.
+    pub fn tunnel(mut self, stream: TcpStream) -> io::Result<(u64, u64)> {
+        let mut upstream = stream.try_clone()?;
+        // Poprzednia wymiana (żądanie otwarcia tunelu) jest już zakończona.
+        self.sent_end = false;
+        self.recv_end = false;
+        let mut downstream = Downstream {
+            id: self.id,
+            shared: self.shared.clone(),
+            inbox: std::mem::replace(&mut self.inbox, mpsc::channel().1),
+            consumed: std::mem::take(&mut self.consumed),
+        };
+        let handle = thread::spawn(move || downstream.run(stream));
+
+        let mut buffer = vec![0; MAX_CHUNK];
+        let mut sent = 0;
+        let result = loop {
+            match upstream.read(&mut buffer) {
+                Ok(0) => break self.send(&[]),
+                Ok(n) => match self.send(&buffer[..n]) {
+                    Ok(()) => sent += n as u64,
+                    Err(e) => break Err(e),
+                },
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => break Err(e),
+            }
+        };
+        if result.is_err() {
+            // Zamknięcie kanału kończy też odbiór.
+            self.close();
+        }
+        let received = handle.join().unwrap_or_else(|_| Err(Error::other("Tunnel thread panicked.")));
+        self.recv_end = received.is_ok();
+        result?;
+        Ok((sent, received?))
+    } // fn tunnel
+
+    /// Zamknięcie kanału po obu stronach.
+    fn close(&mut self) {
+        let known = self.shared.state.lock().unwrap().channels.remove(&self.id).is_some();
+        if known {
+            let _ = self.shared.send(self.id, CLOSE, &[]);
+        }
+    }
+
+    pub fn send_request(&mut self, mut request: Request) -> io::Result<()> {
+        request.set_id(self.id as u64);
+        let data = self.shared.encoding.encode_request(&request)?;
+        self.send(&data)
+    }
+
+    pub fn read_request(&mut self) -> io::Result<Request> {
+        let data = self.recv()?;
+        self.shared.encoding.decode_request(&data)
+    }
+
+    pub fn send_answer(&mut self, mut answer: Answer) -> io::Result<()> {
+        answer.set_id(self.id as u64);
+        let data = self.shared.encoding.encode_answer(&answer)?;
+        self.send(&data)
+    }
+
+    pub fn read_answer(&mut self) -> io::Result<Answer> {
+        let data = self.recv()?;
+        self.shared.encoding.decode_answer(&data)
+    }
+}
+
+impl Drop for Channel {
+    /// Porzucony kanał (wymiana nie zakończyła się w obie strony)
+    /// zamykamy także po drugiej stronie.
+    fn drop(&mut self) {
+        if self.sent_end && self.recv_end {
+            self.shared.state.lock().unwrap().channels.remove(&self.id);
+        } else {
+            self.close();
+        }
+    }
+}
+
+/// Odbierająca połowa tunelu: dane z kanału do połączenia TCP.
+struct Downstream {
+    id: u32,
+    shared: Arc<Shared>,
+    inbox: Receiver<Chunk>,
+    consumed: usize,
+}
+
+impl Downstream {
+    /// Zwraca liczbę odebranych bajtów.
+    fn run(&mut self, mut stream: TcpStream) -> io::Result<u64> {
+        let mut received = 0;
+        loop {
+            let result = self.inbox.recv()
+                .map_err(|_| self.shared.error())
+                .and_then(|chunk| {
+                    stream.write_all(&chunk.data)?;
+                    self.shared.credit(self.id, &mut self.consumed, chunk.data.len())?;
+                    Ok(chunk)
+                });
+            match result {
+                // Pusta wiadomość: druga strona nie wyśle już danych.
+                Ok(chunk) if chunk.end && chunk.data.is_empty() => {
+                    let _ = stream.shutdown(Shutdown::Write);
+                    return Ok(received);
+                }
+                Ok(chunk) => received += chunk.data.len() as u64,
+                Err(e) => {
+                    // Przerywa też odczyt w drugiej połowie tunelu.
+                    let _ = stream.shutdown(Shutdown::Both);
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Instant;
+    use crate::net::connector::ConnectionSide;
+
+    const BIG: usize = 2 * 1024 * 1024;
+
+    /// Para połączonych stron z kanałami: (klient, serwer).
+    fn pair() -> (Mux, Mux) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = Connector::new(stream, ConnectionSide::Server);
+            conn.init().unwrap();
+            Mux::new(conn).unwrap()
+        });
+        let mut conn = Connector::new(TcpStream::connect(addr).unwrap(), ConnectionSide::Client);
+        conn.init().unwrap();
+        (Mux::new(conn).unwrap(), server.join().unwrap())
+    }
+
+    /// Serwer obsługujący każdy kanał w osobnym wątku:
+    /// "big" odsyła duży plik, pozostałe polecenia - swoje parametry.
+    fn serve(server: Mux) -> JoinHandle<()> {
+        thread::spawn(move || {
+            while let Ok(Some(mut channel)) = server.accept(Duration::from_secs(10)) {
+                thread::spawn(move || {
+                    let request = channel.read_request().unwrap();
+                    let mut answer = Answer::new_with_data(0, "OK", &request.command, request.params);
+                    if request.command == "big" {
+                        answer.binary = (0..BIG).map(|i| (i % 253) as u8).collect();
+                    }
+                    let _ = channel.send_answer(answer);
+                });
+            }
+        })
+    }
+
+    #[test]
+    fn test_concurrent_channels() {
+        let (client, server) = pair();
+        let handle = serve(server);
+
+        let mut big = client.open().unwrap();
+        big.send_request(Request::new("big".into(), vec![])).unwrap();
+
+        // Serwer wysyła tylko tyle, ile mieści okno kanału...
+        let started = Instant::now();
+        let window = || client.shared.state.lock().unwrap().channels[&big.id()].recv_window;
+        while window() > 0 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+        // ... a inne kanały działają, choć duża odpowiedź nie została odebrana.
+        let answer = client.request(Request::new("echo".into(), vec!["a".into()])).unwrap();
+        assert_eq!(answer.data, ["a"]);
+
+        let answer = big.read_answer().unwrap();
+        assert_eq!(answer.binary.len(), BIG);
+        assert!(answer.binary.iter().enumerate().all(|(i, b)| *b == (i % 253) as u8));
+
+        drop(big);
+        drop(client);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_channels_out_of_order() {
+        let (client, server) = pair();
+        let handle = serve(server);
+        // Pierwsze ramki kanałów wysłane w odwrotnej kolejności.
+        let mut channels = (0..5).map(|_| client.open().unwrap()).collect::<Vec<_>>();
+        for (i, channel) in channels.iter_mut().enumerate().rev() {
+            channel.send_request(Request::new("echo".into(), vec![i.to_string()])).unwrap();
+        }
+        for (i, channel) in channels.iter_mut().enumerate() {
+            assert_eq!(channel.read_answer().unwrap().data, [i.to_string()]);
+        }
+        drop(channels);
+        drop(client);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_tunnel() {
+        let (client, server) = pair();
+        // Usługa docelowa: odsyła wszystko, co dostanie.
+        let echo = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = echo.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = echo.accept().unwrap();
+            io::copy(&mut stream.try_clone().unwrap(), &mut stream).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+        });
+        let remote = thread::spawn(move || {
+            let mut channel = server.accept(Duration::from_secs(10)).unwrap().unwrap();
+            channel.read_request().unwrap();
+            channel.send_answer(Answer::new(0, "OK", "forward")).unwrap();
+            let counts = channel.tunnel(TcpStream::connect(target).unwrap()).unwrap();
+            (counts, server)
+        });
+
+        // Aplikacja łączy się z lokalnym końcem tunelu.
+        let local = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut app = TcpStream::connect(local.local_addr().unwrap()).unwrap();
+        let mut channel = client.open().unwrap();
+        channel.send_request(Request::new("forward".into(), vec![])).unwrap();
+        assert_eq!(channel.read_answer().unwrap().code, 0);
+        let stream = local.accept().unwrap().0;
+        let tunnel = thread::spawn(move || channel.tunnel(stream).unwrap());
+
+        let data: Vec<u8> = (0..BIG).map(|i| (i % 251) as u8).collect();
+        let writer = {
+            let mut app = app.try_clone().unwrap();
+            let data = data.clone();
+            thread::spawn(move || {
+                app.write_all(&data).unwrap();
+                app.shutdown(Shutdown::Write).unwrap();
+            })
+        };
+        let mut echoed = Vec::new();
+        app.read_to_end(&mut echoed).unwrap();
+        writer.join().unwrap();
+        assert!(echoed == data);
+        assert_eq!(tunnel.join().unwrap(), (BIG as u64, BIG as u64));
+        assert_eq!(remote.join().unwrap().0, (BIG as u64, BIG as u64));
+        // Kanał zakończony w obie strony nie zostawia śladu.
+        assert!(client.shared.state.lock().unwrap().channels.is_empty());
+    }
+
+    #[test]
+    fn test_shutdown_notice() {
+        let (client, server) = pair();
+        server.send_shutdown_notice().unwrap();
+        drop(server);
+        let err = client.request(Request::new("echo".into(), vec![])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionAborted);
+        assert_eq!(err.to_string(), SHUTDOWN_MESSAGE);
+    }
+
+    #[test]
+    fn test_closed_channel() {
+        let (client, server) = pair();
+        let handle = thread::spawn(move || {
+            // Serwer porzuca kanał bez odpowiedzi.
+            let mut channel = server.accept(Duration::from_secs(10)).unwrap().unwrap();
+            channel.read_request().unwrap();
+            drop(channel);
+            server
+        });
+        let err = client.request(Request::new("echo".into(), vec![])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+        drop(handle.join().unwrap());
+        assert!(client.request(Request::new("echo".into(), vec![])).is_err());
+    }
+}
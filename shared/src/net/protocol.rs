@@ -0,0 +1,238 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Wersja protokołu i możliwości stron, uzgadniane w handshake.
+//!
+//! Klient za identyfikatorem wysyła `Hello`: zakres obsługiwanych wersji
+//! protokołu i flagi możliwości. Serwer wybiera najwyższą wspólną wersję
+//! i część wspólną możliwości, a wynik (`Protocol`) odsyła za kluczami.
+//!
+//! Wersja 1 to pierwotny handshake: sam identyfikator klienta
+//! i same klucze, żądania i odpowiedzi w JSON. Tak rozmawiamy
+//! ze starszymi klientami i serwerami.
+
+use std::fmt::{self, Display};
+use std::io::{self, Error, ErrorKind};
+use crate::data::wire::{Encoding, Reader, Writer};
+
+/// Najwyższa obsługiwana wersja protokołu.
+pub const VERSION: u32 = 2;
+/// Najniższa obsługiwana wersja protokołu.
+pub const MIN_VERSION: u32 = 1;
+
+/// Binarne kodowanie żądań i odpowiedzi (inaczej JSON).
+pub const CAP_BINARY: u32 = 1 << 0;
+/// Kompresja danych w ramkach.
+pub const CAP_COMPRESSION: u32 = 1 << 1;
+/// Strumieniowy przesył danych (wiele ramek na jedną odpowiedź).
+pub const CAP_STREAMING: u32 = 1 << 2;
+/// Kanały logiczne w jednym połączeniu (patrz `net::mux`).
+pub const CAP_CHANNELS: u32 = 1 << 3;
+/// Żądania wysyłane bez czekania na odpowiedzi, odpowiedzi dopasowywane po numerze.
+pub const CAP_PIPELINING: u32 = 1 << 4;
+/// Szyfr ramek: Blowfish CBC.
+pub const CAP_BLOWFISH: u32 = 1 << 8;
+/// Bity zarezerwowane dla szyfrów - strony muszą mieć co najmniej jeden wspólny.
+const CIPHERS: u32 = 0xff << 8;
+
+/// Możliwości tej implementacji.
+pub const CAPABILITIES: u32 = CAP_BINARY | CAP_COMPRESSION | CAP_CHANNELS | CAP_PIPELINING | CAP_BLOWFISH;
+
+/// Znacznik początku `Hello` ("HELO").
+const HELLO_MAGIC: u32 = 0x48454c4f;
+
+/// Oferta strony: zakres wersji i możliwości.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hello {
+    pub min_version: u32,
+    pub max_version: u32,
+    pub capabilities: u32,
+}
+
+impl Default for Hello {
+    fn default() -> Self {
+        Hello { min_version: MIN_VERSION, max_version: VERSION, capabilities: CAPABILITIES }
+    }
+}
+
+impl Hello {
+    /// Oferta klienta, który nie wysyła `Hello` (wersja 1).
+    pub const LEGACY: Hello = Hello { min_version: 1, max_version: 1, capabilities: CAP_BLOWFISH };
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::with_capacity(16);
+        w.u32(HELLO_MAGIC);
+        w.u32(self.min_version);
+        w.u32(self.max_version);
+        w.u32(self.capabilities);
+        w.finish()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        let mut r = Reader::new(data);
+        if r.u32()? != HELLO_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid hello."));
+        }
+        let hello = Hello { min_version: r.u32()?, max_version: r.u32()?, capabilities: r.u32()? };
+        r.finish()?;
+        Ok(hello)
+    }
+
+    /// Wybór protokołu przez serwer (self) dla oferty klienta.
+    /// Błąd to opis niezgodności, przeznaczony dla obu stron.
+    pub fn negotiate(&self, client: &Hello) -> Result<Protocol, String> {
+        let version = self.max_version.min(client.max_version);
+        if version < self.min_version.max(client.min_version) {
+            return Err(mismatch(client, self));
+        }
+        let capabilities = match version {
+            1 => Protocol::LEGACY.capabilities,
+            _ => self.capabilities & client.capabilities,
+        };
+        if capabilities & CIPHERS == 0 {
+            return Err("No cipher supported by both client and server.".into());
+        }
+        Ok(Protocol { version, capabilities })
+    }
+
+    /// Sprawdzenie przez klienta (self) protokołu wybranego przez serwer.
+    pub fn accept(&self, protocol: Protocol) -> io::Result<Protocol> {
+        if protocol.version < self.min_version || protocol.version > self.max_version {
+            let server = Hello { min_version: protocol.version, max_version: protocol.version, capabilities: 0 };
+            return Err(Error::new(ErrorKind::Unsupported, mismatch(self, &server)));
+        }
+        if protocol.capabilities & !self.capabilities != 0 || protocol.capabilities & CIPHERS == 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Server chose unsupported capabilities."));
+        }
+        Ok(protocol)
+    }
+
+    fn versions(&self) -> String {
+        match self.min_version == self.max_version {
+            true => self.min_version.to_string(),
+            false => format!("{}-{}", self.min_version, self.max_version),
+        }
+    }
+}
+
+fn mismatch(client: &Hello, server: &Hello) -> String {
+    format!("Incompatible protocol versions: client supports {}, server supports {}.",
+            client.versions(), server.versions())
+}
+
+/// Protokół uzgodniony w handshake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Protocol {
+    pub version: u32,
+    pub capabilities: u32,
+}
+
+impl Protocol {
+    /// Pierwotny protokół (wersja 1).
+    pub const LEGACY: Protocol = Protocol { version: 1, capabilities: CAP_BLOWFISH };
+    /// Rozmiar zapisu protokołu w odpowiedzi serwera.
+    pub const SIZE: usize = 9;
+
+    pub fn has(&self, capability: u32) -> bool {
+        self.capabilities & capability != 0
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        match self.has(CAP_BINARY) {
+            true => Encoding::Binary,
+            false => Encoding::Json,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::with_capacity(Self::SIZE);
+        w.u32(self.version);
+        w.u32(self.capabilities);
+        w.finish()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        let mut r = Reader::new(data);
+        let protocol = Protocol { version: r.u32()?, capabilities: r.u32()? };
+        r.finish()?;
+        Ok(protocol)
+    }
+}
+
+/// Np. "v2 binary,blowfish".
+impl Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (CAP_BINARY, "binary"),
+            (CAP_COMPRESSION, "compression"),
+            (CAP_STREAMING, "streaming"),
+            (CAP_CHANNELS, "channels"),
+            (CAP_PIPELINING, "pipelining"),
+            (CAP_BLOWFISH, "blowfish"),
+        ];
+        let caps = names.iter()
+            .filter(|(cap, _)| self.has(*cap))
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        write!(f, "v{} {}", self.version, caps.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(min_version: u32, max_version: u32, capabilities: u32) -> Hello {
+        Hello { min_version, max_version, capabilities }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let server = hello(1, 3, CAP_BINARY | CAP_COMPRESSION | CAP_BLOWFISH);
+        let p = server.negotiate(&hello(1, 2, CAP_BINARY | CAP_BLOWFISH)).unwrap();
+        assert_eq!(p, Protocol { version: 2, capabilities: CAP_BINARY | CAP_BLOWFISH });
+        assert_eq!(p.to_string(), "v2 binary,blowfish");
+        assert_eq!(Protocol::from_bytes(&p.to_bytes()).unwrap(), p);
+        assert_eq!(p.to_bytes().len(), Protocol::SIZE);
+
+        // Wersja 1 nie zna flag możliwości.
+        assert_eq!(server.negotiate(&Hello::LEGACY).unwrap(), Protocol::LEGACY);
+
+        let err = server.negotiate(&hello(4, 5, CAP_BLOWFISH)).unwrap_err();
+        assert_eq!(err, "Incompatible protocol versions: client supports 4-5, server supports 1-3.");
+        assert!(server.negotiate(&hello(1, 2, CAP_BINARY)).is_err());
+
+        let h = hello(2, 2, CAP_BLOWFISH);
+        assert_eq!(Hello::from_bytes(&h.to_bytes()).unwrap(), h);
+        assert!(Hello::from_bytes(&[0; 17]).is_err());
+    }
+
+    #[test]
+    fn test_accept() {
+        let client = hello(2, 2, CAP_BINARY | CAP_BLOWFISH);
+        assert!(client.accept(Protocol { version: 2, capabilities: CAP_BLOWFISH }).is_ok());
+        let err = client.accept(Protocol::LEGACY).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(err.to_string(), "Incompatible protocol versions: client supports 2, server supports 1.");
+        assert!(client.accept(Protocol { version: 2, capabilities: CAP_COMPRESSION | CAP_BLOWFISH }).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Ochrona przed powtórzeniem (replay) numerów żądań.
+//!
+//! Zamiast wymagać numerów kolejnych (id == poprzedni + 1) pamiętamy
+//! najwyższy przyjęty numer i mapę bitową `WINDOW` numerów poprzedzających.
+//! Numer jest przyjmowany raz, może przyjść z luką lub nie po kolei,
+//! ale nie może być starszy niż okno.
+
+/// Szerokość okna (liczba pamiętanych numerów).
+pub const WINDOW: u64 = 64;
+
+pub struct ReplayWindow {
+    highest: u64,
+    /// Bit i: numer `highest - i` został już przyjęty.
+    seen: u64,
+}
+
+impl Default for ReplayWindow {
+    fn default() -> Self {
+        // Numer 0 nie jest używany.
+        ReplayWindow { highest: 0, seen: 1 }
+    }
+}
+
+impl ReplayWindow {
+    /// Przyjęcie numeru. Zwraca false dla numeru powtórzonego
+    /// lub zbyt starego (poza oknem).
+    pub fn accept(&mut self, id: u64) -> bool {
+        if id > self.highest {
+            let shift = id - self.highest;
+            self.seen = if shift >= WINDOW { 0 } else { self.seen << shift };
+            self.seen |= 1;
+            self.highest = id;
+            return true;
+        }
+        let offset = self.highest - id;
+        if offset >= WINDOW || self.seen & (1 << offset) != 0 {
+            return false;
+        }
+        self.seen |= 1 << offset;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window() {
+        let mut window = ReplayWindow::default();
+        assert!(!window.accept(0));
+        assert!(window.accept(1));
+        assert!(!window.accept(1));
+        // Nie po kolei i z luką.
+        assert!(window.accept(5));
+        assert!(window.accept(3));
+        assert!(window.accept(2));
+        assert!(!window.accept(3));
+        assert!(window.accept(4));
+        // Numer zbyt stary.
+        assert!(window.accept(100));
+        assert!(!window.accept(100 - WINDOW));
+        assert!(window.accept(100 - WINDOW + 1));
+        assert!(!window.accept(100 - WINDOW + 1));
+        // Duży skok czyści mapę.
+        assert!(window.accept(1000));
+        assert!(window.accept(999));
+        assert!(!window.accept(1000));
+    }
+}
@@ -0,0 +1,129 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Lokalny serwer proxy SOCKS5 (RFC 1928) na kanałach sesji.
+//!
+//! Obsługujemy tylko polecenie CONNECT bez uwierzytelniania. Połączenie
+//! z celem nawiązuje serwer (żądanie `forward`, jak dla `L:`), więc decyduje
+//! jego polityka, a nazwy hostów są rozwiązywane po stronie serwera.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, TcpStream};
+use crate::data::request::Request;
+use crate::net::forward::FORWARD_CMD;
+use crate::net::mux::Mux;
+use crate::xerror::Error;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+const CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// Kody odpowiedzi.
+pub const SUCCEEDED: u8 = 0;
+pub const GENERAL_FAILURE: u8 = 1;
+pub const NOT_ALLOWED: u8 = 2;
+pub const NETWORK_UNREACHABLE: u8 = 3;
+pub const HOST_UNREACHABLE: u8 = 4;
+pub const CONNECTION_REFUSED: u8 = 5;
+pub const COMMAND_NOT_SUPPORTED: u8 = 7;
+pub const ADDRESS_NOT_SUPPORTED: u8 = 8;
+
+/// Obsługa jednego klienta proxy: handshake, połączenie przez serwer i tunel.
+pub fn serve(mux: &Mux, mut stream: TcpStream) -> io::Result<()> {
+    let (host, port) = handshake(&mut stream)?;
+    let mut channel = mux.open()?;
+    channel.send_request(Request::new(FORWARD_CMD.into(), vec![host, port.to_string()]))?;
+    let answer = channel.read_answer()?;
+    if answer.code != 0 {
+        reply(&mut stream, reply_code(answer.code))?;
+        return Err(io::Error::other(Error::from(answer).msg));
+    }
+    reply(&mut stream, SUCCEEDED)?;
+    channel.tunnel(stream).map(|_| ())
+} // fn serve
+
+/// Wybór metody i żądanie CONNECT. Zwraca cel (host, port).
+/// Na żądania, których nie obsługujemy, odpowiadamy błędem.
+fn handshake(stream: &mut TcpStream) -> io::Result<(String, u16)> {
+    let [version, count] = read_array(stream)?;
+    if version != VERSION {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("unsupported SOCKS version {}", version)));
+    }
+    let mut methods = vec![0; count as usize];
+    stream.read_exact(&mut methods)?;
+    if !methods.contains(&NO_AUTH) {
+        stream.write_all(&[VERSION, NO_ACCEPTABLE_METHODS])?;
+        return Err(io::Error::new(ErrorKind::PermissionDenied, "SOCKS client requires authentication"));
+    }
+    stream.write_all(&[VERSION, NO_AUTH])?;
+
+    let [version, command, _, atyp] = read_array(stream)?;
+    if version != VERSION {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("unsupported SOCKS version {}", version)));
+    }
+    let host = match atyp {
+        ATYP_IPV4 => Ipv4Addr::from(read_array::<4>(stream)?).to_string(),
+        ATYP_IPV6 => Ipv6Addr::from(read_array::<16>(stream)?).to_string(),
+        ATYP_DOMAIN => {
+            let [len] = read_array(stream)?;
+            let mut name = vec![0; len as usize];
+            stream.read_exact(&mut name)?;
+            String::from_utf8(name).map_err(|_| io::Error::new(ErrorKind::InvalidData, "invalid host name"))?
+        }
+        _ => {
+            reply(stream, ADDRESS_NOT_SUPPORTED)?;
+            return Err(io::Error::new(ErrorKind::Unsupported, format!("unsupported SOCKS address type {}", atyp)));
+        }
+    };
+    let port = u16::from_be_bytes(read_array(stream)?);
+    if command != CONNECT {
+        reply(stream, COMMAND_NOT_SUPPORTED)?;
+        return Err(io::Error::new(ErrorKind::Unsupported, format!("unsupported SOCKS command {}", command)));
+    }
+    Ok((host, port))
+} // fn handshake
+
+/// Odpowiedź na żądanie. Adresu po stronie serwera nie podajemy (0.0.0.0:0).
+fn reply(stream: &mut TcpStream, code: u8) -> io::Result<()> {
+    stream.write_all(&[VERSION, code, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+}
+
+/// Kod odpowiedzi dla błędu zgłoszonego przez serwer (errno albo odmowa polityki).
+fn reply_code(code: i32) -> u8 {
+    match code {
+        libc::EACCES | libc::EPERM => NOT_ALLOWED,
+        libc::ENETUNREACH => NETWORK_UNREACHABLE,
+        libc::EHOSTUNREACH | libc::ETIMEDOUT => HOST_UNREACHABLE,
+        libc::ECONNREFUSED => CONNECTION_REFUSED,
+        _ => GENERAL_FAILURE,
+    }
+}
+
+fn read_array<const N: usize>(stream: &mut TcpStream) -> io::Result<[u8; N]> {
+    let mut buffer = [0; N];
+    stream.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
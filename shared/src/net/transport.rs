@@ -0,0 +1,639 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::Child;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::time::{Duration, Instant};
+
+/// Strumień bajtów, po którym może działać `Connector`.
+/// Oprócz odczytu i zapisu transport musi umieć: podać opis obu stron,
+/// utworzyć drugi uchwyt do tego samego połączenia (osobne wątki
+/// odczytu i zapisu), ograniczyć czas odczytu, poczekać na dane
+/// i zamknąć połączenie (także z innego uchwytu).
+pub trait Transport: Read + Write + Send {
+    fn peer_addr(&self) -> io::Result<Endpoint>;
+    fn local_addr(&self) -> io::Result<Endpoint>;
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>>;
+    /// Limit czasu odczytu (None - bez limitu). Wspólny dla wszystkich uchwytów.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+    /// Oczekiwanie (co najwyżej `timeout`) na dane, bez ich odczytu.
+    /// Zwraca true, gdy można czytać, false gdy czas upłynął.
+    /// Zamknięcie połączenia przez drugą stronę to błąd UnexpectedEof
+    /// (albo true, jeśli transport nie potrafi go wykryć bez odczytu).
+    fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool>;
+    /// Zamknięcie połączenia - zablokowane odczyty kończą się.
+    fn shutdown(&self) -> io::Result<()>;
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn peer_addr(&self) -> io::Result<Endpoint> {
+        (**self).peer_addr()
+    }
+
+    fn local_addr(&self) -> io::Result<Endpoint> {
+        (**self).local_addr()
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        (**self).try_clone()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool> {
+        (**self).wait_for_data(timeout)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        (**self).shutdown()
+    }
+}
+
+/// Opis strony połączenia.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp(SocketAddr),
+    /// Gniazdo Unix (None - gniazdo bez nazwy, np. po stronie klienta).
+    Unix(Option<PathBuf>),
+    /// Standardowe wejście i wyjście procesu.
+    Stdio,
+    /// Proces potomny, z którym rozmawiamy przez jego wejście i wyjście.
+    Command(String),
+    /// Połączenie w pamięci (testy).
+    Memory,
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(addr) => write!(f, "{}", addr),
+            Endpoint::Unix(Some(path)) => write!(f, "unix:{}", path.display()),
+            Endpoint::Unix(None) => write!(f, "unix"),
+            Endpoint::Stdio => write!(f, "stdio"),
+            Endpoint::Command(command) => write!(f, "exec:{}", command),
+            Endpoint::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+//------- TCP ---------------------------------------------------
+
+impl Transport for TcpStream {
+    fn peer_addr(&self) -> io::Result<Endpoint> {
+        TcpStream::peer_addr(self).map(Endpoint::Tcp)
+    }
+
+    fn local_addr(&self) -> io::Result<Endpoint> {
+        TcpStream::local_addr(self).map(Endpoint::Tcp)
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(TcpStream::try_clone(self)?))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool> {
+        TcpStream::set_read_timeout(self, Some(timeout))?;
+        let mut buffer = [0u8; 1];
+        let result = self.peek(&mut buffer);
+        TcpStream::set_read_timeout(self, None)?;
+        match result {
+            Ok(0) => Err(closed()),
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => Ok(false),
+            Err(err) => Err(err),
+        }
+    } // fn wait_for_data
+
+    fn shutdown(&self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+//------- Gniazda Unix ------------------------------------------
+
+impl Transport for UnixStream {
+    fn peer_addr(&self) -> io::Result<Endpoint> {
+        UnixStream::peer_addr(self).map(|addr| Endpoint::Unix(addr.as_pathname().map(PathBuf::from)))
+    }
+
+    fn local_addr(&self) -> io::Result<Endpoint> {
+        UnixStream::local_addr(self).map(|addr| Endpoint::Unix(addr.as_pathname().map(PathBuf::from)))
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(UnixStream::try_clone(self)?))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool> {
+        if !poll_readable(self.as_raw_fd(), Some(timeout))? {
+            return Ok(false);
+        }
+        let mut buffer = [0u8; 1];
+        let flags = libc::MSG_PEEK | libc::MSG_DONTWAIT;
+        match unsafe { libc::recv(self.as_raw_fd(), buffer.as_mut_ptr().cast(), 1, flags) } {
+            0 => Err(closed()),
+            n if n > 0 => Ok(true),
+            _ => match Error::last_os_error() {
+                err if err.kind() == ErrorKind::WouldBlock => Ok(false),
+                err => Err(err),
+            },
+        }
+    } // fn wait_for_data
+
+    fn shutdown(&self) -> io::Result<()> {
+        UnixStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+/// Tożsamość procesu po drugiej stronie gniazda Unix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Odczyt tożsamości klienta gniazda Unix (`SO_PEERCRED`).
+/// Jądro zapamiętuje ją w chwili nawiązania połączenia, więc
+/// klient nie może jej podrobić.
+pub fn peer_credentials(stream: &UnixStream) -> io::Result<Credentials> {
+    let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut size = size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, (&mut cred as *mut libc::ucred).cast(), &mut size)
+    };
+    match result {
+        0 => Ok(Credentials { pid: cred.pid, uid: cred.uid, gid: cred.gid }),
+        _ => Err(Error::last_os_error()),
+    }
+} // fn peer_credentials
+
+//------- Standardowe wejście i wyjście -------------------------
+
+/// Połączenie przez parę deskryptorów: standardowe wejście i wyjście
+/// procesu (serwer uruchomiony przez inny kanał zdalnego wykonania,
+/// np. ssh) albo wejście i wyjście procesu potomnego (klient).
+/// Odczyt i zapis omijają bufory `std::io::Stdin`/`Stdout`.
+pub struct Stdio {
+    inner: Arc<Pipes>,
+    timeout: Arc<Mutex<Option<Duration>>>,
+}
+
+struct Pipes {
+    input: File,
+    output: File,
+    endpoint: Endpoint,
+    child: Mutex<Option<Child>>,
+}
+
+impl Stdio {
+    /// Standardowe wejście i wyjście tego procesu.
+    pub fn inherit() -> io::Result<Stdio> {
+        let input = io::stdin().as_fd().try_clone_to_owned()?;
+        let output = io::stdout().as_fd().try_clone_to_owned()?;
+        Ok(Stdio::new(Pipes { input: input.into(), output: output.into(), endpoint: Endpoint::Stdio, child: Mutex::new(None) }))
+    }
+
+    /// Wejście i wyjście procesu potomnego uruchomionego z `Stdio::piped()`.
+    /// Zamknięcie połączenia kończy proces.
+    pub fn child(mut child: Child, command: &str) -> io::Result<Stdio> {
+        let missing = || Error::new(ErrorKind::InvalidInput, "Child process without piped stdin/stdout.");
+        let input = OwnedFd::from(child.stdout.take().ok_or_else(missing)?);
+        let output = OwnedFd::from(child.stdin.take().ok_or_else(missing)?);
+        Ok(Stdio::new(Pipes {
+            input: input.into(),
+            output: output.into(),
+            endpoint: Endpoint::Command(command.to_string()),
+            child: Mutex::new(Some(child)),
+        }))
+    }
+
+    fn new(pipes: Pipes) -> Stdio {
+        Stdio { inner: Arc::new(pipes), timeout: Arc::new(Mutex::new(None)) }
+    }
+}
+
+impl Drop for Pipes {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Read for Stdio {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = *self.timeout.lock().unwrap();
+        if timeout.is_some() && !poll_readable(self.inner.input.as_raw_fd(), timeout)? {
+            return Err(Error::new(ErrorKind::WouldBlock, "Read timed out."));
+        }
+        (&self.inner.input).read(buf)
+    }
+}
+
+impl Write for Stdio {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.inner.output).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for Stdio {
+    fn peer_addr(&self) -> io::Result<Endpoint> {
+        Ok(self.inner.endpoint.clone())
+    }
+
+    fn local_addr(&self) -> io::Result<Endpoint> {
+        Ok(Endpoint::Stdio)
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(Stdio { inner: self.inner.clone(), timeout: self.timeout.clone() }))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        *self.timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    /// Potoki nie pozwalają podejrzeć danych - koniec połączenia
+    /// ujawni dopiero odczyt.
+    fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool> {
+        poll_readable(self.inner.input.as_raw_fd(), Some(timeout))
+    }
+
+    /// Proces potomny jest kończony. Standardowego wejścia i wyjścia nie
+    /// da się zamknąć z innego uchwytu, chyba że są gniazdami.
+    fn shutdown(&self) -> io::Result<()> {
+        if let Some(child) = self.inner.child.lock().unwrap().as_mut() {
+            return child.kill();
+        }
+        for fd in [self.inner.input.as_raw_fd(), self.inner.output.as_raw_fd()] {
+            unsafe { libc::shutdown(fd, libc::SHUT_RDWR) };
+        }
+        Ok(())
+    }
+}
+
+/// Oczekiwanie, aż z deskryptora będzie można czytać (także koniec danych).
+fn poll_readable(fd: RawFd, timeout: Option<Duration>) -> io::Result<bool> {
+    let mut pollfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+    let timeout = timeout.map_or(-1, |timeout| timeout.as_millis().min(i32::MAX as u128) as i32);
+    loop {
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            n if n >= 0 => return Ok(n > 0),
+            _ => match Error::last_os_error() {
+                err if err.kind() == ErrorKind::Interrupted => continue,
+                err => return Err(err),
+            },
+        }
+    }
+} // fn poll_readable
+
+//------- Połączenie w pamięci ----------------------------------
+
+/// Jeden koniec połączenia w pamięci (patrz `pipe`).
+/// Połączenie jest zamykane, gdy znikną wszystkie uchwyty jednego końca.
+pub struct MemoryPipe {
+    end: Arc<PipeEnd>,
+}
+
+struct PipeEnd {
+    incoming: Arc<Buffer>,
+    outgoing: Arc<Buffer>,
+    timeout: Mutex<Option<Duration>>,
+}
+
+#[derive(Default)]
+struct Buffer {
+    state: Mutex<BufferState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct BufferState {
+    data: VecDeque<u8>,
+    closed: bool,
+}
+
+/// Para połączonych ze sobą końców w pamięci, np. dla testów.
+pub fn pipe() -> (MemoryPipe, MemoryPipe) {
+    let (a, b) = (Arc::new(Buffer::default()), Arc::new(Buffer::default()));
+    let end = |incoming, outgoing| MemoryPipe {
+        end: Arc::new(PipeEnd { incoming, outgoing, timeout: Mutex::new(None) }),
+    };
+    (end(a.clone(), b.clone()), end(b, a))
+}
+
+impl Buffer {
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    /// Oczekiwanie na dane albo zamknięcie. None - czas upłynął.
+    fn wait(&self, timeout: Option<Duration>) -> Option<std::sync::MutexGuard<'_, BufferState>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state.lock().unwrap();
+        while state.data.is_empty() && !state.closed {
+            state = match deadline {
+                None => self.changed.wait(state).unwrap(),
+                Some(deadline) => {
+                    let left = deadline.checked_duration_since(Instant::now())?;
+                    self.changed.wait_timeout(state, left).unwrap().0
+                }
+            };
+        }
+        Some(state)
+    }
+}
+
+impl Drop for PipeEnd {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+impl Read for MemoryPipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = *self.end.timeout.lock().unwrap();
+        let mut state = self.end.incoming.wait(timeout)
+            .ok_or_else(|| Error::new(ErrorKind::WouldBlock, "Read timed out."))?;
+        let n = buf.len().min(state.data.len());
+        for (dst, src) in buf.iter_mut().zip(state.data.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MemoryPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.end.outgoing.state.lock().unwrap();
+        if state.closed {
+            return Err(Error::new(ErrorKind::BrokenPipe, "Pipe closed."));
+        }
+        state.data.extend(buf);
+        self.end.outgoing.changed.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MemoryPipe {
+    fn peer_addr(&self) -> io::Result<Endpoint> {
+        Ok(Endpoint::Memory)
+    }
+
+    fn local_addr(&self) -> io::Result<Endpoint> {
+        Ok(Endpoint::Memory)
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(MemoryPipe { end: self.end.clone() }))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        *self.end.timeout.lock().unwrap() = timeout;
+        Ok(())
+    }
+
+    fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool> {
+        match self.end.incoming.wait(Some(timeout)) {
+            None => Ok(false),
+            Some(state) if state.data.is_empty() => Err(closed()),
+            Some(_) => Ok(true),
+        }
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        self.end.incoming.close();
+        self.end.outgoing.close();
+        Ok(())
+    }
+}
+
+fn closed() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "Connection closed by peer.")
+}
+
+/// Liczniki bajtów odczytanych i zapisanych przez połączenie.
+#[derive(Default)]
+pub struct Traffic {
+    pub read: AtomicU64,
+    pub written: AtomicU64,
+}
+
+/// Transport zliczający przesłane bajty (wszystkie uchwyty - we wspólnym `Traffic`).
+pub struct Metered<T> {
+    inner: T,
+    traffic: Arc<Traffic>,
+}
+
+impl<T> Metered<T> {
+    pub fn new(inner: T, traffic: Arc<Traffic>) -> Self {
+        Metered { inner, traffic }
+    }
+}
+
+impl<T: Read> Read for Metered<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.traffic.read.fetch_add(n as u64, Relaxed);
+        Ok(n)
+    }
+}
+
+impl<T: Write> Write for Metered<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.traffic.written.fetch_add(n as u64, Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Transport> Transport for Metered<T> {
+    fn peer_addr(&self) -> io::Result<Endpoint> {
+        self.inner.peer_addr()
+    }
+
+    fn local_addr(&self) -> io::Result<Endpoint> {
+        self.inner.local_addr()
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(Metered::new(self.inner.try_clone()?, self.traffic.clone())))
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn wait_for_data(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.inner.wait_for_data(timeout)
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        self.inner.shutdown()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for Metered<T> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = std::pin::Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.traffic.read.fetch_add((buf.filled().len() - before) as u64, Relaxed);
+        poll
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: tokio::io::AsyncWrite + Unpin> tokio::io::AsyncWrite for Metered<T> {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let poll = std::pin::Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let std::task::Poll::Ready(Ok(n)) = poll {
+            self.traffic.written.fetch_add(n as u64, Relaxed);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::data::{answer::Answer, request::Request};
+    use crate::net::connector::{ConnectionSide, Connector};
+    use super::*;
+
+    #[test]
+    fn test_metered() {
+        let (a, mut b) = pipe();
+        let traffic = Arc::new(Traffic::default());
+        let mut a = Metered::new(a, traffic.clone());
+        let mut clone = a.try_clone().unwrap();
+        a.write_all(b"hello").unwrap();
+        clone.write_all(b"!").unwrap();
+        b.write_all(b"abc").unwrap();
+        let mut buf = [0; 3];
+        a.read_exact(&mut buf).unwrap();
+        assert_eq!((traffic.written.load(Relaxed), traffic.read.load(Relaxed)), (6, 3));
+    }
+
+    #[test]
+    fn test_memory_pipe() {
+        let (mut a, mut b) = pipe();
+        assert!(!b.wait_for_data(Duration::from_millis(10)).unwrap());
+        b.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        assert_eq!(b.read(&mut [0u8; 4]).unwrap_err().kind(), ErrorKind::WouldBlock);
+
+        a.write_all(b"abc").unwrap();
+        assert!(b.wait_for_data(Duration::from_millis(10)).unwrap());
+        let mut buffer = [0u8; 4];
+        assert_eq!(b.read(&mut buffer).unwrap(), 3);
+        assert_eq!(&buffer[..3], b"abc");
+
+        // Uchwyt z try_clone nie zamyka połączenia, ostatni uchwyt tak.
+        let clone = a.try_clone().unwrap();
+        drop(a);
+        assert!(!b.wait_for_data(Duration::from_millis(10)).unwrap());
+        drop(clone);
+        assert_eq!(b.wait_for_data(Duration::from_millis(10)).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(b.read(&mut buffer).unwrap(), 0);
+        assert_eq!(b.write(b"x").unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_peer_credentials() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        let cred = peer_credentials(&a).unwrap();
+        assert_eq!(cred.uid, unsafe { libc::geteuid() });
+        assert_eq!(cred.pid, std::process::id() as i32);
+    }
+
+    /// Ten sam protokół (handshake, szyfrowanie, ramki) w pamięci.
+    #[test]
+    fn test_connector_over_pipe() {
+        let (client, server) = pipe();
+        let server = thread::spawn(move || {
+            let mut conn = Connector::new(server, ConnectionSide::Server);
+            conn.init().unwrap();
+            assert_eq!(conn.peer_addr().unwrap(), Endpoint::Memory);
+            let request = conn.read_request().unwrap();
+            conn.send_answer(Answer::new(0, &request.params.join(" "), &request.command)).unwrap();
+        });
+        let mut conn = Connector::new(client, ConnectionSide::Client);
+        conn.init().unwrap();
+        conn.send_request(Request::new("echo".into(), vec!["over".into(), "pipe".into()])).unwrap();
+        assert_eq!(conn.read_answer().unwrap().message, "over pipe");
+        server.join().unwrap();
+        let err = conn.wait_for_data(Duration::from_millis(100)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,215 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::ffi::{CStr, CString};
+use std::ops::Deref;
+use std::os::raw::c_int;
+use std::ptr::null_mut;
+use libsqlite3_sys as ffi;
+use super::params::Params;
+use super::row::FromRow;
+use super::statement::Statement;
+use super::types::{Result, Row, SQLiteError};
+
+/// Połączenie z bazą danych SQLite.
+pub struct Connection {
+    db: *mut ffi::sqlite3,
+}
+
+// Połączenie otwieramy w trybie SQLITE_OPEN_FULLMUTEX,
+// więc może być przekazywane między wątkami.
+unsafe impl Send for Connection {}
+
+impl Connection {
+    /// Otwarcie (lub utworzenie) bazy danych do odczytu i zapisu.
+    pub fn open(path: &str) -> Result<Self> {
+        Self::open_with_flags(path, ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE)
+    }
+
+    /// Otwarcie istniejącej bazy danych do odczytu i zapisu.
+    pub fn open_read_write(path: &str) -> Result<Self> {
+        Self::open_with_flags(path, ffi::SQLITE_OPEN_READWRITE)
+    }
+
+    /// Otwarcie istniejącej bazy danych tylko do odczytu.
+    pub fn open_read_only(path: &str) -> Result<Self> {
+        Self::open_with_flags(path, ffi::SQLITE_OPEN_READONLY)
+    }
+
+    /// Baza danych w pamięci (znika po zamknięciu połączenia).
+    pub fn open_in_memory() -> Result<Self> {
+        Self::open(":memory:")
+    }
+
+    fn open_with_flags(path: &str, flags: c_int) -> Result<Self> {
+        let c_path = CString::new(path)
+            .map_err(|_| SQLiteError::new(ffi::SQLITE_MISUSE, "path contains NUL byte".into()))?;
+        let mut db = null_mut();
+        unsafe {
+            let rc = ffi::sqlite3_open_v2(c_path.as_ptr(), &mut db, flags | ffi::SQLITE_OPEN_FULLMUTEX, std::ptr::null());
+            if rc != ffi::SQLITE_OK {
+                // Nawet przy błędzie SQLite zwykle tworzy uchwyt,
+                // z którego można odczytać opis błędu.
+                let err = match db.is_null() {
+                    true => SQLiteError::new(rc, Self::errstr(rc)),
+                    false => Self::error_of(db),
+                };
+                ffi::sqlite3_close(db);
+                return Err(err);
+            }
+            ffi::sqlite3_extended_result_codes(db, 1);
+        }
+        Ok(Connection { db })
+    }
+
+    /// Czas (w milisekundach) oczekiwania na zwolnienie blokady bazy
+    /// przez inny proces, zanim zostanie zgłoszony błąd SQLITE_BUSY.
+    pub fn busy_timeout(&self, ms: i32) -> Result<()> {
+        let rc = unsafe { ffi::sqlite3_busy_timeout(self.db, ms) };
+        self.check(rc)
+    }
+
+    /// Wykonanie jednego lub wielu poleceń SQL bez parametrów.
+    pub fn execute(&self, sql: &str) -> Result<()> {
+        let c_sql = CString::new(sql)
+            .map_err(|_| SQLiteError::new(ffi::SQLITE_MISUSE, "sql contains NUL byte".into()))?;
+        let rc = unsafe { ffi::sqlite3_exec(self.db, c_sql.as_ptr(), None, null_mut(), null_mut()) };
+        self.check(rc)
+    }
+
+    /// Wykonanie jednego polecenia SQL z parametrami.
+    /// Zwraca liczbę zmienionych wierszy.
+    pub fn execute_with<P: Params>(&self, sql: &str, params: P) -> Result<usize> {
+        let mut stmt = self.prepare(sql)?;
+        stmt.bind(params)?;
+        stmt.execute()
+    }
+
+    /// Wykonanie zapytania i odczyt wszystkich wierszy wyniku.
+    pub fn query<P: Params>(&self, sql: &str, params: P) -> Result<Vec<Row>> {
+        let mut stmt = self.prepare(sql)?;
+        stmt.bind(params)?;
+        stmt.rows().collect()
+    }
+
+    /// Wykonanie zapytania i odwzorowanie wszystkich wierszy wyniku na typ T.
+    pub fn query_as<T: FromRow>(&self, sql: &str, params: impl Params) -> Result<Vec<T>> {
+        let mut stmt = self.prepare(sql)?;
+        stmt.query_as(params)?.collect()
+    }
+
+    pub fn prepare(&self, sql: &str) -> Result<Statement<'_>> {
+        Statement::new(self, sql)
+    }
+
+    /// Identyfikator (rowid) ostatnio wstawionego wiersza.
+    pub fn last_insert_id(&self) -> i64 {
+        unsafe { ffi::sqlite3_last_insert_rowid(self.db) }
+    }
+
+    /// Liczba wierszy zmienionych przez ostatnie polecenie.
+    pub fn changes(&self) -> usize {
+        unsafe { ffi::sqlite3_changes(self.db) as usize }
+    }
+
+    /// Rozpoczęcie transakcji.
+    /// Transakcja, która nie została zatwierdzona, jest wycofywana
+    /// w momencie, gdy przestaje istnieć.
+    pub fn transaction(&mut self) -> Result<Transaction<'_>> {
+        self.execute("BEGIN")?;
+        Ok(Transaction { conn: self, finished: false })
+    }
+
+    pub(crate) fn handle(&self) -> *mut ffi::sqlite3 {
+        self.db
+    }
+
+    pub(crate) fn check(&self, rc: c_int) -> Result<()> {
+        match rc {
+            ffi::SQLITE_OK => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    /// Ostatni błąd zgłoszony przez to połączenie.
+    pub(crate) fn error(&self) -> SQLiteError {
+        Self::error_of(self.db)
+    }
+
+    fn error_of(db: *mut ffi::sqlite3) -> SQLiteError {
+        unsafe {
+            let code = ffi::sqlite3_extended_errcode(db);
+            let message = CStr::from_ptr(ffi::sqlite3_errmsg(db)).to_string_lossy().to_string();
+            SQLiteError::new(code, message)
+        }
+    }
+
+    fn errstr(rc: c_int) -> String {
+        unsafe { CStr::from_ptr(ffi::sqlite3_errstr(rc)).to_string_lossy().to_string() }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3_close(self.db); }
+    }
+}
+
+/********************************************************************
+ *                                                                  *
+ *                      T r a n s a c t i o n                       *
+ *                                                                  *
+ *******************************************************************/
+
+/// Otwarta transakcja. Przez nią (Deref) wykonujemy polecenia.
+pub struct Transaction<'c> {
+    conn: &'c mut Connection,
+    finished: bool,
+}
+
+impl Transaction<'_> {
+    pub fn commit(mut self) -> Result<()> {
+        self.finished = true;
+        self.conn.execute("COMMIT")
+    }
+
+    pub fn rollback(mut self) -> Result<()> {
+        self.finished = true;
+        self.conn.execute("ROLLBACK")
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.conn.execute("ROLLBACK");
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use libsqlite3_sys as ffi;
+use super::connection::Connection;
+use super::row::column;
+use super::types::{Result, SQLiteError, Timestamp};
+
+/// Tabela z historią zastosowanych migracji.
+const METADATA: &str = "
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+        version     INTEGER PRIMARY KEY,
+        description TEXT NOT NULL,
+        applied     INTEGER NOT NULL
+    );
+";
+
+/// Jeden krok zmiany schematu bazy danych.
+/// Wersje kolejnych kroków muszą rosnąć, zaczynając od 1.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Aktualna wersja schematu bazy (0 dla bazy bez migracji).
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    conn.execute(METADATA)?;
+    let mut rows = conn.query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_migrations", ())?;
+    match rows.pop() {
+        Some(mut row) => column(&mut row, "version"),
+        None => Ok(0),
+    }
+}
+
+/// Zastosowanie brakujących migracji w jednej transakcji.
+/// Jeśli którykolwiek krok się nie powiedzie, baza zostaje w stanie
+/// sprzed wywołania, a błąd wskazuje nieudany krok.
+/// Zwraca wersję schematu po migracji.
+pub fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<u32> {
+    check_order(migrations)?;
+    let current = schema_version(conn)?;
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+    if current > latest {
+        return Err(SQLiteError::new(
+            ffi::SQLITE_MISMATCH,
+            format!("database schema version {} is newer than supported version {}", current, latest)));
+    }
+    if current == latest {
+        return Ok(current);
+    }
+
+    let tr = conn.transaction()?;
+    for m in migrations.iter().filter(|m| m.version > current) {
+        tr.execute(m.sql)
+            .and_then(|_| tr.execute_with(
+                "INSERT INTO schema_migrations (version, description, applied) VALUES (?, ?, ?)",
+                (m.version, m.description, Timestamp::now())))
+            .map_err(|err| SQLiteError::new(
+                err.code,
+                format!("migration {} ({}) failed: {}", m.version, m.description, err.message)))?;
+    }
+    tr.commit()?;
+    Ok(latest)
+}
+
+fn check_order(migrations: &[Migration]) -> Result<()> {
+    for (i, m) in migrations.iter().enumerate() {
+        if m.version as usize != i + 1 {
+            return Err(SQLiteError::new(
+                ffi::SQLITE_MISUSE,
+                format!("migration {} ({}) is out of order, expected version {}", m.version, m.description, i + 1)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEPS: &[Migration] = &[
+        Migration { version: 1, description: "users", sql: "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)" },
+        Migration { version: 2, description: "email", sql: "ALTER TABLE users ADD COLUMN email TEXT" },
+    ];
+
+    #[test]
+    fn test_migrate() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        assert_eq!(migrate(&mut conn, &STEPS[..1]).unwrap(), 1);
+        assert_eq!(migrate(&mut conn, STEPS).unwrap(), 2);
+        assert_eq!(migrate(&mut conn, STEPS).unwrap(), 2);
+        conn.execute("INSERT INTO users (name, email) VALUES ('Piotr', 'piotr@example.com')").unwrap();
+
+        // Baza nowsza niż znane migracje.
+        assert!(migrate(&mut conn, &STEPS[..1]).is_err());
+    }
+
+    #[test]
+    fn test_failed_step() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let broken = [
+            Migration { version: 1, description: "users", sql: "CREATE TABLE users (id INTEGER PRIMARY KEY)" },
+            Migration { version: 2, description: "broken", sql: "ALTER TABLE missing ADD COLUMN x" },
+        ];
+        let err = migrate(&mut conn, &broken).err().unwrap();
+        assert!(err.message.starts_with("migration 2 (broken) failed:"));
+        // Nic nie zostało zastosowane, także krok 1.
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        assert!(conn.prepare("SELECT * FROM users").is_err());
+
+        let gap = [Migration { version: 2, description: "gap", sql: "" }];
+        assert!(migrate(&mut conn, &gap).is_err());
+    }
+}
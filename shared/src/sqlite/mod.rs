@@ -1,2 +1,17 @@
-mod value;
-mod types;
\ No newline at end of file
+pub mod value;
+pub mod types;
+pub mod connection;
+pub mod statement;
+pub mod params;
+pub mod row;
+pub mod migrate;
+pub mod record;
+
+pub use connection::{Connection, Transaction};
+pub use migrate::{migrate, schema_version, Migration};
+pub use params::Params;
+pub use record::{decode_row, decode_rows, encode_row, encode_rows, RowStream};
+pub use row::{column, FromRow};
+pub use statement::{MappedRows, RowMapper, Rows, Statement};
+pub use types::{Result, Row, SQLiteError, Timestamp, ValueType};
+pub use value::Value;
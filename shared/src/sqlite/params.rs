@@ -0,0 +1,101 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use super::statement::Statement;
+use super::types::Result;
+use super::value::Value;
+
+/// Parametry polecenia SQL.
+///
+/// Pozycyjne (?1, ?2, ...): krotka wartości, np. `(1, "Piotr")`,
+/// albo tablica / wektor gotowych wartości `Value`. Brak parametrów: `()`.
+///
+/// Nazwane (:name, @name, $name): krotka par, np. `((":id", 1), (":name", "Piotr"))`,
+/// albo tablica par `(&str, Value)`.
+pub trait Params {
+    fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()>;
+}
+
+impl Params for () {
+    fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()> {
+        stmt.bind_values(&[])
+    }
+}
+
+impl Params for &[Value] {
+    fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()> {
+        stmt.bind_values(self)
+    }
+}
+
+impl<const N: usize> Params for &[Value; N] {
+    fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()> {
+        stmt.bind_values(self)
+    }
+}
+
+impl Params for Vec<Value> {
+    fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()> {
+        stmt.bind_values(&self)
+    }
+}
+
+impl Params for &[(&str, Value)] {
+    fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()> {
+        stmt.bind_named(self)
+    }
+}
+
+impl<const N: usize> Params for [(&str, Value); N] {
+    fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()> {
+        stmt.bind_named(&self)
+    }
+}
+
+macro_rules! tuple_params {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($name: Into<Value>),+> Params for ($($name,)+) {
+            fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()> {
+                let ($($name,)+) = self;
+                stmt.bind_values(&[$($name.into()),+])
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($name: Into<Value>),+> Params for ($((&str, $name),)+) {
+            fn bind_to(self, stmt: &mut Statement<'_>) -> Result<()> {
+                let ($($name,)+) = self;
+                stmt.bind_named(&[$(($name.0, $name.1.into())),+])
+            }
+        }
+    };
+}
+
+tuple_params!(A);
+tuple_params!(A, B);
+tuple_params!(A, B, C);
+tuple_params!(A, B, C, D);
+tuple_params!(A, B, C, D, E);
+tuple_params!(A, B, C, D, E, F);
+tuple_params!(A, B, C, D, E, F, G);
+tuple_params!(A, B, C, D, E, F, G, H);
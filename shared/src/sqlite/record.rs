@@ -0,0 +1,260 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Binarny format wierszy (rekordów).
+//!
+//! Rekord (jeden wiersz):
+//! ```text
+//! u32                 liczba kolumn
+//! dla każdej kolumny (w kolejności nazw):
+//!   u32               długość nazwy
+//!   [u8]              nazwa (UTF-8)
+//!   [u8]              wartość w formacie Value::to_bytes
+//! ```
+//! Strumień rekordów to rekordy zapisane jeden za drugim,
+//! każdy poprzedzony swoją długością (u32).
+//! Wszystkie liczby zapisywane są w kolejności big-endian.
+
+use libsqlite3_sys as ffi;
+use super::types::{Result, Row, SQLiteError};
+use super::value::Value;
+
+const U32_SIZE: usize = size_of::<u32>();
+/// Najmniejszy możliwy rozmiar kolumny: długość nazwy i marker typu.
+const MIN_COLUMN_SIZE: usize = U32_SIZE + 1;
+
+/// Zapis wiersza jako rekordu.
+/// Kolumny są sortowane według nazw, więc ten sam wiersz daje zawsze te same bajty.
+pub fn encode_row(row: &Row) -> Vec<u8> {
+    let mut names = row.keys().collect::<Vec<_>>();
+    names.sort();
+
+    let mut buffer = Vec::with_capacity(U32_SIZE + row.len() * 16);
+    buffer.extend_from_slice(&(row.len() as u32).to_be_bytes());
+    for name in names {
+        buffer.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(name.as_bytes());
+        buffer.extend_from_slice(&row[name].to_bytes());
+    }
+    buffer
+}
+
+/// Odczyt wiersza z rekordu. Dane muszą zawierać dokładnie jeden rekord.
+pub fn decode_row(data: &[u8]) -> Result<Row> {
+    let mut reader = Reader { data, pos: 0 };
+    let row = reader.row()?;
+    if reader.pos != data.len() {
+        return Err(corrupted(format!("{} trailing bytes after record", data.len() - reader.pos)));
+    }
+    Ok(row)
+}
+
+/// Zapis strumienia wierszy.
+pub fn encode_rows<'a>(rows: impl IntoIterator<Item = &'a Row>) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    for row in rows {
+        let record = encode_row(row);
+        buffer.extend_from_slice(&(record.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(&record);
+    }
+    buffer
+}
+
+/// Odczyt wszystkich wierszy ze strumienia.
+pub fn decode_rows(data: &[u8]) -> Result<Vec<Row>> {
+    RowStream::new(data).collect()
+}
+
+/// Iterator po wierszach strumienia rekordów.
+/// Po pierwszym błędzie iteracja się kończy.
+pub struct RowStream<'a> {
+    reader: Reader<'a>,
+    failed: bool,
+}
+
+impl<'a> RowStream<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        RowStream { reader: Reader { data, pos: 0 }, failed: false }
+    }
+}
+
+impl Iterator for RowStream<'_> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.reader.remaining() == 0 {
+            return None;
+        }
+        let result = self.reader.u32()
+            .and_then(|len| self.reader.take(len as usize))
+            .and_then(decode_row);
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+fn corrupted(message: String) -> SQLiteError {
+    SQLiteError::new(ffi::SQLITE_CORRUPT, message)
+}
+
+/// Odczyt kolejnych elementów rekordu z kontrolą długości danych.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.remaining() {
+            return Err(corrupted(format!("truncated record: {} bytes needed, {} available", n, self.remaining())));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(U32_SIZE)?.try_into().unwrap()))
+    }
+
+    fn row(&mut self) -> Result<Row> {
+        let count = self.u32()? as usize;
+        // Liczba kolumn pochodzi z danych, więc zanim zarezerwujemy
+        // dla nich pamięć, sprawdzamy, czy w ogóle mogą się zmieścić.
+        if count > self.remaining() / MIN_COLUMN_SIZE {
+            return Err(corrupted(format!("record declares {} columns in {} bytes", count, self.remaining())));
+        }
+        let mut row = Row::with_capacity(count);
+        for _ in 0..count {
+            let len = self.u32()? as usize;
+            let name = String::from_utf8(self.take(len)?.to_vec())
+                .map_err(|_| corrupted("column name is not valid UTF-8".into()))?;
+            let (value, used) = Value::decode(&self.data[self.pos..])?;
+            self.pos += used;
+            if row.insert(name, value).is_some() {
+                return Err(corrupted("duplicate column name in record".into()));
+            }
+        }
+        Ok(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            Just(Value::Null),
+            any::<i64>().prop_map(Value::Integer),
+            any::<f64>().prop_map(Value::Real),
+            ".*".prop_map(Value::Text),
+            proptest::collection::vec(any::<u8>(), 0..64).prop_map(Value::Blob),
+        ]
+    }
+
+    fn row() -> impl Strategy<Value = Row> {
+        proptest::collection::hash_map("[a-z_]{0,12}", value(), 0..8)
+    }
+
+    // Wartości porównujemy przez ich bajty (NaN != NaN).
+    fn same(a: &Row, b: &Row) -> bool {
+        encode_row(a) == encode_row(b)
+    }
+
+    proptest! {
+        #[test]
+        fn value_roundtrip(v in value()) {
+            let bytes = v.to_bytes();
+            prop_assert_eq!(Value::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+        }
+
+        #[test]
+        fn rows_roundtrip(rows in proptest::collection::vec(row(), 0..8)) {
+            let decoded = decode_rows(&encode_rows(&rows)).unwrap();
+            prop_assert_eq!(decoded.len(), rows.len());
+            for (a, b) in rows.iter().zip(&decoded) {
+                prop_assert!(same(a, b));
+            }
+        }
+
+        #[test]
+        fn truncated_is_error(r in row(), cut in any::<prop::sample::Index>()) {
+            let bytes = encode_row(&r);
+            let cut = cut.index(bytes.len());
+            prop_assert!(decode_row(&bytes[..cut]).is_err());
+        }
+
+        #[test]
+        fn garbage_does_not_panic(data in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let _ = decode_rows(&data);
+            let _ = Value::from_bytes(&data);
+        }
+    }
+
+    #[test]
+    fn test_value_errors() {
+        // Dawny błąd: tekst odczytywany był z data[5..len] zamiast data[5..5+len].
+        let text = Value::Text("Piotr".into()).to_bytes();
+        assert!(matches!(Value::from_bytes(&text).unwrap(), Value::Text(s) if s == "Piotr"));
+
+        assert!(Value::from_bytes(&[]).is_err());
+        assert!(Value::from_bytes(&[9]).is_err());
+        assert!(Value::from_bytes(&[1, 0, 0]).is_err());
+        assert!(Value::from_bytes(&[3, 0, 0, 0, 10, b'a']).is_err());
+        assert!(Value::from_bytes(&[3, 0, 0, 0, 1, 0xff]).is_err());
+        assert!(Value::from_bytes(&[0, 0]).is_err());
+
+        let mut two = Value::Integer(7).to_bytes();
+        two.extend(Value::Null.to_bytes());
+        let (first, used) = Value::decode(&two).unwrap();
+        assert!(matches!(first, Value::Integer(7)));
+        assert!(matches!(Value::from_bytes(&two[used..]).unwrap(), Value::Null));
+    }
+
+    #[test]
+    fn test_record_errors() {
+        // Ogromna liczba kolumn nie może powodować rezerwacji pamięci.
+        assert!(decode_row(&[0xff, 0xff, 0xff, 0xff]).is_err());
+
+        let mut row = Row::new();
+        row.insert("a".into(), Value::Integer(1));
+        let record = encode_row(&row);
+        // Dwa razy ta sama kolumna.
+        let mut twice = vec![0, 0, 0, 2];
+        twice.extend_from_slice(&record[4..]);
+        twice.extend_from_slice(&record[4..]);
+        assert!(decode_row(&twice).is_err());
+
+        // Strumień kończy się na pierwszym błędzie.
+        let mut stream = encode_rows([&row, &row]);
+        stream.truncate(stream.len() - 1);
+        let results = RowStream::new(&stream).collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok() && results[1].is_err());
+    }
+}
@@ -0,0 +1,137 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::convert::Infallible;
+use libsqlite3_sys as ffi;
+use super::types::{Result, Row, SQLiteError};
+use super::value::Value;
+
+/// Odwzorowanie wiersza wyniku na typ użytkownika.
+/// Dla zwykłych struktur implementację generuje makro `from_row!`.
+pub trait FromRow: Sized {
+    fn from_row(row: Row) -> Result<Self>;
+}
+
+impl FromRow for Row {
+    fn from_row(row: Row) -> Result<Self> {
+        Ok(row)
+    }
+}
+
+/// Wyjęcie z wiersza wartości kolumny z konwersją do oczekiwanego typu.
+/// Brak kolumny w wierszu jest błędem (także dla Option<T>).
+pub fn column<T>(row: &mut Row, name: &str) -> Result<T>
+where
+    T: TryFrom<Value>,
+    SQLiteError: From<T::Error>,
+{
+    let value = row.remove(name)
+        .ok_or_else(|| SQLiteError::new(ffi::SQLITE_RANGE, format!("no such column: {}", name)))?;
+    T::try_from(value)
+        .map_err(SQLiteError::from)
+        .map_err(|err| SQLiteError::new(err.code, format!("column {}: {}", name, err.message)))
+}
+
+// Dla column::<Value>, którego konwersja nie może się nie udać.
+impl From<Infallible> for SQLiteError {
+    fn from(err: Infallible) -> Self {
+        match err {}
+    }
+}
+
+/// Implementacja `FromRow` dla struktury, której pola nazywają się
+/// tak samo jak kolumny wyniku (typy pól muszą mieć `TryFrom<Value>`).
+///
+/// ```
+/// use shared::from_row;
+///
+/// struct User { id: i64, name: String, email: Option<String> }
+/// from_row!(User { id, name, email });
+/// ```
+#[macro_export]
+macro_rules! from_row {
+    ($type:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::sqlite::FromRow for $type {
+            fn from_row(mut row: $crate::sqlite::Row) -> $crate::sqlite::Result<Self> {
+                Ok($type {
+                    $($field: $crate::sqlite::column(&mut row, stringify!($field))?,)*
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sqlite::{Connection, Timestamp};
+
+    struct Person {
+        id: i64,
+        name: String,
+        email: Option<String>,
+        born: Timestamp,
+    }
+    crate::from_row!(Person { id, name, email, born });
+
+    #[test]
+    fn test_from_row() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE person (id INTEGER PRIMARY KEY, name TEXT, email TEXT, born INTEGER)").unwrap();
+        conn.execute_with("INSERT INTO person (name, email, born) VALUES (?, ?, ?)", ("Piotr", "piotr@example.com", 100)).unwrap();
+        conn.execute_with(
+            "INSERT INTO person (name, email, born) VALUES (:name, NULL, :born)",
+            ((":born", 200), (":name", "Anna"))).unwrap();
+
+        let people = conn.query_as::<Person>("SELECT * FROM person ORDER BY id", ()).unwrap();
+        assert_eq!(people.len(), 2);
+        assert_eq!((people[0].id, people[0].name.as_str()), (1, "Piotr"));
+        assert_eq!(people[0].email.as_deref(), Some("piotr@example.com"));
+        assert_eq!((people[1].name.as_str(), people[1].email.as_deref()), ("Anna", None));
+        assert_eq!(people[1].born.value(), 200);
+
+        // Brak kolumny lub zły typ wartości to błąd, a nie panika.
+        assert!(conn.query_as::<Person>("SELECT id, name, email FROM person", ()).is_err());
+        let err = conn.query_as::<Person>("SELECT id, born AS name, email, born FROM person", ()).err().unwrap();
+        assert!(err.message.starts_with("column name:"));
+    }
+
+    #[test]
+    fn test_query_map() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (n INTEGER)").unwrap();
+        for n in 1..=5 {
+            conn.execute_with("INSERT INTO t VALUES (?)", (n,)).unwrap();
+        }
+        let mut stmt = conn.prepare("SELECT n FROM t WHERE n > ? ORDER BY n").unwrap();
+        let squares = stmt.query_map((2,), |mut row| crate::sqlite::column::<i64>(&mut row, "n").map(|n| n * n))
+            .unwrap()
+            .collect::<crate::sqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(squares, [9, 16, 25]);
+
+        // Ponowne użycie tego samego polecenia z innymi parametrami.
+        let first = stmt.query_map((4,), |mut row| crate::sqlite::column::<i64>(&mut row, "n"))
+            .unwrap()
+            .next();
+        assert_eq!(first.unwrap().unwrap(), 5);
+    }
+}
@@ -0,0 +1,385 @@
+// MIT License
+// 
+// Copyright (c) 2025 Piotr Pszczółkowski
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr::{null, null_mut};
+use std::slice;
+use libsqlite3_sys as ffi;
+use super::connection::Connection;
+use super::params::Params;
+use super::row::FromRow;
+use super::types::{Result, Row, SQLiteError};
+use super::value::Value;
+
+/// Funkcja odwzorowująca wiersz na typ T (dla `query_as`).
+pub type RowMapper<T> = fn(Row) -> Result<T>;
+
+/// Przygotowane polecenie SQL.
+pub struct Statement<'c> {
+    conn: &'c Connection,
+    stmt: *mut ffi::sqlite3_stmt,
+}
+
+impl<'c> Statement<'c> {
+    pub(crate) fn new(conn: &'c Connection, sql: &str) -> Result<Self> {
+        let (stmt, tail) = Self::compile(conn, sql)?;
+        if stmt.is_null() {
+            // Tekst bez polecenia (np. same komentarze).
+            return Err(SQLiteError::new(ffi::SQLITE_MISUSE, "empty statement".into()));
+        }
+        let stmt = Statement { conn, stmt };
+        // Za poleceniem mogą być już tylko odstępy i komentarze.
+        if !tail.trim().is_empty() {
+            let (next, _) = Self::compile(conn, tail)?;
+            if !next.is_null() {
+                unsafe { ffi::sqlite3_finalize(next); }
+                return Err(SQLiteError::new(ffi::SQLITE_MISUSE, "only one statement is allowed".into()));
+            }
+        }
+        Ok(stmt)
+    }
+
+    // Kompilacja pierwszego polecenia z tekstu. Zwraca polecenie (null
+    // dla tekstu bez polecenia) i nieprzetworzoną resztę tekstu.
+    fn compile<'s>(conn: &Connection, sql: &'s str) -> Result<(*mut ffi::sqlite3_stmt, &'s str)> {
+        let mut stmt = null_mut();
+        let mut tail = null();
+        let rc = unsafe {
+            ffi::sqlite3_prepare_v2(
+                conn.handle(),
+                sql.as_ptr() as *const c_char,
+                sql.len() as c_int,
+                &mut stmt,
+                &mut tail)
+        };
+        conn.check(rc)?;
+        let used = match tail.is_null() {
+            true => sql.len(),
+            false => tail as usize - sql.as_ptr() as usize,
+        };
+        Ok((stmt, &sql[used..]))
+    }
+
+    /// Liczba parametrów polecenia.
+    pub fn parameter_count(&self) -> usize {
+        unsafe { ffi::sqlite3_bind_parameter_count(self.stmt) as usize }
+    }
+
+    /// Powiązanie parametrów polecenia (pozycyjnych lub nazwanych, patrz `Params`).
+    /// Wcześniejsze powiązania są usuwane, a polecenie wraca na początek.
+    pub fn bind<P: Params>(&mut self, params: P) -> Result<()> {
+        params.bind_to(self)
+    }
+
+    /// Powiązanie wartości z parametrami polecenia (kolejno ?1, ?2, ...).
+    pub fn bind_values(&mut self, params: &[Value]) -> Result<()> {
+        self.check_count(params.len())?;
+        params.iter()
+            .enumerate()
+            .try_for_each(|(i, value)| self.bind_value(i + 1, value))
+    }
+
+    /// Powiązanie wartości z parametrami nazwanymi (nazwa razem z prefiksem, np. ":id").
+    pub fn bind_named(&mut self, params: &[(&str, Value)]) -> Result<()> {
+        self.check_count(params.len())?;
+        params.iter()
+            .try_for_each(|(name, value)| {
+                let idx = self.parameter_index(name)?;
+                self.bind_value(idx, value)
+            })
+    }
+
+    /// Numer (od 1) parametru o podanej nazwie.
+    pub fn parameter_index(&self, name: &str) -> Result<usize> {
+        let not_found = || SQLiteError::new(ffi::SQLITE_RANGE, format!("no such parameter: {}", name));
+        let c_name = CString::new(name).map_err(|_| not_found())?;
+        match unsafe { ffi::sqlite3_bind_parameter_index(self.stmt, c_name.as_ptr()) } {
+            0 => Err(not_found()),
+            idx => Ok(idx as usize),
+        }
+    }
+
+    // Kontrola liczby parametrów i przygotowanie polecenia do nowych powiązań.
+    fn check_count(&mut self, n: usize) -> Result<()> {
+        if n != self.parameter_count() {
+            return Err(SQLiteError::new(
+                ffi::SQLITE_RANGE,
+                format!("expected {} parameters, got {}", self.parameter_count(), n)));
+        }
+        self.reset();
+        unsafe { ffi::sqlite3_clear_bindings(self.stmt); }
+        Ok(())
+    }
+
+    /// Powiązanie wartości z parametrem o wskazanym numerze (od 1).
+    pub fn bind_value(&mut self, idx: usize, value: &Value) -> Result<()> {
+        let idx = idx as c_int;
+        let rc = unsafe {
+            match value {
+                Value::Null => ffi::sqlite3_bind_null(self.stmt, idx),
+                Value::Integer(v) => ffi::sqlite3_bind_int64(self.stmt, idx, *v),
+                Value::Real(v) => ffi::sqlite3_bind_double(self.stmt, idx, *v),
+                Value::Text(v) => ffi::sqlite3_bind_text(
+                    self.stmt, idx,
+                    v.as_ptr() as *const c_char, v.len() as c_int,
+                    ffi::SQLITE_TRANSIENT()),
+                Value::Blob(v) if v.is_empty() => ffi::sqlite3_bind_zeroblob(self.stmt, idx, 0),
+                Value::Blob(v) => ffi::sqlite3_bind_blob(
+                    self.stmt, idx,
+                    v.as_ptr() as *const c_void, v.len() as c_int,
+                    ffi::SQLITE_TRANSIENT()),
+            }
+        };
+        self.conn.check(rc)
+    }
+
+    pub fn column_count(&self) -> usize {
+        unsafe { ffi::sqlite3_column_count(self.stmt) as usize }
+    }
+
+    /// Nazwy kolumn wyniku (w kolejności kolumn).
+    pub fn column_names(&self) -> Vec<String> {
+        (0..self.column_count())
+            .map(|i| unsafe {
+                CStr::from_ptr(ffi::sqlite3_column_name(self.stmt, i as c_int))
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    /// Czy polecenie nie zmienia zawartości bazy danych.
+    pub fn is_read_only(&self) -> bool {
+        unsafe { ffi::sqlite3_stmt_readonly(self.stmt) != 0 }
+    }
+
+    /// Wykonanie kolejnego kroku polecenia.
+    /// Zwraca true, jeśli dostępny jest kolejny wiersz wyniku.
+    pub fn step(&mut self) -> Result<bool> {
+        match unsafe { ffi::sqlite3_step(self.stmt) } {
+            ffi::SQLITE_ROW => Ok(true),
+            ffi::SQLITE_DONE => Ok(false),
+            _ => Err(self.conn.error()),
+        }
+    }
+
+    /// Wykonanie polecenia do końca (ewentualne wiersze są pomijane).
+    /// Zwraca liczbę zmienionych wierszy.
+    pub fn execute(&mut self) -> Result<usize> {
+        while self.step()? {}
+        Ok(self.conn.changes())
+    }
+
+    /// Powrót na początek polecenia (powiązania zostają).
+    pub fn reset(&mut self) {
+        unsafe { ffi::sqlite3_reset(self.stmt); }
+    }
+
+    /// Wartość kolumny bieżącego wiersza.
+    pub fn column_value(&self, i: usize) -> Value {
+        let i = i as c_int;
+        unsafe {
+            match ffi::sqlite3_column_type(self.stmt, i) {
+                ffi::SQLITE_INTEGER => Value::Integer(ffi::sqlite3_column_int64(self.stmt, i)),
+                ffi::SQLITE_FLOAT => Value::Real(ffi::sqlite3_column_double(self.stmt, i)),
+                ffi::SQLITE_TEXT => {
+                    let ptr = ffi::sqlite3_column_text(self.stmt, i);
+                    let n = ffi::sqlite3_column_bytes(self.stmt, i) as usize;
+                    let bytes = match ptr.is_null() {
+                        true => &[][..],
+                        false => slice::from_raw_parts(ptr, n),
+                    };
+                    Value::Text(String::from_utf8_lossy(bytes).to_string())
+                }
+                ffi::SQLITE_BLOB => {
+                    let ptr = ffi::sqlite3_column_blob(self.stmt, i) as *const u8;
+                    let n = ffi::sqlite3_column_bytes(self.stmt, i) as usize;
+                    match ptr.is_null() {
+                        true => Value::Blob(vec![]),
+                        false => Value::Blob(slice::from_raw_parts(ptr, n).to_vec()),
+                    }
+                }
+                _ => Value::Null,
+            }
+        }
+    }
+
+    /// Bieżący wiersz wyniku jako mapa: nazwa kolumny -> wartość.
+    /// Kolumny o tej samej nazwie (np. `SELECT a.id, b.id`) są błędem,
+    /// bo mapa zachowałaby tylko jedną z nich - trzeba je nazwać przez `AS`.
+    pub fn row(&self) -> Result<Row> {
+        let mut row = Row::new();
+        for (i, name) in self.column_names().into_iter().enumerate() {
+            if row.contains_key(&name) {
+                return Err(SQLiteError::new(ffi::SQLITE_ERROR, format!("duplicate column name: {} (rename it with AS)", name)));
+            }
+            row.insert(name, self.column_value(i));
+        }
+        Ok(row)
+    }
+
+    /// Iterator po (pozostałych) wierszach wyniku.
+    pub fn rows(&mut self) -> Rows<'_, 'c> {
+        Rows { stmt: self, done: false }
+    }
+
+    /// Wykonanie zapytania z parametrami. Zwraca iterator, który
+    /// każdy wiersz wyniku przekształca przekazaną funkcją.
+    pub fn query_map<P, T, F>(&mut self, params: P, f: F) -> Result<MappedRows<'_, 'c, F>>
+    where
+        P: Params,
+        F: FnMut(Row) -> Result<T>,
+    {
+        self.bind(params)?;
+        Ok(MappedRows { rows: self.rows(), f })
+    }
+
+    /// Wykonanie zapytania z parametrami. Zwraca iterator
+    /// po wierszach wyniku odwzorowanych na typ T.
+    pub fn query_as<P, T>(&mut self, params: P) -> Result<MappedRows<'_, 'c, RowMapper<T>>>
+    where
+        P: Params,
+        T: FromRow,
+    {
+        self.query_map(params, T::from_row as RowMapper<T>)
+    }
+}
+
+impl Drop for Statement<'_> {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3_finalize(self.stmt); }
+    }
+}
+
+/// Iterator po wierszach wyniku polecenia.
+/// Po pierwszym błędzie iteracja się kończy.
+pub struct Rows<'s, 'c> {
+    stmt: &'s mut Statement<'c>,
+    done: bool,
+}
+
+impl Iterator for Rows<'_, '_> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.stmt.step() {
+            Ok(true) => {
+                let row = self.stmt.row();
+                self.done = row.is_err();
+                Some(row)
+            }
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Iterator po wierszach wyniku przekształcanych funkcją użytkownika.
+pub struct MappedRows<'s, 'c, F> {
+    rows: Rows<'s, 'c>,
+    f: F,
+}
+
+impl<T, F> Iterator for MappedRows<'_, '_, F>
+where
+    F: FnMut(Row) -> Result<T>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next().map(|row| row.and_then(&mut self.f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (i INTEGER, r REAL, s TEXT, b BLOB, n TEXT)").unwrap();
+
+        let tr = conn.transaction().unwrap();
+        let mut stmt = tr.prepare("INSERT INTO t VALUES (?, ?, ?, ?, ?)").unwrap();
+        for i in 0..3 {
+            stmt.bind((i, 1.5, "Piotr", vec![0u8, 1, 2], Value::Null)).unwrap();
+            assert_eq!(stmt.execute().unwrap(), 1);
+        }
+        drop(stmt);
+        tr.commit().unwrap();
+
+        let rows = conn.query("SELECT * FROM t WHERE i >= ? ORDER BY i", (1,)).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(matches!(rows[0]["i"], Value::Integer(1)));
+        assert!(matches!(rows[0]["r"], Value::Real(v) if v == 1.5));
+        assert!(matches!(&rows[0]["s"], Value::Text(v) if v == "Piotr"));
+        assert!(matches!(&rows[0]["b"], Value::Blob(v) if v == &[0u8, 1, 2]));
+        assert!(matches!(rows[1]["n"], Value::Null));
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (i INTEGER)").unwrap();
+        {
+            let tr = conn.transaction().unwrap();
+            tr.execute_with("INSERT INTO t VALUES (?)", (1,)).unwrap();
+            // Bez commit - transakcja zostaje wycofana.
+        }
+        assert!(conn.query("SELECT * FROM t", ()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(conn.prepare("SELECT * FROM missing").is_err());
+        let mut stmt = conn.prepare("SELECT ?").unwrap();
+        assert!(stmt.bind(()).is_err());
+        assert!(stmt.bind([(":missing", Value::Null)]).is_err());
+        assert!(stmt.is_read_only());
+        assert!(conn.prepare("SELECT 1; -- comment").is_ok());
+        assert!(conn.prepare("SELECT 1; SELECT 2").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE a (id INTEGER); CREATE TABLE b (id INTEGER)").unwrap();
+        conn.execute("INSERT INTO a VALUES (1); INSERT INTO b VALUES (2)").unwrap();
+        let err = conn.query("SELECT a.id, b.id FROM a, b", ()).unwrap_err();
+        assert!(err.message.contains("duplicate column name: id"), "{:?}", err);
+        let rows = conn.query("SELECT a.id, b.id AS b_id FROM a, b", ()).unwrap();
+        assert!(matches!(rows[0]["id"], Value::Integer(1)));
+        assert!(matches!(rows[0]["b_id"], Value::Integer(2)));
+    }
+}
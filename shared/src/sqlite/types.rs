@@ -44,6 +44,8 @@ pub type Row = HashMap<String, Value>;
  *                                                                  *
  *******************************************************************/
 
+pub type Result<T> = std::result::Result<T, SQLiteError>;
+
 pub struct SQLiteError {
     pub message: String,
     pub code: i32,
@@ -61,6 +63,25 @@ impl Debug for SQLiteError {
     }
 }
 
+impl Display for SQLiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (sqlite code: {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for SQLiteError {}
+
+impl From<SQLiteError> for crate::xerror::Error {
+    fn from(err: SQLiteError) -> Self {
+        crate::xerror::Error {
+            src: crate::xerror::ErrSrc::SQLite,
+            code: err.code,
+            msg: err.message,
+            ..Default::default()
+        }
+    }
+}
+
 /********************************************************************
  *                                                                  *
  *                       V a l u e T y p e                          *
@@ -76,6 +97,7 @@ pub enum ValueType {
 }
 
 impl ValueType {
+    #[allow(clippy::self_named_constructors)]
     pub fn value_type(v: u8) -> Self {
         match v {
             1 => Self::Integer,
@@ -87,6 +109,22 @@ impl ValueType {
     }
 }
 
+/// Ścisła wersja `value_type`: nieznany marker jest błędem.
+impl TryFrom<u8> for ValueType {
+    type Error = u8;
+
+    fn try_from(v: u8) -> std::result::Result<Self, u8> {
+        match v {
+            0 => Ok(Self::Null),
+            1 => Ok(Self::Integer),
+            2 => Ok(Self::Real),
+            3 => Ok(Self::Text),
+            4 => Ok(Self::Blob),
+            _ => Err(v),
+        }
+    }
+}
+
 impl Display for ValueType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
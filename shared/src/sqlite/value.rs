@@ -23,13 +23,15 @@
 #![allow(unused_imports)]
 use std::mem;
 use chrono::NaiveDateTime;
-use super::types::{ ValueType, Timestamp };
+use serde::{Serialize, Deserialize};
+use libsqlite3_sys as ffi;
+use super::types::{ Result, SQLiteError, ValueType, Timestamp };
 
 static U32_SIZE: usize = size_of::<u32>();
 static I64_SIZE: usize = size_of::<i64>();
 static F64_SIZE: usize = size_of::<f64>();
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Value {
     Null,
     Integer(i64),
@@ -101,36 +103,60 @@ impl Value {
             },
         }
     }
-    pub fn from_bytes(data: &[u8]) -> Option<Value> {
-        let size = data.len();
-        if size == 0 {
-            return None;
+    /// Odtworzenie wartości z bajtów (dokładnie jednej wartości, patrz `to_bytes`).
+    pub fn from_bytes(data: &[u8]) -> Result<Value> {
+        let (value, used) = Self::decode(data)?;
+        if used != data.len() {
+            return Err(corrupted(format!("{} trailing bytes after value", data.len() - used)));
         }
-        
-        match ValueType::value_type(data[0]) {     // pierwszy bajt danych jest markerem typu
-            ValueType::Null => {
-                Some(Value::Null)
-            }
+        Ok(value)
+    }
+
+    /// Odczyt pierwszej wartości z ciągu bajtów.
+    /// Zwraca wartość i liczbę zużytych bajtów, dzięki czemu
+    /// można odczytywać kolejne wartości zapisane jedna za drugą.
+    pub fn decode(data: &[u8]) -> Result<(Value, usize)> {
+        // pierwszy bajt danych jest markerem typu
+        let Some(&marker) = data.first() else {
+            return Err(corrupted("no data".into()));
+        };
+        let kind = ValueType::try_from(marker)
+            .map_err(|_| corrupted(format!("unknown value type marker: {}", marker)))?;
+        let data = &data[1..];
+        match kind {
+            ValueType::Null => Ok((Value::Null, 1)),
             ValueType::Integer => {
-                Some(Value::Integer(i64::from_be_bytes(data[1..].try_into().unwrap())))
+                let v = take(data, I64_SIZE)?;
+                Ok((Value::Integer(i64::from_be_bytes(v.try_into().unwrap())), 1 + I64_SIZE))
             }
             ValueType::Real => {
-                Some(Value::Real(f64::from_be_bytes(data[1..].try_into().unwrap())))
+                let v = take(data, F64_SIZE)?;
+                Ok((Value::Real(f64::from_be_bytes(v.try_into().unwrap())), 1 + F64_SIZE))
             }
-            ValueType::Text => {
-                let len = u32::from_be_bytes(data[1..5].try_into().unwrap());
-                let text = String::from_utf8(data[5..len as usize].to_vec()).unwrap();
-                Some(Value::Text(text))
-            }
-            ValueType::Blob => {
-                let len = u32::from_be_bytes(data[1..5].try_into().unwrap());
-                let blob = data[5..len as usize].to_vec();
-                Some(Value::Blob(blob))
+            ValueType::Text | ValueType::Blob => {
+                let len = u32::from_be_bytes(take(data, U32_SIZE)?.try_into().unwrap()) as usize;
+                let bytes = take(&data[U32_SIZE..], len)?.to_vec();
+                let value = match kind {
+                    ValueType::Text => Value::Text(String::from_utf8(bytes)
+                        .map_err(|_| corrupted("text value is not valid UTF-8".into()))?),
+                    _ => Value::Blob(bytes),
+                };
+                Ok((value, 1 + U32_SIZE + len))
             }
         }
     }
 }
 
+fn corrupted(message: String) -> SQLiteError {
+    SQLiteError::new(ffi::SQLITE_CORRUPT, message)
+}
+
+/// Początkowe n bajtów danych lub błąd, jeśli danych jest za mało.
+fn take(data: &[u8], n: usize) -> Result<&[u8]> {
+    data.get(..n)
+        .ok_or_else(|| corrupted(format!("truncated value: {} bytes needed, {} available", n, data.len())))
+}
+
 impl From<Timestamp> for Value {
     fn from(tms: Timestamp) -> Self {
         Value::Integer(tms.value())
@@ -161,9 +187,15 @@ impl From<i32> for Value {
     }
 }
 
+impl From<u32> for Value {
+    fn from(v: u32) -> Self {
+        Value::Integer(i64::from(v))
+    }
+}
+
 impl From<i64> for Value {
     fn from(v: i64) -> Self {
-        Value::Integer(i64::from(v))
+        Value::Integer(v)
     }
 }
 
@@ -210,51 +242,108 @@ impl From<&[u8]> for Value {
 }
 
 // Value --> x
+//
+// Konwersje odwrotne mogą się nie udać (inny typ wartości w kolumnie),
+// dlatego zwracają błąd SQLITE_MISMATCH zamiast panikować.
+// Każdy typ ma również wariant Option<T>, dla którego Null daje None.
 
-impl From<Value> for i64 {
-    fn from(v: Value) -> Self {
-        match v {
-            Value::Integer(v) => v,
-            _ => panic!("Value::Integer expected"),
-        }
-    }
+fn mismatch(expected: &str, v: &Value) -> SQLiteError {
+    SQLiteError::new(ffi::SQLITE_MISMATCH, format!("{} expected, got {}", expected, v.kind()))
 }
 
-impl From<Value> for f64 {
-    fn from(v: Value) -> Self {
-        match v {
-            Value::Real(v) => v,
-            _ => panic!("Value::Real expected"),
-        }
-    }
-}
+macro_rules! try_from_value {
+    ($type:ty, $v:ident => $body:expr) => {
+        impl TryFrom<Value> for $type {
+            type Error = SQLiteError;
 
-impl From<Value> for String {
-    fn from(v: Value) -> Self {
-        match v {
-            Value::Text(v) => v,
-            _ => panic!("Value::Text expected"),
+            fn try_from($v: Value) -> Result<Self> {
+                $body
+            }
         }
-    }
-}
 
-impl From<Value> for Vec<u8> {
-    fn from(v: Value) -> Self {
-        match v {
-            Value::Blob(v) => v,
-            _ => panic!("Value::Blob expected"),
+        impl TryFrom<Value> for Option<$type> {
+            type Error = SQLiteError;
+
+            fn try_from(v: Value) -> Result<Self> {
+                match v {
+                    Value::Null => Ok(None),
+                    v => <$type>::try_from(v).map(Some),
+                }
+            }
         }
-    }
+    };
 }
 
-impl From<Value> for Timestamp {
-    fn from(v: Value) -> Self {
-        Timestamp::from(i64::from(v))
-    }
+macro_rules! try_from_integer {
+    ($($type:ty),*) => {
+        $(
+            try_from_value!($type, v => match v {
+                Value::Integer(n) => <$type>::try_from(n).map_err(|_| SQLiteError::new(
+                    ffi::SQLITE_RANGE,
+                    format!("{} out of range for {}", n, stringify!($type)))),
+                v => Err(mismatch("INT", &v)),
+            });
+        )*
+    };
 }
 
-impl From<Value> for NaiveDateTime {
-    fn from(v: Value) -> Self {
-        NaiveDateTime::parse_from_str(&String::from(v), "%Y-%m-%d %H:%M:%S").unwrap()
+try_from_integer!(u8, i16, i32, u32, i64, u64, usize);
+
+try_from_value!(bool, v => match v {
+    Value::Integer(n) => Ok(n != 0),
+    v => Err(mismatch("INT", &v)),
+});
+
+// Liczba całkowita w kolumnie REAL jest akceptowana
+// (SQLite może tak zapisać wartość bez części ułamkowej).
+try_from_value!(f64, v => match v {
+    Value::Real(n) => Ok(n),
+    Value::Integer(n) => Ok(n as f64),
+    v => Err(mismatch("REAL", &v)),
+});
+
+try_from_value!(String, v => match v {
+    Value::Text(s) => Ok(s),
+    v => Err(mismatch("TEXT", &v)),
+});
+
+try_from_value!(Vec<u8>, v => match v {
+    Value::Blob(b) => Ok(b),
+    v => Err(mismatch("BLOB", &v)),
+});
+
+try_from_value!(Timestamp, v => i64::try_from(v).map(Timestamp::from));
+
+try_from_value!(NaiveDateTime, v => {
+    let text = String::try_from(v)?;
+    NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S")
+        .map_err(|err| SQLiteError::new(ffi::SQLITE_MISMATCH, format!("{}: {}", text, err)))
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from() {
+        assert_eq!(i64::try_from(Value::Integer(-7)).unwrap(), -7);
+        assert_eq!(u8::try_from(Value::Integer(255)).unwrap(), 255);
+        assert!(u8::try_from(Value::Integer(256)).is_err());
+        assert!(u32::try_from(Value::Integer(-1)).is_err());
+        assert_eq!(f64::try_from(Value::Integer(2)).unwrap(), 2.0);
+        assert!(bool::try_from(Value::Integer(1)).unwrap());
+        assert_eq!(String::try_from(Value::from("Piotr")).unwrap(), "Piotr");
+
+        let err = i64::try_from(Value::from("12")).unwrap_err();
+        assert_eq!(err.code, ffi::SQLITE_MISMATCH);
+        assert!(String::try_from(Value::Null).is_err());
+
+        assert_eq!(Option::<i64>::try_from(Value::Null).unwrap(), None);
+        assert_eq!(Option::<i64>::try_from(Value::Integer(3)).unwrap(), Some(3));
+        assert!(Option::<String>::try_from(Value::Integer(3)).is_err());
+
+        let dt = NaiveDateTime::parse_from_str("2024-02-29 12:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(NaiveDateTime::try_from(Value::from(dt)).unwrap(), dt);
+        assert_eq!(Timestamp::try_from(Value::Integer(42)).unwrap().value(), 42);
     }
 }
@@ -24,7 +24,6 @@ extern crate libc;
 
 use std::cmp::Ordering;
 use std::ffi::{CStr, CString};
-use std::ptr::null_mut;
 use crate::ufs::fileinfo::FileInfo;
 use crate::xerror::{ Result, Error };
 
@@ -34,8 +33,6 @@ pub struct Dir;
 impl Dir {
     /// Odczyt zawartości wskazanego katalogu.
     pub fn read(path: &str, hidden_too: bool) -> Result<Vec<FileInfo>> {
-        eprintln!("{}", path);
-        
         unsafe {
             let mut files: Vec<FileInfo> = vec![];
             let dirp = Self::open_dir(path)?;
@@ -130,7 +127,7 @@ impl Dir {
         unsafe {
             let c_path = CString::new(path).unwrap();
             match libc::opendir(c_path.as_ptr()) {
-                ptr if ptr == null_mut() => Err(Error::from_errno()),
+                ptr if ptr.is_null() => Err(Error::from_errno()),
                 dirp => Ok(dirp),
             }
         }
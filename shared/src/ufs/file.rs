@@ -252,7 +252,7 @@ impl File {
 
         // Wyznaczenie liczby bajtów w pliku, Kursor pliku jest na początku pliku.
         let nbytes = self.size()?;
-        let mut buffer = Vec::with_capacity(nbytes);
+        let mut buffer = vec![0u8; nbytes];
         self.read_exact(&mut buffer)?;
         Ok(buffer)
     }
@@ -383,3 +383,23 @@ impl Drop for File {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_all_vec() {
+        let path = std::env::temp_dir().join(format!("shared-test-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut fh = File::new(path);
+        fh.create().unwrap();
+        fh.write(b"Yamato & Musashi").unwrap();
+        fh.close().unwrap();
+
+        fh.open_read_only().unwrap();
+        assert_eq!(fh.read_all_vec().unwrap(), b"Yamato & Musashi");
+        fh.close().unwrap();
+        rm(path).unwrap();
+    }
+}
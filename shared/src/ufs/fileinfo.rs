@@ -22,7 +22,6 @@
 #![allow(dead_code)]
 use std::ffi::{CStr, CString};
 use std::fmt::Debug;
-use std::ptr::null_mut;
 use crate::xerror::{Result, Error};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Local, Utc };
@@ -171,6 +170,7 @@ impl FileInfo {
     }
     
     /// Zamiana praw dostępu do pliku na postać tekstową,
+    #[allow(clippy::unnecessary_cast)]
     fn file_permission(mode: libc::mode_t) -> String {
         let mut buffer = String::new();
         match Self::ftype(mode as u32) {
@@ -255,10 +255,10 @@ impl FileInfo {
     }
 
     /// Odczyt nazwy użytkownika ze wskazanym uid.
-    fn user_name(uid: u32) -> Result<String> {
+    pub fn user_name(uid: u32) -> Result<String> {
         unsafe {
             match libc::getpwuid(uid) {
-                ptr if ptr == null_mut() => return Err(Error::from_errno()),
+                ptr if ptr.is_null() => Err(Error::from_errno()),
                 passwd => {
                     let name_cstr = CStr::from_ptr((*passwd).pw_name);
                     let name_str = CStr::from_ptr(name_cstr.as_ptr()).to_str().unwrap();
@@ -277,10 +277,10 @@ impl FileInfo {
     }
 
     /// Odczyt nazwy grupy ze wskazanych gid.
-    fn group_name(gid: u32) -> Result<String> {
+    pub fn group_name(gid: u32) -> Result<String> {
         unsafe {
             let group = libc::getgrgid(gid);
-            if group == null_mut() {
+            if group.is_null() {
                 return Err(Error::from_errno());
             }
             let name_cstr = CStr::from_ptr((*group).gr_name);
@@ -299,8 +299,8 @@ impl FileInfo {
         bufferr.push_str(&format!("\t      rozmiar bloku: {}\n", self.block_size));
         bufferr.push_str(&format!("\t      liczba bloków: {}\n", self.block_number));
         bufferr.push_str(&format!("\t        last access: {}\n", self.last_access.format("%Y-%m-%d %H:%M:%S")));
-        bufferr.push_str(&format!("\t  last modification: {}\n", self.last_modification.format("%Y-%m-%d %H:%M:%S").to_string()));
-        bufferr.push_str(&format!("\tlast status changed: {}\n", self.last_status_changed.format("%Y-%m-%d %H:%M:%S").to_string()));
+        bufferr.push_str(&format!("\t  last modification: {}\n", self.last_modification.format("%Y-%m-%d %H:%M:%S")));
+        bufferr.push_str(&format!("\tlast status changed: {}\n", self.last_status_changed.format("%Y-%m-%d %H:%M:%S")));
         bufferr.push_str(&format!("\t             owner : {} (uid: {})\n", self.owner_name, self.owner_id));
         bufferr.push_str(&format!("\t              group: {} (gid: {})\n", self.group_name, self.group_id));
         
@@ -40,6 +40,7 @@ pub enum ErrSrc {
     IO,
     Errno,
     Serde,
+    SQLite,
     App
 }
 
@@ -66,7 +67,6 @@ impl Error {
             code,
             msg: msg.to_string(),
             kind: kind.to_string(),
-            ..Default::default()
         }
     }
     
@@ -79,7 +79,7 @@ impl Error {
             Error {
                 src: ErrSrc::Errno,
                 code: errno,
-                msg: message.into(),
+                msg: message,
                 ..Default::default()
             }
         }
@@ -115,7 +115,7 @@ impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
         Error {
             src: ErrSrc::IO,
-            code: err.raw_os_error().unwrap(),
+            code: err.raw_os_error().unwrap_or(-1),
             msg: err.to_string(),
             kind: err.kind().to_string(),
         }
@@ -0,0 +1,169 @@
+//! Proxy SOCKS5 na kanałach sesji: klient SOCKS -> proxy -> kanał -> "serwer"
+//! -> lokalne serwery echo.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use shared::data::answer::Answer;
+use shared::net::connector::{ConnectionSide, Connector};
+use shared::net::forward::{host_port, FORWARD_CMD};
+use shared::net::mux::Mux;
+use shared::net::socks;
+use shared::xerror::Error;
+
+/// Serwer echo, zwraca jego adres.
+fn echo_server(addr: &str) -> SocketAddr {
+    let listener = TcpListener::bind(addr).unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            thread::spawn(move || {
+                io::copy(&mut stream.try_clone().unwrap(), &mut stream).unwrap();
+                let _ = stream.shutdown(Shutdown::Write);
+            });
+        }
+    });
+    addr
+}
+
+/// Sesja (klient, serwer). Serwer łączy się tylko z celami z `allowed`,
+/// jak serwer z polityką `forwarding.allow`.
+fn session(allowed: Vec<String>) -> Arc<Mux> {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut conn = Connector::new(stream, ConnectionSide::Server);
+        conn.init().unwrap();
+        let mux = Mux::new(conn).unwrap();
+        while let Ok(Some(mut channel)) = mux.accept(Duration::from_secs(30)) {
+            let allowed = allowed.clone();
+            thread::spawn(move || {
+                let request = channel.read_request().unwrap();
+                assert_eq!(request.command, FORWARD_CMD);
+                let target = host_port(&request.params[0], request.params[1].parse().unwrap());
+                if !allowed.contains(&target) {
+                    let _ = channel.send_answer(Answer::from(Error::new(libc::EACCES, "not allowed")));
+                    return;
+                }
+                match TcpStream::connect(&target) {
+                    Ok(stream) => {
+                        channel.send_answer(Answer::new(0, "OK", FORWARD_CMD)).unwrap();
+                        let _ = channel.tunnel(stream);
+                    }
+                    Err(e) => {
+                        let _ = channel.send_answer(Answer::from(Error::new(e.raw_os_error().unwrap_or(-1), "connect")));
+                    }
+                }
+            });
+        }
+    });
+    let mut conn = Connector::new(TcpStream::connect(addr).unwrap(), ConnectionSide::Client);
+    conn.init().unwrap();
+    Arc::new(Mux::new(conn).unwrap())
+}
+
+/// Proxy SOCKS na wolnym porcie, zwraca jego adres.
+fn proxy(mux: Arc<Mux>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mux = mux.clone();
+            thread::spawn(move || {
+                let _ = socks::serve(&mux, stream.unwrap());
+            });
+        }
+    });
+    addr
+}
+
+/// Klient SOCKS5: CONNECT do celu zapisanego jako `atyp` + adres.
+/// Zwraca kod odpowiedzi i połączenie.
+fn socks_connect(proxy: SocketAddr, atyp: u8, addr: &[u8], port: u16) -> (u8, TcpStream) {
+    let mut stream = TcpStream::connect(proxy).unwrap();
+    stream.write_all(&[5, 1, 0]).unwrap();
+    let mut method = [0; 2];
+    stream.read_exact(&mut method).unwrap();
+    assert_eq!(method, [5, 0]);
+
+    let mut request = vec![5, 1, 0, atyp];
+    request.extend_from_slice(addr);
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).unwrap();
+    let mut reply = [0; 10];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(reply[0], 5);
+    (reply[1], stream)
+}
+
+fn assert_echo(mut stream: TcpStream) {
+    let data: Vec<u8> = (0..300_000).map(|i| (i % 249) as u8).collect();
+    let mut reader = stream.try_clone().unwrap();
+    let echoed = thread::spawn(move || {
+        let mut echoed = Vec::new();
+        reader.read_to_end(&mut echoed).unwrap();
+        echoed
+    });
+    stream.write_all(&data).unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+    assert!(echoed.join().unwrap() == data);
+}
+
+#[test]
+fn test_socks_connect() {
+    let v4 = echo_server("127.0.0.1:0");
+    let named = echo_server("127.0.0.1:0");
+    let v6 = TcpListener::bind("[::1]:0").is_ok().then(|| echo_server("[::1]:0"));
+    let mut allowed = vec![v4.to_string(), format!("localhost:{}", named.port())];
+    allowed.extend(v6.map(|addr| addr.to_string()));
+    let proxy = proxy(session(allowed));
+
+    // IPv4.
+    let (code, stream) = socks_connect(proxy, 1, &[127, 0, 0, 1], v4.port());
+    assert_eq!(code, socks::SUCCEEDED);
+    assert_echo(stream);
+
+    // Nazwa hosta - rozwiązywana po stronie serwera.
+    let name = b"localhost";
+    let mut addr = vec![name.len() as u8];
+    addr.extend_from_slice(name);
+    let (code, stream) = socks_connect(proxy, 3, &addr, named.port());
+    assert_eq!(code, socks::SUCCEEDED);
+    assert_echo(stream);
+
+    // IPv6 (jeśli jest dostępne).
+    if let Some(v6) = v6 {
+        let (code, stream) = socks_connect(proxy, 4, &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1], v6.port());
+        assert_eq!(code, socks::SUCCEEDED);
+        assert_echo(stream);
+    }
+
+    // Cel spoza polityki serwera.
+    let (code, _) = socks_connect(proxy, 1, &[127, 0, 0, 1], named.port());
+    assert_eq!(code, socks::NOT_ALLOWED);
+}
+
+#[test]
+fn test_socks_unsupported() {
+    let proxy = proxy(session(vec![]));
+
+    // Tylko uwierzytelnianie hasłem - brak wspólnej metody.
+    let mut stream = TcpStream::connect(proxy).unwrap();
+    stream.write_all(&[5, 1, 2]).unwrap();
+    let mut method = [0; 2];
+    stream.read_exact(&mut method).unwrap();
+    assert_eq!(method, [5, 0xff]);
+
+    // BIND nie jest obsługiwane.
+    let mut stream = TcpStream::connect(proxy).unwrap();
+    stream.write_all(&[5, 1, 0]).unwrap();
+    stream.read_exact(&mut method).unwrap();
+    stream.write_all(&[5, 2, 0, 1, 127, 0, 0, 1, 0, 80]).unwrap();
+    let mut reply = [0; 10];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(reply[1], socks::COMMAND_NOT_SUPPORTED);
+}